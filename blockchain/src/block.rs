@@ -1,9 +1,9 @@
-use std::{fmt};
+use std::{fmt, cmp};
 use std::collections::LinkedList;
 use wallet_crypto::cbor::{ExtendedResult};
 use wallet_crypto::{cbor};
 
-use types::{HeaderHash, SlotId, EpochId};
+use types::{HeaderHash, SlotId, EpochId, ChainDifficulty};
 use genesis;
 use normal;
 
@@ -45,13 +45,13 @@ impl RawBlock {
 impl AsRef<[u8]> for RawBlockHeader { fn as_ref(&self) -> &[u8] { self.0.as_ref() } }
 impl AsRef<[u8]> for RawBlock { fn as_ref(&self) -> &[u8] { self.0.as_ref() } }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BlockHeader {
     GenesisBlockHeader(genesis::BlockHeader),
     MainBlockHeader(normal::BlockHeader),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BlockDate {
     Genesis(EpochId),
     Normal(SlotId),
@@ -72,6 +72,22 @@ impl BlockDate {
     }
 }
 
+impl PartialOrd for BlockDate {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for BlockDate {
+    /// the epoch boundary block comes before the first slot of its epoch
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        fn key(date: &BlockDate) -> (EpochId, Option<u32>) {
+            match date {
+                &BlockDate::Genesis(e) => (e, None),
+                &BlockDate::Normal(ref s) => (s.epoch, Some(s.slotid)),
+            }
+        }
+        key(self).cmp(&key(other))
+    }
+}
+
 impl fmt::Display for BlockDate {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -100,6 +116,13 @@ impl BlockHeader {
         self.get_blockdate()
     }
 
+    pub fn get_chain_difficulty(&self) -> ChainDifficulty {
+        match self {
+            &BlockHeader::GenesisBlockHeader(ref blo) => blo.consensus.chain_difficulty,
+            &BlockHeader::MainBlockHeader(ref blo) => blo.consensus.chain_difficulty,
+        }
+    }
+
     pub fn is_genesis_block(&self) -> bool {
         match self {
             &BlockHeader::GenesisBlockHeader(_) => true,
@@ -130,12 +153,16 @@ impl fmt::Display for BlockHeader {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum Block {
     GenesisBlock(genesis::Block),
     MainBlock(normal::Block),
 }
 impl Block {
+    pub fn to_raw(&self) -> RawBlock {
+        RawBlock(cbor::encode_to_cbor(self).unwrap())
+    }
+
     pub fn get_header(&self) -> BlockHeader {
         match self {
             &Block::GenesisBlock(ref blk) => BlockHeader::GenesisBlockHeader(blk.header.clone()),
@@ -160,7 +187,18 @@ impl fmt::Display for Block {
 
 impl cbor::CborValue for Block {
     fn encode(&self) -> cbor::Value {
-        unimplemented!()
+        match self {
+            &Block::GenesisBlock(ref blk) => {
+                cbor::Value::Array(
+                   vec![cbor::Value::U64(0), cbor::CborValue::encode(blk)]
+                )
+            },
+            &Block::MainBlock(ref blk) => {
+                cbor::Value::Array(
+                   vec![cbor::Value::U64(1), cbor::CborValue::encode(blk)]
+                )
+            },
+        }
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
         value.array().and_then(|array| {
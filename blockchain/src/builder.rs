@@ -0,0 +1,230 @@
+//! construction of new blocks
+//!
+//! Mainly useful to generate chains for tests and development networks:
+//! the main blocks are built with empty SSC, delegation and update
+//! payloads and are signed directly by the slot leader.
+//!
+
+use wallet_crypto::{cbor, hdwallet, tx, hash::{Blake2b256}};
+use wallet_crypto::address::{StakeholderId};
+use wallet_crypto::config::{ProtocolMagic};
+
+use types::{HeaderHash, HeaderExtraData, BlockHeaderAttributes, BlockVersion, SoftwareVersion, SlotId, EpochId, ChainDifficulty, SscProof};
+use block::{BlockHeader, Block, RawBlock};
+use genesis;
+use normal;
+
+/// the extra body data, with no attributes
+fn extra_data() -> cbor::Value {
+    cbor::Value::Array(vec![ cbor::CborValue::encode(&BlockHeaderAttributes::default()) ])
+}
+
+fn hash_value(value: &cbor::Value) -> Blake2b256 {
+    Blake2b256::new(&cbor::encode_to_cbor(value).unwrap())
+}
+
+/// builder of a main block, signed by the slot leader
+#[derive(Debug, Clone)]
+pub struct BlockBuilder {
+    protocol_magic: ProtocolMagic,
+    previous_header: HeaderHash,
+    chain_difficulty: ChainDifficulty,
+    slot_id: SlotId,
+    leader: hdwallet::XPrv,
+    txs: normal::TxPayload,
+    block_version: BlockVersion,
+    software_version: SoftwareVersion,
+}
+impl BlockBuilder {
+    /// the block of `slot_id`, following the `previous` header
+    pub fn new(protocol_magic: ProtocolMagic, previous: &BlockHeader, slot_id: SlotId, leader: hdwallet::XPrv, txs: normal::TxPayload) -> Self {
+        let difficulty = u64::from(previous.get_chain_difficulty()) + 1;
+        BlockBuilder {
+            protocol_magic: protocol_magic,
+            previous_header: previous.compute_hash(),
+            chain_difficulty: ChainDifficulty::from(difficulty),
+            slot_id: slot_id,
+            leader: leader,
+            txs: txs,
+            block_version: BlockVersion::default(),
+            software_version: SoftwareVersion::default(),
+        }
+    }
+
+    pub fn block_version(&mut self, block_version: BlockVersion) -> &mut Self {
+        self.block_version = block_version;
+        self
+    }
+
+    pub fn software_version(&mut self, software_version: SoftwareVersion) -> &mut Self {
+        self.software_version = software_version;
+        self
+    }
+
+    pub fn make(&self) -> normal::Block {
+        let vss_certificates = cbor::Value::Tag(258, Box::new(cbor::Value::Array(vec![])));
+        let body = normal::Body::new(
+            self.txs.clone(),
+            cbor::Value::Array(vec![ cbor::Value::U64(3), vss_certificates.clone() ]),
+            cbor::Value::Array(vec![]),
+            cbor::Value::Array(vec![ cbor::Value::Array(vec![]), cbor::Value::Array(vec![]) ]),
+        );
+        let extra = extra_data();
+
+        let body_proof = normal::BodyProof::new(
+            tx::TxProof::generate(body.tx.iter()),
+            SscProof::Certificate(hash_value(&vss_certificates)),
+            hash_value(&body.delegation),
+            hash_value(&body.update),
+        );
+        let consensus = normal::Consensus {
+            slot_id: self.slot_id.clone(),
+            leader_key: self.leader.public(),
+            chain_difficulty: self.chain_difficulty,
+            block_signature: normal::BlockSignature::Signature(hdwallet::Signature::from_bytes([0; hdwallet::SIGNATURE_SIZE])),
+        };
+        let extra_data = HeaderExtraData::new(
+            self.block_version.clone(),
+            self.software_version.clone(),
+            BlockHeaderAttributes::default(),
+            hash_value(&extra),
+        );
+        let mut header = normal::BlockHeader::new(self.protocol_magic, self.previous_header.clone(), body_proof, consensus, extra_data);
+        header.sign(&self.leader);
+
+        normal::Block::new(header, body, extra)
+    }
+
+    pub fn make_raw(&self) -> RawBlock {
+        Block::MainBlock(self.make()).to_raw()
+    }
+}
+
+/// builder of an epoch boundary block
+#[derive(Debug, Clone)]
+pub struct GenesisBlockBuilder {
+    protocol_magic: ProtocolMagic,
+    previous_header: HeaderHash,
+    chain_difficulty: ChainDifficulty,
+    epoch: EpochId,
+    slot_leaders: Vec<StakeholderId>,
+}
+impl GenesisBlockBuilder {
+    /// the first block of the chain, following the `genesis_prev` hash
+    pub fn first(protocol_magic: ProtocolMagic, genesis_prev: &HeaderHash, slot_leaders: Vec<StakeholderId>) -> Self {
+        GenesisBlockBuilder {
+            protocol_magic: protocol_magic,
+            previous_header: genesis_prev.clone(),
+            chain_difficulty: ChainDifficulty::from(0),
+            epoch: 0,
+            slot_leaders: slot_leaders,
+        }
+    }
+
+    /// the boundary block of the epoch following the one of `previous`
+    pub fn new(protocol_magic: ProtocolMagic, previous: &BlockHeader, slot_leaders: Vec<StakeholderId>) -> Self {
+        GenesisBlockBuilder {
+            protocol_magic: protocol_magic,
+            previous_header: previous.compute_hash(),
+            chain_difficulty: previous.get_chain_difficulty(),
+            epoch: previous.get_blockdate().get_epochid() + 1,
+            slot_leaders: slot_leaders,
+        }
+    }
+
+    pub fn make(&self) -> genesis::Block {
+        let body = genesis::Body { slot_leaders: self.slot_leaders.clone() };
+        let consensus = genesis::Consensus { epoch: self.epoch, chain_difficulty: self.chain_difficulty };
+        let header = genesis::BlockHeader::new(
+            self.protocol_magic,
+            self.previous_header.clone(),
+            genesis::BodyProof::generate_from_body(&body),
+            consensus,
+            genesis::HeaderExtraData::default(),
+        );
+        genesis::Block { header: header, body: body, extra: extra_data() }
+    }
+
+    pub fn make_raw(&self) -> RawBlock {
+        Block::GenesisBlock(self.make()).to_raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wallet_crypto::address::{ExtendedAddr, AddrType, SpendingData, Attributes};
+    use wallet_crypto::coin::{Coin};
+    use wallet_crypto::config::{Config};
+    use validation::{Validator, Parameters};
+
+    fn leader() -> hdwallet::XPrv {
+        hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([0;32]))
+    }
+
+    fn txaux(key: &hdwallet::XPrv) -> tx::TxAux {
+        let pk = key.public();
+        let addr = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk.clone()), Attributes::new_bootstrap_era(None));
+        let mut tx = tx::Tx::new();
+        tx.add_input(tx::TxIn::new(tx::TxId::new(&addr.to_bytes()), 0));
+        tx.add_output(tx::TxOut::new(addr, Coin::new(1000).unwrap()));
+        let witness = tx::TxInWitness::new(&Config::new(ProtocolMagic::new(0)), key, &tx);
+        tx::TxAux::new(tx, vec![witness])
+    }
+
+    #[test]
+    fn build_valid_chain() {
+        let pm = ProtocolMagic::new(0);
+        let genesis_prev = HeaderHash::new(b"genesis");
+        let key = leader();
+        let leaders = vec![StakeholderId::new(&key.public()); 10];
+        let params = Parameters { epoch_slots: 10, .. Parameters::new(pm) };
+        let mut validator = Validator::new(params, genesis_prev.clone());
+
+        let ebb = GenesisBlockBuilder::first(pm, &genesis_prev, leaders.clone()).make_raw();
+        let mut previous = validator.validate_block(&ebb).unwrap().get_header();
+
+        for slotid in &[0, 1, 5] {
+            let txs = normal::TxPayload::new(::std::iter::once(txaux(&key)).collect());
+            let slot = SlotId { epoch: 0, slotid: *slotid };
+            let raw = BlockBuilder::new(pm, &previous, slot, key.clone(), txs).make_raw();
+            previous = validator.validate_block(&raw).unwrap().get_header();
+        }
+        assert_eq!(previous.get_chain_difficulty(), ChainDifficulty::from(3));
+
+        let ebb = GenesisBlockBuilder::new(pm, &previous, leaders).make_raw();
+        let blk = validator.validate_block(&ebb).unwrap();
+        assert_eq!(blk.get_header().get_chain_difficulty(), ChainDifficulty::from(3));
+        assert_eq!(blk.get_header().get_blockdate().get_epochid(), 1);
+    }
+
+    #[test]
+    fn built_block_roundtrip() {
+        let pm = ProtocolMagic::new(0);
+        let genesis = GenesisBlockBuilder::first(pm, &HeaderHash::new(b"genesis"), vec![]);
+        let previous = BlockHeader::GenesisBlockHeader(genesis.make().header);
+        let raw = BlockBuilder::new(pm, &previous, SlotId { epoch: 0, slotid: 0 }, leader(), normal::TxPayload::empty()).make_raw();
+        let blk = raw.decode().unwrap();
+        assert_eq!(blk.to_raw().as_ref(), raw.as_ref());
+    }
+
+    #[test]
+    fn built_block_json() {
+        let pm = ProtocolMagic::new(0);
+        let key = leader();
+        let genesis = GenesisBlockBuilder::first(pm, &HeaderHash::new(b"genesis"), vec![StakeholderId::new(&key.public())]);
+        let previous = BlockHeader::GenesisBlockHeader(genesis.make().header);
+        let txaux = txaux(&key);
+        let txs = normal::TxPayload::new(::std::iter::once(txaux.clone()).collect());
+        let blk = Block::MainBlock(BlockBuilder::new(pm, &previous, SlotId { epoch: 0, slotid: 2 }, key, txs).make());
+
+        let json = ::serde_json::to_value(&blk).unwrap();
+        let hdr = &json["MainBlock"]["header"];
+        assert_eq!(hdr["previous_header"], format!("{}", previous.compute_hash()));
+        assert_eq!(hdr["consensus"]["slot_id"]["slotid"], 2);
+        assert_eq!(hdr["extra_data"]["block_version"], "0.1.0");
+        let tx = &json["MainBlock"]["body"]["tx"][0]["tx"];
+        assert_eq!(tx["outputs"][0]["address"], format!("{}", txaux.tx.outputs.front().unwrap().address));
+        assert_eq!(json["MainBlock"]["extra"], "81a0");
+    }
+}
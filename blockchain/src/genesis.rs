@@ -1,14 +1,21 @@
 use wallet_crypto::{cbor, hash::{Blake2b256}};
+use wallet_crypto::address::{StakeholderId};
 use wallet_crypto::cbor::{ExtendedResult};
 use wallet_crypto::config::{ProtocolMagic};
 use std::{fmt};
-use std::collections::{LinkedList};
 
 use types;
 use types::{HeaderHash, ChainDifficulty};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub struct BodyProof(Blake2b256);
+impl BodyProof {
+    /// the proof of a genesis block's body is the hash of its slot leaders
+    pub fn generate_from_body(body: &Body) -> Self {
+        let bytes = cbor::encode_to_cbor(body).unwrap();
+        BodyProof(Blake2b256::new(&bytes))
+    }
+}
 
 impl cbor::CborValue for BodyProof {
     fn encode(&self) -> cbor::Value {
@@ -19,10 +26,16 @@ impl cbor::CborValue for BodyProof {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Body {
-    //pub slot_leaders: Vec<tx::Hash>
-    pub slot_leaders: LinkedList<cbor::Value>,
+    /// the stakeholder expected to issue the block of each slot of the epoch
+    pub slot_leaders: Vec<StakeholderId>,
+}
+impl Body {
+    /// the stakeholder scheduled to issue the block of the given slot of the epoch
+    pub fn get_slot_leader(&self, slotid: u32) -> Option<&StakeholderId> {
+        self.slot_leaders.get(slotid as usize)
+    }
 }
 /*
 impl fmt::Display for Body {
@@ -33,22 +46,26 @@ impl fmt::Display for Body {
 */
 impl cbor::CborValue for Body {
     fn encode(&self) -> cbor::Value {
-        unimplemented!()
+        cbor::Value::IArray(self.slot_leaders.iter().map(cbor::CborValue::encode).collect())
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
         value.iarray().and_then(|array| {
-            Ok(Body { slot_leaders: array })
+            let mut slot_leaders = Vec::with_capacity(array.len());
+            for leader in array {
+                slot_leaders.push(cbor::CborValue::decode(leader)?);
+            }
+            Ok(Body { slot_leaders: slot_leaders })
         }).embed("While decoding genesis::Body")
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BlockHeader {
     pub protocol_magic: ProtocolMagic,
     pub previous_header: HeaderHash,
     pub body_proof: BodyProof,
     pub consensus: Consensus,
-    pub extra_data: types::BlockHeaderAttributes,
+    pub extra_data: HeaderExtraData,
 }
 impl fmt::Display for BlockHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -60,7 +77,7 @@ impl fmt::Display for BlockHeader {
     }
 }
 impl BlockHeader {
-    pub fn new(pm: ProtocolMagic, pb: HeaderHash, bp: BodyProof, c: Consensus, ed: types::BlockHeaderAttributes) -> Self {
+    pub fn new(pm: ProtocolMagic, pb: HeaderHash, bp: BodyProof, c: Consensus, ed: HeaderExtraData) -> Self {
         BlockHeader {
             protocol_magic: pm,
             previous_header: pb,
@@ -93,10 +110,28 @@ impl cbor::CborValue for BlockHeader {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeaderExtraData {
+    pub attributes: types::BlockHeaderAttributes,
+}
+impl cbor::CborValue for HeaderExtraData {
+    fn encode(&self) -> cbor::Value {
+        cbor::Value::Array(vec![ cbor::CborValue::encode(&self.attributes) ])
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.array().and_then(|array| {
+            let (array, attributes) = cbor::array_decode_elem(array, 0).embed("attributes")?;
+            if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+            Ok(HeaderExtraData { attributes: attributes })
+        }).embed("While decoding a genesis::HeaderExtraData")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Block {
     pub header: BlockHeader,
     pub body: Body,
+    #[serde(serialize_with = "types::serialize_cbor_value")]
     pub extra: cbor::Value
 }
 
@@ -108,7 +143,11 @@ impl fmt::Display for Block {
 }
 impl cbor::CborValue for Block {
     fn encode(&self) -> cbor::Value {
-        unimplemented!()
+        cbor::Value::Array(vec![
+            cbor::CborValue::encode(&self.header),
+            cbor::CborValue::encode(&self.body),
+            self.extra.clone(),
+        ])
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
         value.array().and_then(|array| {
@@ -121,7 +160,7 @@ impl cbor::CborValue for Block {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Consensus {
     pub epoch: u32,
     pub chain_difficulty: ChainDifficulty,
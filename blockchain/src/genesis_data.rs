@@ -0,0 +1,444 @@
+//! network genesis data
+//!
+//! The genesis data file (the "genesis JSON") defines the initial state of
+//! a network: the initial balances (AVVM and non-AVVM), the boot
+//! stakeholders, the heavy weight delegation and the protocol constants.
+//!
+//! Its hash is the `genesis_prev` hash of the network: the hash the first
+//! epoch boundary block links to.
+//!
+
+use std::collections::BTreeMap;
+use std::{fmt, result};
+
+use base64;
+use serde_json;
+use wallet_crypto::{hdwallet, redeem};
+use wallet_crypto::address::{ExtendedAddr, StakeholderId};
+use wallet_crypto::coin::{Coin};
+use wallet_crypto::config::{ProtocolMagic, RequiresNetworkMagic, Config};
+use wallet_crypto::tx::{TxIn, TxOut, TxId};
+use wallet_crypto::tx::fee::{LinearFee, Coeff};
+use wallet_crypto::tx::validation as tx_validation;
+use wallet_crypto::util::{base58, hex};
+
+use types::{HeaderHash};
+use normal::{ProxySecretKey, HeavyWeightDelegation};
+use validation;
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    /// the hash of the genesis data (second) is not the expected one (first)
+    WrongGenesisHash(HeaderHash, HeaderHash),
+    /// the value of the given field cannot be interpreted
+    InvalidValue(&'static str, String),
+    /// the heavy weight delegation certificate of the stakeholder is invalid
+    InvalidDelegation(StakeholderId),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::JsonError(ref err) => write!(f, "invalid genesis data: {}", err),
+            &Error::WrongGenesisHash(ref expected, ref got) => write!(f, "wrong genesis data hash, expected {} but got {}", expected, got),
+            &Error::InvalidValue(field, ref value) => write!(f, "invalid {} in genesis data: {}", field, value),
+            &Error::InvalidDelegation(ref stakeholder) => write!(f, "invalid heavy delegation certificate for stakeholder {}", stakeholder),
+        }
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::JsonError(e) }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// protocol constants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolConstants {
+    /// the security parameter: the maximum number of blocks a rollback can go back
+    pub k: u64,
+    pub protocol_magic: ProtocolMagic,
+    /// do the addresses of the network carry its protocol magic
+    pub requires_network_magic: RequiresNetworkMagic,
+    pub vss_min_ttl: u32,
+    pub vss_max_ttl: u32,
+}
+impl ProtocolConstants {
+    /// number of slots in an epoch
+    pub fn epoch_slots(&self) -> u32 { (self.k * 10) as u32 }
+
+    /// the configuration of the network (its protocol magic)
+    pub fn config(&self) -> Config {
+        Config::new_with_network_magic(self.protocol_magic, self.requires_network_magic)
+    }
+}
+
+/// linear transaction fee policy: `summand + multiplier * size`
+///
+/// the coefficients are expressed in nano-lovelace (10^-9 lovelace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxFeePolicy {
+    pub summand: u64,
+    pub multiplier: u64,
+}
+impl TxFeePolicy {
+    /// the fee algorithm of the policy
+    pub fn linear_fee(&self) -> LinearFee {
+        LinearFee::new(Coeff::from_nanos(self.summand), Coeff::from_nanos(self.multiplier))
+    }
+}
+
+/// the initial block version data (the updatable protocol parameters)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockVersionData {
+    pub max_block_size: usize,
+    pub max_header_size: usize,
+    pub max_tx_size: usize,
+    /// duration of a slot, in milliseconds
+    pub slot_duration: u64,
+    pub tx_fee_policy: TxFeePolicy,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenesisData {
+    /// the hash of the genesis data, the `genesis_prev` of the network
+    pub genesis_prev: HeaderHash,
+    /// the start of the first epoch, in seconds since the unix epoch
+    pub start_time: u64,
+    pub protocol_constants: ProtocolConstants,
+    pub block_version_data: BlockVersionData,
+    /// the initial balances of the redeem (AVVM) keys
+    pub avvm_distr: BTreeMap<redeem::PublicKey, Coin>,
+    /// the initial balances of regular addresses
+    pub non_avvm_balances: BTreeMap<ExtendedAddr, Coin>,
+    /// the stakeholders of the bootstrap era, with their weight
+    pub boot_stakeholders: BTreeMap<StakeholderId, u32>,
+    pub heavy_delegation: BTreeMap<StakeholderId, ProxySecretKey<HeavyWeightDelegation>>,
+}
+impl GenesisData {
+    /// the validation parameters of the network
+    pub fn validation_parameters(&self) -> validation::Parameters {
+        validation::Parameters {
+            protocol_magic: self.protocol_constants.protocol_magic,
+            epoch_slots: self.protocol_constants.epoch_slots(),
+            max_header_size: self.block_version_data.max_header_size,
+            max_block_size: self.block_version_data.max_block_size,
+        }
+    }
+
+    /// the limits of the transactions of the network
+    pub fn tx_validation_parameters(&self) -> tx_validation::Parameters {
+        tx_validation::Parameters { max_tx_size: self.block_version_data.max_tx_size, .. tx_validation::Parameters::default() }
+    }
+
+    /// the initial UTxOs: every initial balance is spendable from the
+    /// output `0` of a pseudo transaction identified by the hash of
+    /// the address.
+    pub fn initial_utxos(&self) -> BTreeMap<TxIn, TxOut> {
+        let config = self.protocol_constants.config();
+        let avvm = self.avvm_distr.iter().map(|(pk, value)| {
+            (redeem_address(&config, pk), *value)
+        });
+        let non_avvm = self.non_avvm_balances.iter().map(|(addr, value)| {
+            (addr.clone(), *value)
+        });
+        avvm.chain(non_avvm).map(|(addr, value)| {
+            let txin = TxIn::new(TxId::new(&addr.to_bytes()), 0);
+            (txin, TxOut::new(addr, value))
+        }).collect()
+    }
+}
+
+/// the address of the redeem key on the given network: the AVVM
+/// balances are redeemed from it
+pub fn redeem_address(config: &Config, pk: &redeem::PublicKey) -> ExtendedAddr {
+    ExtendedAddr::new_redeem(pk, config.address_protocol_magic())
+}
+
+/// parse the genesis data, checking its hash is the expected `genesis_prev`
+pub fn parse(json: &[u8], genesis_prev: &HeaderHash) -> Result<GenesisData> {
+    let hash = HeaderHash::new(json);
+    if &hash != genesis_prev {
+        return Err(Error::WrongGenesisHash(genesis_prev.clone(), hash));
+    }
+    let raw : raw::Genesis = serde_json::from_slice(json)?;
+
+    let (protocol_magic, requires_network_magic) = match raw.protocol_consts.protocol_magic {
+        raw::ProtocolMagic::Legacy(pm) => (pm, RequiresNetworkMagic::default()),
+        raw::ProtocolMagic::WithNetworkMagic { pm, requires_network_magic } => (pm, requires_network_magic),
+    };
+    let protocol_constants = ProtocolConstants {
+        k: raw.protocol_consts.k,
+        protocol_magic: ProtocolMagic::new(protocol_magic),
+        requires_network_magic: requires_network_magic,
+        vss_min_ttl: raw.protocol_consts.vss_min_ttl,
+        vss_max_ttl: raw.protocol_consts.vss_max_ttl,
+    };
+    let bvd = &raw.block_version_data;
+    let block_version_data = BlockVersionData {
+        max_block_size: parse_int("maxBlockSize", &bvd.max_block_size)? as usize,
+        max_header_size: parse_int("maxHeaderSize", &bvd.max_header_size)? as usize,
+        max_tx_size: parse_int("maxTxSize", &bvd.max_tx_size)? as usize,
+        slot_duration: parse_int("slotDuration", &bvd.slot_duration)?,
+        tx_fee_policy: TxFeePolicy {
+            summand: parse_int("txFeePolicy.summand", &bvd.tx_fee_policy.summand)?,
+            multiplier: parse_int("txFeePolicy.multiplier", &bvd.tx_fee_policy.multiplier)?,
+        },
+    };
+
+    let mut avvm_distr = BTreeMap::new();
+    for (key, value) in raw.avvm_distr.iter() {
+        let pk = base64::decode_config(key, base64::URL_SAFE).ok()
+            .and_then(|bytes| redeem::PublicKey::from_slice(&bytes).ok())
+            .ok_or_else(|| Error::InvalidValue("avvmDistr key", key.clone()))?;
+        avvm_distr.insert(pk, parse_coin(value)?);
+    }
+
+    let mut non_avvm_balances = BTreeMap::new();
+    for (addr, value) in raw.non_avvm_balances.iter() {
+        let address = base58::decode(addr).ok()
+            .and_then(|bytes| ExtendedAddr::from_bytes(&bytes).ok())
+            .ok_or_else(|| Error::InvalidValue("nonAvvmBalances address", addr.clone()))?;
+        non_avvm_balances.insert(address, parse_coin(value)?);
+    }
+
+    let mut boot_stakeholders = BTreeMap::new();
+    for (id, weight) in raw.boot_stakeholders.iter() {
+        boot_stakeholders.insert(parse_stakeholder_id(id)?, *weight);
+    }
+
+    let mut heavy_delegation = BTreeMap::new();
+    for (id, dlg) in raw.heavy_delegation.iter() {
+        let stakeholder = parse_stakeholder_id(id)?;
+        let psk = ProxySecretKey {
+            omega: dlg.omega,
+            issuer_pk: parse_xpub("heavyDelegation issuerPk", &dlg.issuer_pk)?,
+            delegate_pk: parse_xpub("heavyDelegation delegatePk", &dlg.delegate_pk)?,
+            cert: hdwallet::Signature::from_hex(&dlg.cert)
+                .map_err(|_| Error::InvalidValue("heavyDelegation cert", dlg.cert.clone()))?,
+        };
+        if StakeholderId::new(&psk.issuer_pk) != stakeholder || ! psk.verify(protocol_constants.protocol_magic) {
+            return Err(Error::InvalidDelegation(stakeholder));
+        }
+        heavy_delegation.insert(stakeholder, psk);
+    }
+
+    Ok(GenesisData {
+        genesis_prev: hash,
+        start_time: raw.start_time,
+        protocol_constants: protocol_constants,
+        block_version_data: block_version_data,
+        avvm_distr: avvm_distr,
+        non_avvm_balances: non_avvm_balances,
+        boot_stakeholders: boot_stakeholders,
+        heavy_delegation: heavy_delegation,
+    })
+}
+
+fn parse_int(field: &'static str, value: &str) -> Result<u64> {
+    value.parse().map_err(|_| Error::InvalidValue(field, value.to_string()))
+}
+fn parse_coin(value: &str) -> Result<Coin> {
+    parse_int("balance", value).ok().and_then(|v| Coin::new(v).ok())
+        .ok_or_else(|| Error::InvalidValue("balance", value.to_string()))
+}
+fn parse_stakeholder_id(value: &str) -> Result<StakeholderId> {
+    hex::decode(value).ok().and_then(|bytes| StakeholderId::from_slice(&bytes))
+        .ok_or_else(|| Error::InvalidValue("stakeholder id", value.to_string()))
+}
+fn parse_xpub(field: &'static str, value: &str) -> Result<hdwallet::XPub> {
+    base64::decode(value).ok().and_then(|bytes| hdwallet::XPub::from_slice(&bytes).ok())
+        .ok_or_else(|| Error::InvalidValue(field, value.to_string()))
+}
+
+/// the layout of the genesis JSON file
+mod raw {
+    use std::collections::BTreeMap;
+    use wallet_crypto::config::{RequiresNetworkMagic};
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Genesis {
+        pub avvm_distr: BTreeMap<String, String>,
+        pub non_avvm_balances: BTreeMap<String, String>,
+        pub boot_stakeholders: BTreeMap<String, u32>,
+        pub heavy_delegation: BTreeMap<String, HeavyDelegation>,
+        pub protocol_consts: ProtocolConsts,
+        pub block_version_data: BlockVersionData,
+        pub start_time: u64,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct HeavyDelegation {
+        pub omega: u64,
+        pub issuer_pk: String,
+        pub delegate_pk: String,
+        pub cert: String,
+    }
+
+    /// the protocol magic, with whether the addresses carry it. The
+    /// legacy format (a plain number, as in the mainnet's genesis) does
+    /// not say: the addresses are taken not to carry it.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum ProtocolMagic {
+        #[serde(rename_all = "camelCase")]
+        WithNetworkMagic { pm: u32, requires_network_magic: RequiresNetworkMagic },
+        Legacy(u32),
+    }
+
+    #[derive(Deserialize)]
+    pub struct ProtocolConsts {
+        pub k: u64,
+        #[serde(rename = "protocolMagic")]
+        pub protocol_magic: ProtocolMagic,
+        #[serde(rename = "vssMinTTL")]
+        pub vss_min_ttl: u32,
+        #[serde(rename = "vssMaxTTL")]
+        pub vss_max_ttl: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BlockVersionData {
+        pub max_block_size: String,
+        pub max_header_size: String,
+        pub max_tx_size: String,
+        pub slot_duration: String,
+        pub tx_fee_policy: TxFeePolicy,
+    }
+
+    #[derive(Deserialize)]
+    pub struct TxFeePolicy {
+        pub summand: String,
+        pub multiplier: String,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wallet_crypto::address::{AddrType, SpendingData, Attributes};
+
+    const ISSUER: &str = "301604045de9138b8b23b6730495f7e34b5151d29ba3456bc9b332f6f084a551d646bc30cf126fa8ed776c05a8932a5ab35c8bac41eb01bb9a16cfe229b94b405d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b12";
+
+    fn genesis_json(addr: &ExtendedAddr, issuer: &hdwallet::XPrv, cert: &str) -> String {
+        let delegate = issuer.derive(hdwallet::DerivationScheme::V2, 0);
+        format!(r#"{{
+  "avvmDistr": {{ "-0BJDi-gauylk4LptQTgjMeo7kY9lTCbZv12vwOSTZk=": "9999300000000" }},
+  "nonAvvmBalances": {{ "{}": "1000000" }},
+  "bootStakeholders": {{ "{}": 1 }},
+  "heavyDelegation": {{ "{}": {{ "omega": 0, "issuerPk": "{}", "delegatePk": "{}", "cert": "{}" }} }},
+  "protocolConsts": {{ "k": 2160, "protocolMagic": 764824073, "vssMinTTL": 2, "vssMaxTTL": 6 }},
+  "blockVersionData": {{ "maxBlockSize": "2000000", "maxHeaderSize": "2000000", "maxTxSize": "4096", "slotDuration": "20000",
+                        "txFeePolicy": {{ "summand": "155381000000000", "multiplier": "43946000000" }} }},
+  "startTime": 1506203091
+}}"#, addr, StakeholderId::new(&issuer.public()), StakeholderId::new(&issuer.public()),
+      base64::encode(issuer.public().as_ref()), base64::encode(delegate.public().as_ref()), cert)
+    }
+
+    fn certify(issuer: &hdwallet::XPrv, pm: ProtocolMagic) -> String {
+        let delegate = issuer.derive(hdwallet::DerivationScheme::V2, 0).public();
+        let buf = ProxySecretKey::signed_data(pm, &delegate, &0u64);
+        hex::encode(issuer.sign::<()>(&buf).as_ref())
+    }
+
+    fn setup() -> (ExtendedAddr, hdwallet::XPrv) {
+        let issuer = hdwallet::XPrv::from_hex(ISSUER).unwrap();
+        let pk = issuer.derive(hdwallet::DerivationScheme::V2, 1).public();
+        let addr = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk.clone()), Attributes::new_bootstrap_era(None));
+        (addr, issuer)
+    }
+
+    #[test]
+    fn parse_genesis_data() {
+        let (addr, issuer) = setup();
+        let json = genesis_json(&addr, &issuer, &certify(&issuer, ProtocolMagic::new(764824073)));
+        let genesis = parse(json.as_bytes(), &HeaderHash::new(json.as_bytes())).unwrap();
+
+        assert_eq!(genesis.protocol_constants.protocol_magic, ProtocolMagic::new(764824073));
+        assert_eq!(genesis.validation_parameters().epoch_slots, 21600);
+        assert_eq!(genesis.tx_validation_parameters(), ::wallet_crypto::tx::validation::Parameters::default());
+        assert_eq!(genesis.block_version_data.tx_fee_policy, TxFeePolicy { summand: 155381000000000, multiplier: 43946000000 });
+        assert_eq!(genesis.block_version_data.tx_fee_policy.linear_fee(), LinearFee::default());
+        assert_eq!(genesis.boot_stakeholders.get(&StakeholderId::new(&issuer.public())), Some(&1));
+        assert_eq!(genesis.heavy_delegation.len(), 1);
+
+        let utxos = genesis.initial_utxos();
+        assert_eq!(utxos.len(), 2);
+        let txin = TxIn::new(TxId::new(&addr.to_bytes()), 0);
+        assert_eq!(utxos.get(&txin), Some(&TxOut::new(addr, Coin::new(1000000).unwrap())));
+        let redeem = utxos.values().find(|txout| txout.address.addr_type == AddrType::ATRedeem).unwrap();
+        assert_eq!(redeem.value, Coin::new(9999300000000).unwrap());
+        assert_eq!(redeem.address.attributes.protocol_magic, None);
+    }
+
+    #[test]
+    fn parse_genesis_data_network_magic() {
+        let (addr, issuer) = setup();
+        let pm = ProtocolMagic::new(1097911063);
+        let json = genesis_json(&addr, &issuer, &certify(&issuer, pm))
+            .replace(r#""protocolMagic": 764824073"#, r#""protocolMagic": { "pm": 1097911063, "requiresNetworkMagic": "RequiresMagic" }"#);
+        let genesis = parse(json.as_bytes(), &HeaderHash::new(json.as_bytes())).unwrap();
+        let config = genesis.protocol_constants.config();
+        assert_eq!(config.address_protocol_magic(), Some(pm));
+
+        // the redeem addresses are the ones of the network
+        let utxos = genesis.initial_utxos();
+        let redeem = utxos.values().find(|txout| txout.address.addr_type == AddrType::ATRedeem).unwrap();
+        assert_eq!(redeem.address.attributes.protocol_magic, Some(pm));
+        assert!(redeem.address.is_for_network(&config));
+    }
+
+    // an excerpt of the mainnet genesis data, with one of its heavy weight
+    // delegations (as found in the headers of the mainnet's blocks)
+    const MAINNET_EXCERPT: &str = r#"{
+  "avvmDistr": {},
+  "nonAvvmBalances": {},
+  "bootStakeholders": { "af2800c124e599d6dec188a75f8bfde397ebb778163a18240371f2d1": 1 },
+  "heavyDelegation": {
+    "af2800c124e599d6dec188a75f8bfde397ebb778163a18240371f2d1": {
+      "omega": 0,
+      "issuerPk": "G8l6L+AsKXiAzo7P2Zf+TB7AnuEP7u6faGdgFmsFKB1ig0aP/ZO+ywyVbM3dZC35sSRMkVkRGF+kk1X28iv6uQ==",
+      "delegatePk": "YSYalbdhPua/IGfa13twNJcpsMUNV7wc8w3g20oec6iF0AVK98I/xsN5GdukHGAqV+LQ+TKaeVS4ZzONb7LJRQ==",
+      "cert": "e03e62f083df5576360e60a32e22bbb07b3c8df4fcab8079f1d6f61af3954d242ba8a06516c395939f24096f3df14e103a7d9c2b80a68a9363cf1f27c7a4e307"
+    }
+  },
+  "protocolConsts": { "k": 2160, "protocolMagic": 764824073, "vssMinTTL": 2, "vssMaxTTL": 6 },
+  "blockVersionData": { "maxBlockSize": "2000000", "maxHeaderSize": "2000000", "maxTxSize": "4096", "slotDuration": "20000",
+                        "txFeePolicy": { "summand": "155381000000000", "multiplier": "43946000000" } },
+  "startTime": 1506203091
+}"#;
+
+    #[test]
+    fn parse_mainnet_heavy_delegation() {
+        let genesis = parse(MAINNET_EXCERPT.as_bytes(), &HeaderHash::new(MAINNET_EXCERPT.as_bytes())).unwrap();
+        let id = StakeholderId::from_slice(&hex::decode("af2800c124e599d6dec188a75f8bfde397ebb778163a18240371f2d1").unwrap()).unwrap();
+        let psk = genesis.heavy_delegation.get(&id).unwrap();
+        assert_eq!(StakeholderId::new(&psk.issuer_pk), id);
+        assert!(psk.verify(ProtocolMagic::new(764824073)));
+        assert!(!psk.verify(ProtocolMagic::new(633343913)));
+        assert_eq!(genesis.protocol_constants.config().address_protocol_magic(), None);
+    }
+
+    #[test]
+    fn parse_genesis_data_wrong_hash() {
+        let (addr, issuer) = setup();
+        let json = genesis_json(&addr, &issuer, &certify(&issuer, ProtocolMagic::new(764824073)));
+        match parse(json.as_bytes(), &HeaderHash::new(&[])) {
+            Err(Error::WrongGenesisHash(_, got)) => assert_eq!(got, HeaderHash::new(json.as_bytes())),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn parse_genesis_data_invalid_delegation() {
+        let (addr, issuer) = setup();
+        let json = genesis_json(&addr, &issuer, &certify(&issuer, ProtocolMagic::new(0)));
+        match parse(json.as_bytes(), &HeaderHash::new(json.as_bytes())) {
+            Err(Error::InvalidDelegation(id)) => assert_eq!(id, StakeholderId::new(&issuer.public())),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! ledger state: the set of unspent transaction outputs (UTxOs)
+//!
+//! Applying a block validates each of its transactions against the
+//! current UTxOs:
+//!
+//! * every input exists and is spent only once;
+//! * every input has a witness verifying against the input's address;
+//! * the outputs and the minimal fee are covered by the inputs.
+//!
+//! and returns the `Diff` of the UTxOs, so the block can be undone later
+//! (e.g. on a rollback).
+//!
+
+use std::collections::BTreeMap;
+use std::{fmt, result};
+
+use wallet_crypto::{cbor, coin, tx};
+use wallet_crypto::coin::{Coin};
+use wallet_crypto::config::{Config};
+use wallet_crypto::tx::{TxIn, TxOut, TxId, TxAux};
+use wallet_crypto::tx::fee::{LinearFee};
+
+use block::{Block};
+use genesis_data::{GenesisData};
+
+pub type Utxos = BTreeMap<TxIn, TxOut>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the transaction has no inputs
+    NoInputs(TxId),
+    /// the transaction has no outputs
+    NoOutputs(TxId),
+    /// the number of witnesses (third) is not the number of inputs (second)
+    WrongNumberOfWitnesses(TxId, usize, usize),
+    /// the input does not exist or has already been spent
+    MissingInput(TxId, TxIn),
+    /// the input is spent more than once in the transaction
+    DoubleSpend(TxId, TxIn),
+    /// the witness of the input does not verify against the input's address
+    InvalidWitness(TxId, TxIn),
+    /// the outputs (third) are worth more than the inputs (second)
+    OutputsExceedInputs(TxId, Coin, Coin),
+    /// the fee (third) is less than the minimal fee (second)
+    InsufficientFee(TxId, Coin, Coin),
+    CoinError(TxId, coin::Error),
+    FeeError(TxId, tx::fee::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::NoInputs(ref id) => write!(f, "transaction {} has no inputs", id),
+            &Error::NoOutputs(ref id) => write!(f, "transaction {} has no outputs", id),
+            &Error::WrongNumberOfWitnesses(ref id, inputs, witnesses) => write!(f, "transaction {} has {} inputs but {} witnesses", id, inputs, witnesses),
+            &Error::MissingInput(ref id, ref txin) => write!(f, "transaction {} spends the unknown or already spent input {}", id, txin),
+            &Error::DoubleSpend(ref id, ref txin) => write!(f, "transaction {} spends the input {} more than once", id, txin),
+            &Error::InvalidWitness(ref id, ref txin) => write!(f, "transaction {} has an invalid witness for the input {}", id, txin),
+            &Error::OutputsExceedInputs(ref id, inputs, outputs) => write!(f, "transaction {} outputs {} but its inputs are only {}", id, outputs, inputs),
+            &Error::InsufficientFee(ref id, expected, got) => write!(f, "transaction {} fee is {} but the minimal fee is {}", id, got, expected),
+            &Error::CoinError(ref id, err) => write!(f, "transaction {}: {}", id, err),
+            &Error::FeeError(ref id, err) => write!(f, "transaction {}: {}", id, err),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// the changes a block made to the UTxOs
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Diff {
+    /// the outputs spent by the block, with their value
+    pub spent: Utxos,
+    /// the outputs created by the block (and not spent by the block itself)
+    pub created: Utxos,
+}
+impl Diff {
+    pub fn new() -> Self { Diff::default() }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    config: Config,
+    fee: LinearFee,
+    utxos: Utxos,
+}
+impl Ledger {
+    pub fn new(config: Config, fee: LinearFee, utxos: Utxos) -> Self {
+        Ledger { config: config, fee: fee, utxos: utxos }
+    }
+
+    /// the ledger at the start of the network
+    pub fn from_genesis(genesis: &GenesisData) -> Self {
+        Ledger::new(genesis.protocol_constants.config(), genesis.block_version_data.tx_fee_policy.linear_fee(), genesis.initial_utxos())
+    }
+
+    pub fn utxos(&self) -> &Utxos { &self.utxos }
+
+    pub fn get(&self, txin: &TxIn) -> Option<&TxOut> { self.utxos.get(txin) }
+
+    /// validate the transactions of the block and, if all valid, apply
+    /// them to the UTxOs. On error the ledger is left untouched.
+    pub fn apply_block(&mut self, blk: &Block) -> Result<Diff> {
+        let mut diff = Diff::new();
+        if let &Block::MainBlock(ref b) = blk {
+            for txaux in b.body.tx.iter() {
+                self.verify_txaux(txaux, &mut diff)?;
+            }
+        }
+        for txin in diff.spent.keys() {
+            self.utxos.remove(txin);
+        }
+        self.utxos.extend(diff.created.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Ok(diff)
+    }
+
+    /// revert the changes of a previously applied block
+    pub fn undo(&mut self, diff: &Diff) {
+        for txin in diff.created.keys() {
+            self.utxos.remove(txin);
+        }
+        self.utxos.extend(diff.spent.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// verify the transaction against the UTxOs updated with the changes
+    /// of the previous transactions of the block, and record its changes.
+    fn verify_txaux(&self, txaux: &TxAux, diff: &mut Diff) -> Result<()> {
+        let tx = &txaux.tx;
+        let id = tx.id();
+        if tx.inputs.is_empty() { return Err(Error::NoInputs(id)); }
+        if tx.outputs.is_empty() { return Err(Error::NoOutputs(id)); }
+        if tx.inputs.len() != txaux.witnesses.len() {
+            return Err(Error::WrongNumberOfWitnesses(id, tx.inputs.len(), txaux.witnesses.len()));
+        }
+
+        let mut spent = Vec::with_capacity(tx.inputs.len());
+        let mut input_value = Coin::zero();
+        for (txin, witness) in tx.inputs.iter().zip(txaux.witnesses.iter()) {
+            if spent.iter().any(|&(ref i, _)| i == txin) {
+                return Err(Error::DoubleSpend(id, txin.clone()));
+            }
+            let txout = match diff.created.get(txin) {
+                Some(txout) => txout.clone(),
+                None if diff.spent.contains_key(txin) => return Err(Error::MissingInput(id, txin.clone())),
+                None => match self.utxos.get(txin) {
+                    Some(txout) => txout.clone(),
+                    None => return Err(Error::MissingInput(id, txin.clone())),
+                },
+            };
+            if ! witness.verify(&self.config, &txout.address, tx) {
+                return Err(Error::InvalidWitness(id, txin.clone()));
+            }
+            input_value = (input_value + txout.value).map_err(|e| Error::CoinError(id, e))?;
+            spent.push((txin.clone(), txout));
+        }
+
+        let mut output_value = Coin::zero();
+        for txout in tx.outputs.iter() {
+            output_value = (output_value + txout.value).map_err(|e| Error::CoinError(id, e))?;
+        }
+        let fee = match input_value - output_value {
+            None => return Err(Error::OutputsExceedInputs(id, input_value, output_value)),
+            Some(fee) => fee,
+        };
+        // redemption transactions are exempted from fees
+        if ! txaux.is_redemption() {
+            let size = cbor::encode_to_cbor(txaux).unwrap().len();
+            let min_fee = self.fee.estimate(size).map_err(|e| Error::FeeError(id, e))?.to_coin();
+            if fee < min_fee {
+                return Err(Error::InsufficientFee(id, min_fee, fee));
+            }
+        }
+
+        for (txin, txout) in spent {
+            if diff.created.remove(&txin).is_none() {
+                diff.spent.insert(txin, txout);
+            }
+        }
+        for (index, txout) in tx.outputs.iter().enumerate() {
+            diff.created.insert(TxIn::new(id, index as u32), txout.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wallet_crypto::hdwallet;
+    use wallet_crypto::address::{ExtendedAddr, AddrType, SpendingData, Attributes};
+    use wallet_crypto::config::{ProtocolMagic};
+    use types::{HeaderHash, SlotId};
+    use block::{BlockHeader};
+    use builder::{BlockBuilder, GenesisBlockBuilder};
+    use normal::{TxPayload};
+
+    fn key(i: u8) -> hdwallet::XPrv {
+        hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([i;32]))
+    }
+    fn address(key: &hdwallet::XPrv) -> ExtendedAddr {
+        ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key.public()), Attributes::new_bootstrap_era(None))
+    }
+    fn coin(v: u64) -> Coin { Coin::new(v).unwrap() }
+
+    fn config() -> Config { Config::new(ProtocolMagic::new(0)) }
+
+    /// a ledger with a single output of 1 ADA owned by `key(0)`
+    fn ledger() -> (Ledger, TxIn) {
+        let addr = address(&key(0));
+        let txin = TxIn::new(TxId::new(&addr.to_bytes()), 0);
+        let mut utxos = Utxos::new();
+        utxos.insert(txin.clone(), TxOut::new(addr, coin(1_000_000)));
+        (Ledger::new(config(), LinearFee::default(), utxos), txin)
+    }
+
+    fn txaux(signer: &hdwallet::XPrv, inputs: &[TxIn], outputs: &[u64]) -> TxAux {
+        let mut tx = tx::Tx::new();
+        for i in inputs { tx.add_input(i.clone()); }
+        for v in outputs { tx.add_output(TxOut::new(address(&key(1)), coin(*v))); }
+        let witnesses = inputs.iter().map(|_| tx::TxInWitness::new(&config(), signer, &tx)).collect();
+        TxAux::new(tx, witnesses)
+    }
+
+    fn block(txauxs: Vec<TxAux>) -> Block {
+        let pm = ProtocolMagic::new(0);
+        let ebb = GenesisBlockBuilder::first(pm, &HeaderHash::new(b"genesis"), vec![]).make();
+        let previous = BlockHeader::GenesisBlockHeader(ebb.header);
+        let slot = SlotId { epoch: 0, slotid: 0 };
+        Block::MainBlock(BlockBuilder::new(pm, &previous, slot, key(9), TxPayload::new(txauxs.into_iter().collect())).make())
+    }
+
+    #[test]
+    fn apply_and_undo() {
+        let (mut ledger, txin) = ledger();
+        let initial = ledger.utxos().clone();
+
+        let t1 = txaux(&key(0), &[txin.clone()], &[400_000, 400_000]);
+        let t1_out = TxIn::new(t1.tx.id(), 0);
+        // spends an output created by the previous transaction of the block
+        let t2 = txaux(&key(1), &[t1_out.clone()], &[200_000]);
+        let t2_out = TxIn::new(t2.tx.id(), 0);
+
+        let diff = ledger.apply_block(&block(vec![t1.clone(), t2])).unwrap();
+        assert_eq!(diff.spent.keys().collect::<Vec<_>>(), vec![&txin]);
+        assert_eq!(diff.created.len(), 2);
+        assert!(ledger.get(&txin).is_none());
+        assert!(ledger.get(&t1_out).is_none());
+        assert_eq!(ledger.get(&TxIn::new(t1.tx.id(), 1)).map(|o| o.value), Some(coin(400_000)));
+        assert_eq!(ledger.get(&t2_out).map(|o| o.value), Some(coin(200_000)));
+
+        ledger.undo(&diff);
+        assert_eq!(ledger.utxos(), &initial);
+    }
+
+    #[test]
+    fn reject_invalid_transactions() {
+        let (mut ledger, txin) = ledger();
+        let initial = ledger.utxos().clone();
+
+        let unknown = TxIn::new(TxId::new(b"unknown"), 0);
+        let t = txaux(&key(0), &[unknown.clone()], &[1000]);
+        assert_eq!(ledger.apply_block(&block(vec![t.clone()])), Err(Error::MissingInput(t.tx.id(), unknown)));
+
+        let t = txaux(&key(1), &[txin.clone()], &[1000]);
+        assert_eq!(ledger.apply_block(&block(vec![t.clone()])), Err(Error::InvalidWitness(t.tx.id(), txin.clone())));
+
+        let t = txaux(&key(0), &[txin.clone(), txin.clone()], &[1000]);
+        assert_eq!(ledger.apply_block(&block(vec![t.clone()])), Err(Error::DoubleSpend(t.tx.id(), txin.clone())));
+
+        let t = txaux(&key(0), &[txin.clone()], &[2_000_000]);
+        assert_eq!(ledger.apply_block(&block(vec![t.clone()])), Err(Error::OutputsExceedInputs(t.tx.id(), coin(1_000_000), coin(2_000_000))));
+
+        let t = txaux(&key(0), &[txin.clone()], &[990_000]);
+        match ledger.apply_block(&block(vec![t.clone()])) {
+            Err(Error::InsufficientFee(id, _, fee)) => { assert_eq!(id, t.tx.id()); assert_eq!(fee, coin(10_000)); },
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        // the same input spent by two transactions of the block
+        let t1 = txaux(&key(0), &[txin.clone()], &[500_000]);
+        let t2 = txaux(&key(0), &[txin.clone()], &[400_000]);
+        assert_eq!(ledger.apply_block(&block(vec![t1, t2.clone()])), Err(Error::MissingInput(t2.tx.id(), txin.clone())));
+
+        assert_eq!(ledger.utxos(), &initial);
+    }
+
+    #[test]
+    fn redeem_without_fee() {
+        let redeem_key = ::wallet_crypto::redeem::PrivateKey::generate(&[3;32]);
+        let redeem_addr = ExtendedAddr::new_redeem(&redeem_key.public(), None);
+        let txin = TxIn::new(TxId::new(&redeem_addr.to_bytes()), 0);
+        let mut utxos = Utxos::new();
+        utxos.insert(txin.clone(), TxOut::new(redeem_addr, coin(1_000_000)));
+        let mut ledger = Ledger::new(config(), LinearFee::default(), utxos);
+
+        let wrong_key = ::wallet_crypto::redeem::PrivateKey::generate(&[4;32]);
+        let t = TxAux::new_redeem(&config(), &wrong_key, txin.clone(), coin(1_000_000), address(&key(1)));
+        assert_eq!(ledger.apply_block(&block(vec![t.clone()])), Err(Error::InvalidWitness(t.tx.id(), txin.clone())));
+
+        let t = TxAux::new_redeem(&config(), &redeem_key, txin.clone(), coin(1_000_000), address(&key(1)));
+        assert!(t.is_redemption());
+        ledger.apply_block(&block(vec![t.clone()])).unwrap();
+        assert_eq!(ledger.get(&TxIn::new(t.tx.id(), 0)).map(|o| o.value), Some(coin(1_000_000)));
+    }
+}
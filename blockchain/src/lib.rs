@@ -10,11 +10,18 @@ extern crate test;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+extern crate serde_json;
+extern crate base64;
 
 mod types;
 pub mod genesis; /* genesis block related value */
 pub mod normal; /* normal block related value */
 mod block;
+pub mod validation;
+pub mod genesis_data; /* network genesis data (genesis JSON) */
+pub mod time;
+pub mod builder;
+pub mod ledger;
 
 pub use types::*;
 pub use block::*;
@@ -1,14 +1,16 @@
 use wallet_crypto::{tx, hdwallet, cbor, hash::{Blake2b256}};
 use wallet_crypto::cbor::{ExtendedResult};
 use wallet_crypto::config::{ProtocolMagic};
-use std::{fmt};
+use std::{fmt, result};
 use std::collections::linked_list::{Iter};
 use std::collections::{LinkedList};
 
+use serde;
+
 use types;
-use types::{HeaderHash, HeaderExtraData, SlotId, ChainDifficulty};
+use types::{HeaderHash, HeaderExtraData, SlotId, EpochId, ChainDifficulty};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub struct BodyProof {
     pub tx: tx::TxProof,
     pub mpc: types::SscProof,
@@ -71,6 +73,13 @@ impl TxPayload {
     }
     pub fn iter(&self) -> Iter<tx::TxAux> { self.txaux.iter() }
 }
+impl serde::Serialize for TxPayload {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        serializer.collect_seq(self.txaux.iter())
+    }
+}
 impl cbor::CborValue for TxPayload {
     fn encode(&self) -> cbor::Value {
         let mut l = LinkedList::new();
@@ -90,11 +99,14 @@ impl cbor::CborValue for TxPayload {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Body {
     pub tx: TxPayload,
+    #[serde(serialize_with = "types::serialize_cbor_value")]
     pub scc: cbor::Value,
+    #[serde(serialize_with = "types::serialize_cbor_value")]
     pub delegation: cbor::Value,
+    #[serde(serialize_with = "types::serialize_cbor_value")]
     pub update: cbor::Value
 }
 impl Body {
@@ -109,7 +121,12 @@ impl fmt::Display for Body {
 }
 impl cbor::CborValue for Body {
     fn encode(&self) -> cbor::Value {
-        unimplemented!()
+        cbor::Value::Array(vec![
+            cbor::CborValue::encode(&self.tx),
+            self.scc.clone(),
+            self.delegation.clone(),
+            self.update.clone(),
+        ])
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
         value.array().and_then(|array| {
@@ -123,7 +140,7 @@ impl cbor::CborValue for Body {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BlockHeader {
     pub protocol_magic: ProtocolMagic,
     pub previous_header: HeaderHash,
@@ -149,7 +166,59 @@ impl BlockHeader {
             consensus: c,
             extra_data: ed
         }
-}
+    }
+
+    /// the CBOR encoded data the slot leader signs: previous hash, body proof,
+    /// slot, difficulty and extra data (a.k.a. `MainToSign`)
+    fn to_sign(&self) -> Vec<u8> {
+        let v = cbor::Value::Array(vec![
+            cbor::CborValue::encode(&self.previous_header),
+            cbor::CborValue::encode(&self.body_proof),
+            cbor::CborValue::encode(&self.consensus.slot_id),
+            cbor::CborValue::encode(&self.consensus.chain_difficulty),
+            cbor::CborValue::encode(&self.extra_data),
+        ]);
+        cbor::encode_to_cbor(&v).unwrap()
+    }
+
+    /// make `key` the leader of the block and sign the header with it
+    pub fn sign(&mut self, key: &hdwallet::XPrv) {
+        self.consensus.leader_key = key.public();
+        let mut buf = vec![ SigningTag::MainBlock as u8 ];
+        buf.extend_from_slice(&cbor::encode_to_cbor(&self.protocol_magic).unwrap());
+        buf.extend_from_slice(&self.to_sign());
+        self.consensus.block_signature = BlockSignature::Signature(key.sign(&buf));
+    }
+
+    /// verify the block signature against the `leader_key` of the consensus
+    /// and this header's protocol magic.
+    ///
+    /// In the case of a proxy signature, the delegation certificate needs to
+    /// have been issued by the leader (and for the current epoch when light
+    /// weight delegation is used).
+    pub fn verify_signature(&self) -> bool {
+        let data = self.to_sign();
+        let leader = &self.consensus.leader_key;
+        match &self.consensus.block_signature {
+            &BlockSignature::Signature(ref sig) => {
+                let mut buf = vec![ SigningTag::MainBlock as u8 ];
+                buf.extend_from_slice(&cbor::encode_to_cbor(&self.protocol_magic).unwrap());
+                buf.extend_from_slice(&data);
+                leader.verify(&buf, sig)
+            },
+            &BlockSignature::ProxyLight(ref proxy) => {
+                let (start, end) = proxy.psk.omega;
+                let epoch = self.consensus.slot_id.epoch;
+                &proxy.psk.issuer_pk == leader
+                    && start <= epoch && epoch <= end
+                    && proxy.verify(self.protocol_magic, SigningTag::MainBlockLight, &data)
+            },
+            &BlockSignature::ProxyHeavy(ref proxy) => {
+                &proxy.psk.issuer_pk == leader
+                    && proxy.verify(self.protocol_magic, SigningTag::MainBlockHeavy, &data)
+            },
+        }
+    }
 }
 impl cbor::CborValue for BlockHeader {
     fn encode(&self) -> cbor::Value {
@@ -174,10 +243,11 @@ impl cbor::CborValue for BlockHeader {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Block {
     pub header: BlockHeader,
     pub body: Body,
+    #[serde(serialize_with = "types::serialize_cbor_value")]
     pub extra: cbor::Value
 }
 impl Block {
@@ -212,29 +282,121 @@ impl cbor::CborValue for Block {
 
 type SignData = ();
 
-#[derive(Debug, Clone)]
+/// tag prepended to the signed data, making sure a signature
+/// produced for a given purpose cannot be replayed for another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningTag {
+    MainBlock = 0x07,
+    MainBlockLight = 0x08,
+    MainBlockHeavy = 0x09,
+    ProxySK = 0x0a,
+}
+
+/// delegation certificate: the issuer allows the delegate to sign
+/// on its behalf for the given `omega` (epoch range or epoch).
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxySecretKey<W> {
+    pub omega: W,
+    pub issuer_pk: hdwallet::XPub,
+    pub delegate_pk: hdwallet::XPub,
+    pub cert: hdwallet::Signature<SignData>,
+}
+impl<W: cbor::CborValue> ProxySecretKey<W> {
+    /// verify the certificate has been issued by `issuer_pk`
+    pub fn verify(&self, protocol_magic: ProtocolMagic) -> bool {
+        let buf = Self::signed_data(protocol_magic, &self.delegate_pk, &self.omega);
+        self.issuer_pk.verify(&buf, &self.cert)
+    }
+
+    /// the data signed by the issuer in the certificate: the `ProxySK`
+    /// tag and the protocol magic, then the delegate's key and `omega`
+    /// (prefixed with `"00"`) as a CBOR byte string
+    pub fn signed_data(protocol_magic: ProtocolMagic, delegate_pk: &hdwallet::XPub, omega: &W) -> Vec<u8> {
+        let mut payload = b"00".to_vec();
+        payload.extend_from_slice(delegate_pk.as_ref());
+        payload.extend_from_slice(&cbor::encode_to_cbor(omega).unwrap());
+
+        let mut buf = vec![ SigningTag::ProxySK as u8 ];
+        buf.extend_from_slice(&cbor::encode_to_cbor(&protocol_magic).unwrap());
+        buf.extend_from_slice(&cbor::encode_to_cbor(&cbor::Bytes::new(payload)).unwrap());
+        buf
+    }
+}
+impl<W: cbor::CborValue> cbor::CborValue for ProxySecretKey<W> {
+    fn encode(&self) -> cbor::Value {
+        cbor::Value::Array(vec![
+            cbor::CborValue::encode(&self.omega),
+            cbor::CborValue::encode(&self.issuer_pk),
+            cbor::CborValue::encode(&self.delegate_pk),
+            cbor::CborValue::encode(&self.cert),
+        ])
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.array().and_then(|array| {
+            let (array, omega)       = cbor::array_decode_elem(array, 0).embed("omega")?;
+            let (array, issuer_pk)   = cbor::array_decode_elem(array, 0).embed("issuer_pk")?;
+            let (array, delegate_pk) = cbor::array_decode_elem(array, 0).embed("delegate_pk")?;
+            let (array, cert)        = cbor::array_decode_elem(array, 0).embed("cert")?;
+            if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+            Ok(ProxySecretKey { omega: omega, issuer_pk: issuer_pk, delegate_pk: delegate_pk, cert: cert })
+        }).embed("While decoding main::ProxySecretKey")
+    }
+}
+
+/// signature made by a delegate on behalf of the issuer of `psk`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxySignature<W> {
+    pub psk: ProxySecretKey<W>,
+    pub sig: hdwallet::Signature<SignData>,
+}
+impl<W: cbor::CborValue> ProxySignature<W> {
+    /// verify the delegation certificate and the signature of `data`
+    pub fn verify(&self, protocol_magic: ProtocolMagic, tag: SigningTag, data: &[u8]) -> bool {
+        let mut buf = b"01".to_vec();
+        buf.extend_from_slice(self.psk.issuer_pk.as_ref());
+        buf.push(tag as u8);
+        buf.extend_from_slice(&cbor::encode_to_cbor(&protocol_magic).unwrap());
+        buf.extend_from_slice(data);
+        self.psk.verify(protocol_magic) && self.psk.delegate_pk.verify(&buf, &self.sig)
+    }
+}
+impl<W: cbor::CborValue> cbor::CborValue for ProxySignature<W> {
+    fn encode(&self) -> cbor::Value {
+        cbor::Value::Array(vec![
+            cbor::CborValue::encode(&self.psk),
+            cbor::CborValue::encode(&self.sig),
+        ])
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.array().and_then(|array| {
+            let (array, psk) = cbor::array_decode_elem(array, 0).embed("psk")?;
+            let (array, sig) = cbor::array_decode_elem(array, 0).embed("sig")?;
+            if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+            Ok(ProxySignature { psk: psk, sig: sig })
+        }).embed("While decoding main::ProxySignature")
+    }
+}
+
+/// light weight delegation is valid for the given range of epochs (inclusive)
+pub type LightWeightDelegation = (EpochId, EpochId);
+/// heavy weight delegation is valid from the given epoch
+pub type HeavyWeightDelegation = u64;
+
+#[derive(Debug, Clone, Serialize)]
 pub enum BlockSignature {
     Signature(hdwallet::Signature<SignData>),
-    ProxyLight(Vec<cbor::Value>),
-    ProxyHeavy(Vec<cbor::Value>),
+    ProxyLight(ProxySignature<LightWeightDelegation>),
+    ProxyHeavy(ProxySignature<HeavyWeightDelegation>),
 }
 impl cbor::CborValue for BlockSignature {
     fn encode(&self) -> cbor::Value {
         match self {
             &BlockSignature::Signature(ref sig) =>
                 cbor::Value::Array(vec![ cbor::Value::U64(0), cbor::CborValue::encode(sig) ]),
-            &BlockSignature::ProxyLight(ref v) => {
-                let mut r = Vec::new();
-                r.push(cbor::Value::U64(1));
-                r.extend_from_slice(v);
-                cbor::Value::Array(r)
-            },
-            &BlockSignature::ProxyHeavy(ref v) => {
-                let mut r = Vec::new();
-                r.push(cbor::Value::U64(2));
-                r.extend_from_slice(v);
-                cbor::Value::Array(r)
-            },
+            &BlockSignature::ProxyLight(ref sig) =>
+                cbor::Value::Array(vec![ cbor::Value::U64(1), cbor::CborValue::encode(sig) ]),
+            &BlockSignature::ProxyHeavy(ref sig) =>
+                cbor::Value::Array(vec![ cbor::Value::U64(2), cbor::CborValue::encode(sig) ]),
         }
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
@@ -246,15 +408,23 @@ impl cbor::CborValue for BlockSignature {
                     if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
                     Ok(BlockSignature::Signature(sig))
                 },
-                1u64 => { Ok(BlockSignature::ProxyLight(array)) },
-                2u64 => { Ok(BlockSignature::ProxyHeavy(array)) },
-                _    => { cbor::Result::array(array, cbor::Error::UnparsedValues) },
+                1u64 => {
+                    let (array, sig) = cbor::array_decode_elem(array,0).embed("light weight proxy signature")?;
+                    if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+                    Ok(BlockSignature::ProxyLight(sig))
+                },
+                2u64 => {
+                    let (array, sig) = cbor::array_decode_elem(array,0).embed("heavy weight proxy signature")?;
+                    if ! array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+                    Ok(BlockSignature::ProxyHeavy(sig))
+                },
+                _    => { cbor::Result::array(array, cbor::Error::InvalidSumtype(code)) },
             }
         }).embed("While decoding main::BlockSignature")
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Consensus {
     pub slot_id: SlotId,
     pub leader_key: hdwallet::XPub,
@@ -287,3 +457,130 @@ impl cbor::CborValue for Consensus {
         }).embed("While decoding main::Consensus")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wallet_crypto::util::hex;
+
+    const HEADER: &str = "85005820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22848301582096d38c5aafb839450511e1bae3b4ecde215888dee3403526e2373d016fdfdd1e582083ac5d0d6ac0c02abf8c5ad766d0135873ca4ac53dd582187c9aa15aa149c0da82035820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d225820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d225820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22848201182a58401c0c3ae1825e90b6ddda3f40a122c007e1008e83b2e102c142baefb721d72c1a5d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b1281182a82005840a90522874cccf9a67e209031fd9dfe37a82fd943dee63300aa823cb98e0f704e913f6e02b2aa0a33693e052c15f43aee242164d2812a572b2774c1b5ada818018483000100826a63617264616e6f2d736c00a05820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22";
+
+    // the private key associated to the `leader_key` of `HEADER`
+    const LEADER: &str = "301604045de9138b8b23b6730495f7e34b5151d29ba3456bc9b332f6f084a551d646bc30cf126fa8ed776c05a8932a5ab35c8bac41eb01bb9a16cfe229b94b405d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b12";
+
+    fn header() -> BlockHeader {
+        cbor::decode_from_cbor(&hex::decode(HEADER).unwrap()).unwrap()
+    }
+
+    fn sign(key: &hdwallet::XPrv, hdr: &BlockHeader) -> hdwallet::Signature<SignData> {
+        let mut buf = vec![ SigningTag::MainBlock as u8 ];
+        buf.extend_from_slice(&cbor::encode_to_cbor(&hdr.protocol_magic).unwrap());
+        buf.extend_from_slice(&hdr.to_sign());
+        key.sign(&buf)
+    }
+
+    fn proxy_sign<W: cbor::CborValue>(issuer: &hdwallet::XPrv, delegate: &hdwallet::XPrv, omega: W, tag: SigningTag, hdr: &BlockHeader) -> ProxySignature<W> {
+        let buf = ProxySecretKey::signed_data(hdr.protocol_magic, &delegate.public(), &omega);
+        let psk = ProxySecretKey {
+            omega: omega,
+            issuer_pk: issuer.public(),
+            delegate_pk: delegate.public(),
+            cert: issuer.sign(&buf),
+        };
+
+        let mut buf = b"01".to_vec();
+        buf.extend_from_slice(issuer.public().as_ref());
+        buf.push(tag as u8);
+        buf.extend_from_slice(&cbor::encode_to_cbor(&hdr.protocol_magic).unwrap());
+        buf.extend_from_slice(&hdr.to_sign());
+        ProxySignature { psk: psk, sig: delegate.sign(&buf) }
+    }
+
+    #[test]
+    fn verify_block_signature() {
+        let leader = hdwallet::XPrv::from_hex(LEADER).unwrap();
+        let mut hdr = header();
+        assert_eq!(hdr.consensus.leader_key, leader.public());
+
+        hdr.consensus.block_signature = BlockSignature::Signature(sign(&leader, &hdr));
+        assert!(hdr.verify_signature());
+
+        // the signature covers the slot
+        hdr.consensus.slot_id = hdr.consensus.slot_id.next();
+        assert!(!hdr.verify_signature());
+    }
+
+    #[test]
+    fn verify_block_signature_protocol_magic() {
+        let leader = hdwallet::XPrv::from_hex(LEADER).unwrap();
+        let mut hdr = header();
+        hdr.consensus.block_signature = BlockSignature::Signature(sign(&leader, &hdr));
+        hdr.protocol_magic = ProtocolMagic::default();
+        assert!(!hdr.verify_signature());
+    }
+
+    #[test]
+    fn verify_block_proxy_heavy_signature() {
+        let leader = hdwallet::XPrv::from_hex(LEADER).unwrap();
+        let delegate = leader.derive(hdwallet::DerivationScheme::V2, 0x80000000);
+        let mut hdr = header();
+
+        let proxy = proxy_sign(&leader, &delegate, 0u64, SigningTag::MainBlockHeavy, &hdr);
+        hdr.consensus.block_signature = BlockSignature::ProxyHeavy(proxy.clone());
+        assert!(hdr.verify_signature());
+
+        let bytes = cbor::encode_to_cbor(&hdr).unwrap();
+        let decoded : BlockHeader = cbor::decode_from_cbor(&bytes).unwrap();
+        assert_eq!(bytes, cbor::encode_to_cbor(&decoded).unwrap());
+        assert!(decoded.verify_signature());
+
+        // the delegation needs to have been issued by the slot leader
+        let proxy = proxy_sign(&delegate, &leader, 0u64, SigningTag::MainBlockHeavy, &hdr);
+        hdr.consensus.block_signature = BlockSignature::ProxyHeavy(proxy);
+        assert!(!hdr.verify_signature());
+    }
+
+    #[test]
+    fn verify_block_proxy_light_signature() {
+        let leader = hdwallet::XPrv::from_hex(LEADER).unwrap();
+        let delegate = leader.derive(hdwallet::DerivationScheme::V2, 0x80000000);
+        let mut hdr = header();
+        let epoch = hdr.consensus.slot_id.epoch;
+
+        let proxy = proxy_sign(&leader, &delegate, (epoch, epoch + 1), SigningTag::MainBlockLight, &hdr);
+        hdr.consensus.block_signature = BlockSignature::ProxyLight(proxy);
+        assert!(hdr.verify_signature());
+
+        let bytes = cbor::encode_to_cbor(&hdr).unwrap();
+        let decoded : BlockHeader = cbor::decode_from_cbor(&bytes).unwrap();
+        assert!(decoded.verify_signature());
+
+        // signed with the heavy weight tag
+        let proxy = proxy_sign(&leader, &delegate, (epoch, epoch + 1), SigningTag::MainBlockHeavy, &hdr);
+        hdr.consensus.block_signature = BlockSignature::ProxyLight(proxy);
+        assert!(!hdr.verify_signature());
+
+        // the delegation does not cover the block's epoch
+        let proxy = proxy_sign(&leader, &delegate, (epoch + 1, epoch + 2), SigningTag::MainBlockLight, &hdr);
+        hdr.consensus.block_signature = BlockSignature::ProxyLight(proxy);
+        assert!(!hdr.verify_signature());
+    }
+
+    // a mainnet header (epoch 1, slot 14258), signed by a heavy weight
+    // delegate of the genesis stakeholder
+    const MAINNET_HEADER: &str = "851a2d964a0958203e705e9aa27fe54e2c662a0a5aa80c360bd47ce24bb9429dfa4fdf170c2ded8184830358200a56168c95c6781fe37e68539b6cef88cee1b472e1d29a7be3ed497929c29c3d58204fa3ffe49fc2359eaeb5e24e70c07ae952000c39c90fa6719528b6ab27d0393f82035820d36a2619a672494604e11bb447cbcf5231e9f2ba25c2169177edc941bd50ad6c5820afc0da64183bf2664f3d4eec7238d524ba607faeeab24fc100eb861dba69971b58204e66280cd94d591072349bec0a3090a53aa945562efb6d08d56e53654b0e40988482011937b258401bc97a2fe02c297880ce8ecfd997fe4c1ec09ee10feeee9f686760166b05281d6283468ffd93becb0c956ccddd642df9b1244c915911185fa49355f6f22bfab981198bfe820282840058401bc97a2fe02c297880ce8ecfd997fe4c1ec09ee10feeee9f686760166b05281d6283468ffd93becb0c956ccddd642df9b1244c915911185fa49355f6f22bfab9584061261a95b7613ee6bf2067dad77b70349729b0c50d57bc1cf30de0db4a1e73a885d0054af7c23fc6c37919dba41c602a57e2d0f9329a7954b867338d6fb2c9455840e03e62f083df5576360e60a32e22bbb07b3c8df4fcab8079f1d6f61af3954d242ba8a06516c395939f24096f3df14e103a7d9c2b80a68a9363cf1f27c7a4e30758402a64f299c7fe54433389ca74c7cf8e2c35ff463a3612f08cb56aceb59efc75db4748ad7c12f74189fde573699148e0fcf978f291d0c1dea6f7f5d98a0cb11b058483000000826a63617264616e6f2d736c01a058204ba92aa320c60acc9ad7b9a64f2eda55c4d2ec28e604faf186708b4f0c4e8edf";
+
+    #[test]
+    fn verify_mainnet_proxy_heavy_signature() {
+        let mut hdr : BlockHeader = cbor::decode_from_cbor(&hex::decode(MAINNET_HEADER).unwrap()).unwrap();
+        assert_eq!(hdr.protocol_magic, ProtocolMagic::new(764824073));
+        match &hdr.consensus.block_signature {
+            &BlockSignature::ProxyHeavy(ref proxy) => assert!(proxy.psk.verify(hdr.protocol_magic)),
+            sig => panic!("unexpected block signature: {:?}", sig),
+        }
+        assert!(hdr.verify_signature());
+
+        hdr.consensus.slot_id = hdr.consensus.slot_id.next();
+        assert!(!hdr.verify_signature());
+    }
+}
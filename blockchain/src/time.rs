@@ -0,0 +1,167 @@
+//! relation between the block dates and the wall clock
+//!
+//! The slots are numbered from the start of the network (the system
+//! start) and all have the same duration. An epoch is made of `10 * k`
+//! slots; its epoch boundary block shares the time of its first slot.
+//!
+
+use std::time::{SystemTime, Duration, UNIX_EPOCH};
+
+use types::{SlotId, EpochId};
+use block::{BlockDate};
+use genesis_data::{GenesisData};
+
+/// the absolute slot number, counted from the system start
+pub type SlotNumber = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeParameters {
+    /// start of the first slot of the first epoch
+    pub system_start: SystemTime,
+    pub slot_duration: Duration,
+    /// number of slots in an epoch
+    pub epoch_slots: u32,
+}
+impl TimeParameters {
+    /// `system_start` in seconds since the unix epoch, `slot_duration` in
+    /// milliseconds and the security parameter `k`.
+    pub fn new(system_start: u64, slot_duration: u64, k: u64) -> Self {
+        TimeParameters {
+            system_start: UNIX_EPOCH + Duration::from_secs(system_start),
+            slot_duration: Duration::from_millis(slot_duration),
+            epoch_slots: (k * 10) as u32,
+        }
+    }
+
+    pub fn from_genesis(genesis: &GenesisData) -> Self {
+        TimeParameters {
+            system_start: UNIX_EPOCH + Duration::from_secs(genesis.start_time),
+            slot_duration: Duration::from_millis(genesis.block_version_data.slot_duration),
+            epoch_slots: genesis.protocol_constants.epoch_slots(),
+        }
+    }
+
+    /// the absolute slot number of the block date
+    pub fn slot_number(&self, date: &BlockDate) -> SlotNumber {
+        match date {
+            &BlockDate::Genesis(epoch) => self.epoch_first_slot(epoch),
+            &BlockDate::Normal(ref slot) => self.epoch_first_slot(slot.epoch) + slot.slotid as u64,
+        }
+    }
+
+    fn epoch_first_slot(&self, epoch: EpochId) -> SlotNumber {
+        epoch as u64 * self.epoch_slots as u64
+    }
+
+    /// the slot of the given absolute slot number
+    pub fn slot_id(&self, slot: SlotNumber) -> SlotId {
+        SlotId {
+            epoch: (slot / self.epoch_slots as u64) as EpochId,
+            slotid: (slot % self.epoch_slots as u64) as u32,
+        }
+    }
+
+    /// the date `n` slots after the given one
+    pub fn add_slots(&self, date: &BlockDate, n: u64) -> BlockDate {
+        BlockDate::Normal(self.slot_id(self.slot_number(date) + n))
+    }
+
+    /// number of slots from `from` to `to`, or `None` if `to` is before `from`
+    pub fn slots_between(&self, from: &BlockDate, to: &BlockDate) -> Option<u64> {
+        self.slot_number(to).checked_sub(self.slot_number(from))
+    }
+
+    /// the time at which the slot of the block date starts
+    pub fn slot_start_time(&self, date: &BlockDate) -> SystemTime {
+        let slot = self.slot_number(date);
+        let millis = slot * duration_millis(&self.slot_duration);
+        self.system_start + Duration::from_millis(millis)
+    }
+
+    /// the slot in progress at the given time, or `None` if the time is
+    /// before the system start
+    pub fn slot_at(&self, time: SystemTime) -> Option<SlotId> {
+        let elapsed = time.duration_since(self.system_start).ok()?;
+        Some(self.slot_id(duration_millis(&elapsed) / duration_millis(&self.slot_duration)))
+    }
+
+    /// the slot in progress now
+    pub fn current_slot(&self) -> Option<SlotId> {
+        self.slot_at(SystemTime::now())
+    }
+
+    /// how far behind `now` the slot of the given block date is
+    pub fn lag(&self, date: &BlockDate, now: SystemTime) -> Duration {
+        now.duration_since(self.slot_start_time(date)).unwrap_or(Duration::from_secs(0))
+    }
+}
+
+fn duration_millis(d: &Duration) -> u64 {
+    d.as_secs() * 1000 + (d.subsec_nanos() / 1_000_000) as u64
+}
+
+/// format the time as an UTC ISO 8601 date, e.g. `2017-09-23T21:44:51Z`
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // days to civil date (proleptic gregorian calendar)
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // mainnet
+    fn params() -> TimeParameters { TimeParameters::new(1506203091, 20000, 2160) }
+
+    fn date(epoch: EpochId, slotid: u32) -> BlockDate { BlockDate::Normal(SlotId { epoch: epoch, slotid: slotid }) }
+
+    #[test]
+    fn slot_numbers() {
+        let p = params();
+        assert_eq!(p.slot_number(&BlockDate::Genesis(0)), 0);
+        assert_eq!(p.slot_number(&date(0, 0)), 0);
+        assert_eq!(p.slot_number(&BlockDate::Genesis(2)), 43200);
+        assert_eq!(p.slot_number(&date(2, 17)), 43217);
+        assert_eq!(p.slot_id(43217), SlotId { epoch: 2, slotid: 17 });
+        assert_eq!(p.add_slots(&date(0, 21599), 1), date(1, 0));
+        assert_eq!(p.slots_between(&date(1, 5), &date(2, 5)), Some(21600));
+        assert_eq!(p.slots_between(&date(2, 5), &date(1, 5)), None);
+    }
+
+    #[test]
+    fn slot_times() {
+        let p = params();
+        assert_eq!(format_utc(p.slot_start_time(&BlockDate::Genesis(0))), "2017-09-23T21:44:51Z");
+        assert_eq!(format_utc(p.slot_start_time(&date(1, 0))), "2017-09-28T21:44:51Z");
+        assert_eq!(format_utc(p.slot_start_time(&date(1, 3))), "2017-09-28T21:45:51Z");
+
+        let t = p.slot_start_time(&date(1, 3)) + Duration::from_millis(19999);
+        assert_eq!(p.slot_at(t), Some(SlotId { epoch: 1, slotid: 3 }));
+        assert_eq!(p.slot_at(UNIX_EPOCH), None);
+        assert_eq!(p.lag(&date(1, 2), t), Duration::from_millis(39999));
+    }
+
+    #[test]
+    fn block_date_ordering() {
+        assert!(BlockDate::Genesis(1) < date(1, 0));
+        assert!(date(0, 21599) < BlockDate::Genesis(1));
+        assert!(date(1, 2) < date(1, 3));
+        assert!(date(1, 3) < date(2, 0));
+    }
+}
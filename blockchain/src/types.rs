@@ -1,6 +1,9 @@
-use std::{fmt};
+use std::{fmt, result};
 use wallet_crypto::cbor::{ExtendedResult};
 use wallet_crypto::{cbor, hash, hash::{HASH_SIZE, Blake2b256}};
+use wallet_crypto::util::{hex};
+use wallet_crypto::attributes::{AttributesMap};
+use serde;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Version {
@@ -61,8 +64,15 @@ impl fmt::Display for BlockVersion {
 impl Default for BlockVersion {
     fn default() -> Self { BlockVersion::new(0,1,0) }
 }
+impl serde::Serialize for BlockVersion {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        serializer.serialize_str(&format!("{}", self))
+    }
+}
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize)]
 pub struct SoftwareVersion {
     application_name: String,
     application_version: u32
@@ -84,10 +94,27 @@ impl Default for SoftwareVersion {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct BlockHeaderAttributes(cbor::Value);
+/// so far there are no known block header attributes, they are all kept
+/// unparsed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BlockHeaderAttributes(pub AttributesMap);
 
-#[derive(Debug, Clone)]
+/// serialize a value we do not interpret (yet) as its CBOR encoding,
+/// hexadecimal encoded for the human readable formats (e.g. JSON).
+///
+/// To use with `#[serde(serialize_with = "...")]`.
+pub fn serialize_cbor_value<S>(value: &cbor::Value, serializer: S) -> result::Result<S::Ok, S::Error>
+    where S: serde::Serializer,
+{
+    let bytes = cbor::encode_to_cbor(value).map_err(|err| serde::ser::Error::custom(err))?;
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&hex::encode(&bytes))
+    } else {
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct HeaderExtraData {
     pub block_version: BlockVersion,
     pub software_version: SoftwareVersion,
@@ -105,7 +132,7 @@ impl HeaderExtraData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub enum SscProof {
     Commitments(Blake2b256, Blake2b256),
     Openings(Blake2b256, Blake2b256),
@@ -113,8 +140,14 @@ pub enum SscProof {
     Certificate(Blake2b256)
 }
 
-#[derive(Debug,Clone,Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub struct ChainDifficulty(u64);
+impl From<u64> for ChainDifficulty {
+    fn from(v: u64) -> Self { ChainDifficulty(v) }
+}
+impl From<ChainDifficulty> for u64 {
+    fn from(cd: ChainDifficulty) -> Self { cd.0 }
+}
 
 impl fmt::Display for ChainDifficulty {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -124,7 +157,7 @@ impl fmt::Display for ChainDifficulty {
 
 pub type EpochId = u32;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SlotId {
     pub epoch: EpochId,
     pub slotid: u32,
@@ -215,10 +248,11 @@ impl cbor::CborValue for HeaderHash {
 
 impl cbor::CborValue for BlockHeaderAttributes {
     fn encode(&self) -> cbor::Value {
-        self.0.clone()
+        cbor::CborValue::encode(&self.0)
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
-        Ok(BlockHeaderAttributes(value))
+        cbor::CborValue::decode(value).map(BlockHeaderAttributes)
+            .embed("while decoding BlockHeaderAttributes")
     }
 }
 
@@ -0,0 +1,431 @@
+//! validation of a chain of headers and blocks
+//!
+//! The `Validator` keeps track of the tip of the chain being validated
+//! and checks each header (or block) appended to it:
+//!
+//! * the previous hash links to the current tip;
+//! * the block date follows the tip's (epoch boundary blocks starting every epoch);
+//! * the chain difficulty is incremented by every main block;
+//! * the protocol magic is the one of the network;
+//! * the header is signed by the slot leader;
+//! * the slot leader is the one scheduled by the epoch boundary block;
+//! * the body matches the proofs in the header;
+//! * the header and the block are within the size limits.
+//!
+
+use std::{fmt, result};
+use wallet_crypto::{cbor, tx, hash::{Blake2b256}};
+use wallet_crypto::config::{ProtocolMagic};
+use wallet_crypto::address::{StakeholderId};
+
+use types::{HeaderHash, ChainDifficulty, EpochId, SlotId, SscProof};
+use block::{BlockHeader, Block, BlockDate, RawBlock};
+use genesis;
+use normal;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the header's protocol magic (second) is not the expected one (first)
+    WrongProtocolMagic(ProtocolMagic, ProtocolMagic),
+    /// the header's previous hash (second) does not link to the expected hash (first)
+    WrongPreviousHeader(HeaderHash, HeaderHash),
+    /// the block date (second) does not follow the date of the previous block (first)
+    NonMonotonicDate(BlockDate, BlockDate),
+    /// the epoch boundary block (second) is not the one expected after the previous block (first)
+    UnexpectedEpochBoundary(BlockDate, BlockDate),
+    /// the main block (second) belongs to a different epoch than the previous block (first)
+    MissingEpochBoundary(BlockDate, BlockDate),
+    /// the slot is beyond the number of slots in an epoch
+    SlotOutOfEpoch(SlotId),
+    /// the chain difficulty (second) is not the expected one (first)
+    WrongChainDifficulty(ChainDifficulty, ChainDifficulty),
+    /// the main block header is not signed by its slot leader
+    InvalidSignature(HeaderHash),
+    /// the slot leader of the main block (third) is not the scheduled one (second)
+    WrongSlotLeader(SlotId, StakeholderId, StakeholderId),
+    /// no slot leader is scheduled for the given slot
+    NoSlotLeader(SlotId),
+    /// the body of the block does not match the header's body proof
+    WrongBodyProof(HeaderHash),
+    /// the header (of the given size) is larger than the allowed maximum
+    HeaderTooLarge(usize, usize),
+    /// the block (of the given size) is larger than the allowed maximum
+    BlockTooLarge(usize, usize),
+    /// the block could not be decoded
+    CborError(cbor::Value, cbor::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::WrongProtocolMagic(expected, got) => write!(f, "wrong protocol magic, expected {} but got {}", expected, got),
+            &Error::WrongPreviousHeader(ref expected, ref got) => write!(f, "previous header doesn't match, expected {} but got {}", expected, got),
+            &Error::NonMonotonicDate(ref previous, ref got) => write!(f, "block date {} does not follow {}", got, previous),
+            &Error::UnexpectedEpochBoundary(ref previous, ref got) => write!(f, "unexpected epoch boundary block {} after {}", got, previous),
+            &Error::MissingEpochBoundary(ref previous, ref got) => write!(f, "missing epoch boundary block between {} and {}", previous, got),
+            &Error::SlotOutOfEpoch(ref slot) => write!(f, "slot {} is out of the epoch", slot),
+            &Error::WrongChainDifficulty(expected, got) => write!(f, "wrong chain difficulty, expected {} but got {}", expected, got),
+            &Error::InvalidSignature(ref hash) => write!(f, "invalid block signature for block {}", hash),
+            &Error::WrongSlotLeader(ref slot, ref expected, ref got) => write!(f, "wrong slot leader for slot {}, expected {} but got {}", slot, expected, got),
+            &Error::NoSlotLeader(ref slot) => write!(f, "no slot leader scheduled for slot {}", slot),
+            &Error::WrongBodyProof(ref hash) => write!(f, "the body does not match the proof of block {}", hash),
+            &Error::HeaderTooLarge(sz, max) => write!(f, "header of {} bytes is larger than the maximum of {} bytes", sz, max),
+            &Error::BlockTooLarge(sz, max) => write!(f, "block of {} bytes is larger than the maximum of {} bytes", sz, max),
+            &Error::CborError(_, ref err) => write!(f, "invalid block encoding: {:?}", err),
+        }
+    }
+}
+impl From<(cbor::Value, cbor::Error)> for Error {
+    fn from((v, e): (cbor::Value, cbor::Error)) -> Self { Error::CborError(v, e) }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// the protocol parameters the validation depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameters {
+    pub protocol_magic: ProtocolMagic,
+    /// number of slots in an epoch
+    pub epoch_slots: u32,
+    pub max_header_size: usize,
+    pub max_block_size: usize,
+}
+impl Parameters {
+    pub fn new(protocol_magic: ProtocolMagic) -> Self {
+        Parameters { protocol_magic: protocol_magic, .. Parameters::default() }
+    }
+}
+impl Default for Parameters {
+    /// the mainnet parameters
+    fn default() -> Self {
+        Parameters {
+            protocol_magic: ProtocolMagic::default(),
+            epoch_slots: 21600,
+            max_header_size: 2_000_000,
+            max_block_size: 2_000_000,
+        }
+    }
+}
+
+/// check the properties of the header that do not depend on the chain:
+/// the protocol magic, the slot and the signature.
+pub fn verify_header(params: &Parameters, hdr: &BlockHeader) -> Result<()> {
+    let protocol_magic = match hdr {
+        &BlockHeader::GenesisBlockHeader(ref h) => h.protocol_magic,
+        &BlockHeader::MainBlockHeader(ref h) => h.protocol_magic,
+    };
+    if protocol_magic != params.protocol_magic {
+        return Err(Error::WrongProtocolMagic(params.protocol_magic, protocol_magic));
+    }
+
+    let size = cbor::encode_to_cbor(hdr).unwrap().len();
+    if size > params.max_header_size {
+        return Err(Error::HeaderTooLarge(size, params.max_header_size));
+    }
+
+    if let &BlockHeader::MainBlockHeader(ref h) = hdr {
+        if h.consensus.slot_id.slotid >= params.epoch_slots {
+            return Err(Error::SlotOutOfEpoch(h.consensus.slot_id.clone()));
+        }
+        if ! h.verify_signature() {
+            return Err(Error::InvalidSignature(hdr.compute_hash()));
+        }
+    }
+    Ok(())
+}
+
+/// check the body of the block matches the proof of its header
+///
+/// The SSC proof is computed by the nodes over an internal representation
+/// of the payload, so only its kind is checked against the payload's.
+pub fn verify_body(blk: &Block) -> Result<()> {
+    let valid = match blk {
+        &Block::GenesisBlock(ref b) => {
+            genesis::BodyProof::generate_from_body(&b.body) == b.header.body_proof
+        },
+        &Block::MainBlock(ref b) => {
+            let proof = &b.header.body_proof;
+            proof.tx == tx::TxProof::generate(b.body.tx.iter())
+                && ssc_proof_kind(&proof.mpc) == ssc_payload_kind(&b.body.scc)
+                && proof.proxy_sk == hash_value(&b.body.delegation)
+                && proof.update == hash_value(&b.body.update)
+        },
+    };
+    if valid { Ok(()) } else { Err(Error::WrongBodyProof(blk.get_header().compute_hash())) }
+}
+
+/// check the main block header is issued by the slot leader scheduled
+/// in the given epoch boundary block's body.
+pub fn verify_slot_leader(leaders: &genesis::Body, hdr: &normal::BlockHeader) -> Result<()> {
+    let slot = &hdr.consensus.slot_id;
+    match leaders.get_slot_leader(slot.slotid) {
+        None => Err(Error::NoSlotLeader(slot.clone())),
+        Some(expected) => {
+            let got = StakeholderId::new(&hdr.consensus.leader_key);
+            if &got != expected {
+                return Err(Error::WrongSlotLeader(slot.clone(), expected.clone(), got));
+            }
+            Ok(())
+        },
+    }
+}
+
+fn hash_value(value: &cbor::Value) -> Blake2b256 {
+    Blake2b256::new(&cbor::encode_to_cbor(value).unwrap())
+}
+
+fn ssc_proof_kind(proof: &SscProof) -> Option<u64> {
+    match proof {
+        &SscProof::Commitments(_, _) => Some(0),
+        &SscProof::Openings(_, _) => Some(1),
+        &SscProof::Shares(_, _) => Some(2),
+        &SscProof::Certificate(_) => Some(3),
+    }
+}
+fn ssc_payload_kind(payload: &cbor::Value) -> Option<u64> {
+    match payload {
+        &cbor::Value::Array(ref v) => match v.first() {
+            Some(&cbor::Value::U64(kind)) => Some(kind),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// the tip of the chain being validated
+#[derive(Debug, Clone)]
+pub struct Validator {
+    params: Parameters,
+    tip: HeaderHash,
+    date: Option<BlockDate>,
+    difficulty: Option<ChainDifficulty>,
+    leaders: Option<(EpochId, genesis::Body)>,
+}
+impl Validator {
+    /// start validating a chain from the given hash, of which we know
+    /// nothing else (e.g. the `genesis_prev` hash of a network).
+    pub fn new(params: Parameters, tip: HeaderHash) -> Self {
+        Validator { params: params, tip: tip, date: None, difficulty: None, leaders: None }
+    }
+
+    /// start validating a chain from the given, already validated, header
+    pub fn from_header(params: Parameters, hdr: &BlockHeader) -> Self {
+        Validator {
+            params: params,
+            tip: hdr.compute_hash(),
+            date: Some(hdr.get_blockdate()),
+            difficulty: Some(hdr.get_chain_difficulty()),
+            leaders: None,
+        }
+    }
+
+    /// the hash of the last validated header
+    pub fn tip(&self) -> &HeaderHash { &self.tip }
+
+    /// the date of the last validated header, if known
+    pub fn date(&self) -> Option<&BlockDate> { self.date.as_ref() }
+
+    /// the slot leaders of the current epoch, if its epoch boundary
+    /// block has been validated.
+    pub fn slot_leaders(&self) -> Option<&genesis::Body> {
+        match self.leaders {
+            Some((epoch, ref leaders)) if self.date.as_ref().map(|d| d.get_epochid()) == Some(epoch) => Some(leaders),
+            _ => None,
+        }
+    }
+
+    fn verify_date(&self, date: &BlockDate) -> Result<()> {
+        let previous = match self.date {
+            None => return Ok(()),
+            Some(ref previous) => previous,
+        };
+        let expected_epoch : EpochId = previous.get_epochid() + 1;
+        match (previous, date) {
+            (_, &BlockDate::Genesis(epoch)) => {
+                if epoch != expected_epoch {
+                    return Err(Error::UnexpectedEpochBoundary(previous.clone(), date.clone()));
+                }
+            },
+            (&BlockDate::Genesis(epoch), &BlockDate::Normal(ref slot)) => {
+                if slot.epoch != epoch {
+                    return Err(Error::MissingEpochBoundary(previous.clone(), date.clone()));
+                }
+            },
+            (&BlockDate::Normal(ref prev), &BlockDate::Normal(ref slot)) => {
+                if slot.epoch != prev.epoch {
+                    return Err(Error::MissingEpochBoundary(previous.clone(), date.clone()));
+                }
+                if slot.slotid <= prev.slotid {
+                    return Err(Error::NonMonotonicDate(previous.clone(), date.clone()));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn verify_difficulty(&self, hdr: &BlockHeader) -> Result<()> {
+        let previous = match self.difficulty {
+            None => return Ok(()),
+            Some(previous) => u64::from(previous),
+        };
+        let expected = if hdr.is_genesis_block() { previous } else { previous + 1 };
+        let got = hdr.get_chain_difficulty();
+        if u64::from(got) != expected {
+            return Err(Error::WrongChainDifficulty(ChainDifficulty::from(expected), got));
+        }
+        Ok(())
+    }
+
+    /// validate the given header against the current tip and, if valid,
+    /// make it the new tip.
+    pub fn validate_header(&mut self, hdr: &BlockHeader) -> Result<()> {
+        let previous_header = hdr.get_previous_header();
+        if previous_header != self.tip {
+            return Err(Error::WrongPreviousHeader(self.tip.clone(), previous_header));
+        }
+        let date = hdr.get_blockdate();
+        self.verify_date(&date)?;
+        self.verify_difficulty(hdr)?;
+        verify_header(&self.params, hdr)?;
+
+        self.tip = hdr.compute_hash();
+        self.date = Some(date);
+        self.difficulty = Some(hdr.get_chain_difficulty());
+        Ok(())
+    }
+
+    /// decode and validate the given block (header and body) against the
+    /// current tip and, if valid, make it the new tip.
+    pub fn validate_block(&mut self, raw: &RawBlock) -> Result<Block> {
+        let size = raw.as_ref().len();
+        if size > self.params.max_block_size {
+            return Err(Error::BlockTooLarge(size, self.params.max_block_size));
+        }
+        let blk = raw.decode()?;
+        verify_body(&blk)?;
+        if let &Block::MainBlock(ref b) = &blk {
+            if let Some(leaders) = self.slot_leaders() {
+                verify_slot_leader(leaders, &b.header)?;
+            }
+        }
+        self.validate_header(&blk.get_header())?;
+        if let &Block::GenesisBlock(ref b) = &blk {
+            self.leaders = Some((b.header.consensus.epoch, b.body.clone()));
+        }
+        Ok(blk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wallet_crypto::util::hex;
+
+    const GENESIS_HEADER: &str = "820085005820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d225820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d228201810081a0";
+
+    fn genesis_header() -> BlockHeader {
+        cbor::decode_from_cbor(&hex::decode(GENESIS_HEADER).unwrap()).unwrap()
+    }
+
+    const MAIN_HEADER: &str = "85005820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22848301582096d38c5aafb839450511e1bae3b4ecde215888dee3403526e2373d016fdfdd1e582083ac5d0d6ac0c02abf8c5ad766d0135873ca4ac53dd582187c9aa15aa149c0da82035820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d225820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d225820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22848201182a58401c0c3ae1825e90b6ddda3f40a122c007e1008e83b2e102c142baefb721d72c1a5d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b1281182a82005840a90522874cccf9a67e209031fd9dfe37a82fd943dee63300aa823cb98e0f704e913f6e02b2aa0a33693e052c15f43aee242164d2812a572b2774c1b5ada818018483000100826a63617264616e6f2d736c00a05820c4e0fc3a4ffb3191f88b26a9834453cbac0e6b9c8d8f7ae810696bee575d1d22";
+
+    fn params() -> Parameters { Parameters::new(ProtocolMagic::new(0)) }
+
+    // a mainnet block (epoch 1, slot 14258) with 3 transactions
+    const MAINNET_BLOCK: &str = "820183851a2d964a0958203e705e9aa27fe54e2c662a0a5aa80c360bd47ce24bb9429dfa4fdf170c2ded8184830358200a56168c95c6781fe37e68539b6cef88cee1b472e1d29a7be3ed497929c29c3d58204fa3ffe49fc2359eaeb5e24e70c07ae952000c39c90fa6719528b6ab27d0393f82035820d36a2619a672494604e11bb447cbcf5231e9f2ba25c2169177edc941bd50ad6c5820afc0da64183bf2664f3d4eec7238d524ba607faeeab24fc100eb861dba69971b58204e66280cd94d591072349bec0a3090a53aa945562efb6d08d56e53654b0e40988482011937b258401bc97a2fe02c297880ce8ecfd997fe4c1ec09ee10feeee9f686760166b05281d6283468ffd93becb0c956ccddd642df9b1244c915911185fa49355f6f22bfab981198bfe820282840058401bc97a2fe02c297880ce8ecfd997fe4c1ec09ee10feeee9f686760166b05281d6283468ffd93becb0c956ccddd642df9b1244c915911185fa49355f6f22bfab9584061261a95b7613ee6bf2067dad77b70349729b0c50d57bc1cf30de0db4a1e73a885d0054af7c23fc6c37919dba41c602a57e2d0f9329a7954b867338d6fb2c9455840e03e62f083df5576360e60a32e22bbb07b3c8df4fcab8079f1d6f61af3954d242ba8a06516c395939f24096f3df14e103a7d9c2b80a68a9363cf1f27c7a4e30758402a64f299c7fe54433389ca74c7cf8e2c35ff463a3612f08cb56aceb59efc75db4748ad7c12f74189fde573699148e0fcf978f291d0c1dea6f7f5d98a0cb11b058483000000826a63617264616e6f2d736c01a058204ba92aa320c60acc9ad7b9a64f2eda55c4d2ec28e604faf186708b4f0c4e8edf849f82839f8200d8185824825820c4c98f60c84b4ddcc8c5eeb74df6d0e63aaa83617f8d96481b42264c739f3e9801ff9f8282d818584283581c6d2925ff0e0ca46221cee39fb4f566daae8f91dae7f3a6c51b3eb069a101581e581c9ce9955113dbdfb81acaca590783ad7d1cdd1396fe905a322b062e2a001a846711f91b00000002739c7d1f8282d818584283581cc641a9e593bfaf1d6c9b35317e07376267b8ea10e36e961a0e52ee46a101581e581cca3e553c9c63c5553f4e0f43c0fb2011f9a741fdbe324fdcdb6b6c76001a5590b0711b0000000773594000ffa0818200d8185885825840342122d9c424518f351a066849ac8f7f522f0e5ceeebb79d5bdbd2e5c3ef6a81c20a923010f85979133c51a73827efa7cc36bae63007c731a63de51ccd9958975840b9641b8d5b6b02f95a677a2d440ff942c2afbe9c1ecf4a921150d291f99001c7705d8eeb47f1b358159ca9613744e2aea2a6a4c38f7bc1bdac20879166fb960d82839f8200d8185824825820fef988b1e9cc31ff29bb016749a543f07659ad61e677663d9f1d75f15ef96c9b00ff9f8282d818584283581cfde8dcf123e612cb41f505628c5ef242778d6c665635b7f607a26d36a101581e581cca3e553c9c63c536bd563243dd464b372ddfc51e8730f5213453d7d4001a1827e7ce1b0000442a3d3148b68282d818584283581ca4619457a8825f2c60303dcbe10e37ed72a214d716d050e4c438945ca101581e581cca3e553c9c63c57fc4222243746b3a5f31c8f74d5507381542f67f7f001ac4459d501a00958940ffa0818200d81858858258409bb84a56ad61d0dfd6047eca463b6e691a8be8dc064d004e5c9b79752155b6790aa79ccaefb04cab5f636cd48f7f9395926d565fe77fd724c5ede7dc3e2396dc584023752530be6a66efb9c46476b92b7fc9769bb42d33d2168abfeb2ac258f9323fb3513c982a0d4e839ce29612a56ea8aca637a90d87635dd925fe1d6e95e46b0282839f8200d8185824825820c7e7015cfa4b4412e0b934eacc9da701a0b59aedf282292b4d2fa42d9e707a6100ff9f8282d818584283581ca3da056ff5c2080e6532221f1d7329da2d3568a1416f5d9ddc583277a101581e581cd4d66457f7e68912e90e4353f948f36ecbcc22674996b9b28f806b4e001ab5b30d631b0000004b318ef6808282d818584283581c06fb4fb5c09550e5364cd65e246f6e1547c94b0cb6f454fffdaa7c18a101581e581cca3e553c9c63c578a5d6524349f77b6aa4b75e05bcc62d4f9c04433e001a7d33d6b81b0000011b0f9f1592ffa0818200d81858858258402659b6c9a2673b51ea126166f6e82d7fdd3fb624c1b17354c9acf52b72a150c5668b74bef0a3eb103dbe760c2b816dee77034e69c5141eba709e18011bd0f0c95840322826e7575926ce95548a0ccee9929c3c27066f14b1b9199187412e99ceb78d48dfd39a58bbf654aa367c5474908228edfe796cd4f2b1d5a296220191dce501ff8203d90102809fff82809fff81a0";
+
+    #[test]
+    fn validate_mainnet_block() {
+        let raw = RawBlock::from_dat(hex::decode(MAINNET_BLOCK).unwrap());
+        let hdr = raw.decode().unwrap().get_header();
+        let mut validator = Validator::new(Parameters::default(), hdr.get_previous_header());
+        let blk = validator.validate_block(&raw).unwrap();
+        assert_eq!(validator.tip(), &hdr.compute_hash());
+        match blk {
+            Block::MainBlock(ref b) => assert_eq!(b.body.tx.iter().count(), 3),
+            _ => panic!("expected a main block"),
+        }
+
+        // the body is checked against the header's proof
+        let mut blk = raw.decode().unwrap();
+        if let Block::MainBlock(ref mut b) = blk {
+            b.body.tx = normal::TxPayload::new(b.body.tx.iter().skip(1).cloned().collect());
+        }
+        assert_eq!(verify_body(&blk), Err(Error::WrongBodyProof(hdr.compute_hash())));
+    }
+
+    #[test]
+    fn validate_genesis() {
+        let hdr = genesis_header();
+        let mut validator = Validator::new(params(), hdr.get_previous_header());
+        assert_eq!(validator.validate_header(&hdr), Ok(()));
+        assert_eq!(validator.tip(), &hdr.compute_hash());
+        assert_eq!(validator.date(), Some(&BlockDate::Genesis(1)));
+    }
+
+    #[test]
+    fn validate_wrong_previous_header() {
+        let hdr = genesis_header();
+        let tip = HeaderHash::new(&[]);
+        let mut validator = Validator::new(params(), tip.clone());
+        assert_eq!(validator.validate_header(&hdr), Err(Error::WrongPreviousHeader(tip, hdr.get_previous_header())));
+    }
+
+    #[test]
+    fn validate_wrong_protocol_magic() {
+        let hdr = genesis_header();
+        let mut validator = Validator::new(Parameters::default(), hdr.get_previous_header());
+        assert_eq!(validator.validate_header(&hdr), Err(Error::WrongProtocolMagic(ProtocolMagic::default(), ProtocolMagic::new(0))));
+    }
+
+    #[test]
+    fn validate_epoch_boundary() {
+        let hdr = genesis_header();
+        let mut validator = Validator::new(params(), hdr.get_previous_header());
+        validator.validate_header(&hdr).unwrap();
+
+        // a second epoch boundary block for the same epoch
+        let mut next = hdr.clone();
+        if let BlockHeader::GenesisBlockHeader(ref mut h) = next { h.previous_header = hdr.compute_hash(); }
+        assert_eq!(validator.validate_header(&next), Err(Error::UnexpectedEpochBoundary(BlockDate::Genesis(1), BlockDate::Genesis(1))));
+
+        // the next epoch boundary block
+        if let BlockHeader::GenesisBlockHeader(ref mut h) = next { h.consensus.epoch = 2; }
+        assert_eq!(validator.validate_header(&next), Ok(()));
+    }
+
+    #[test]
+    fn validate_chain_difficulty() {
+        let hdr = genesis_header();
+        let mut validator = Validator::new(params(), hdr.get_previous_header());
+        validator.validate_header(&hdr).unwrap();
+
+        let mut next = hdr.clone();
+        if let BlockHeader::GenesisBlockHeader(ref mut h) = next {
+            h.previous_header = hdr.compute_hash();
+            h.consensus.epoch = 2;
+            h.consensus.chain_difficulty = ChainDifficulty::from(1);
+        }
+        assert_eq!(validator.validate_header(&next), Err(Error::WrongChainDifficulty(ChainDifficulty::from(0), ChainDifficulty::from(1))));
+    }
+
+    #[test]
+    fn validate_slot_leader() {
+        let hdr : normal::BlockHeader = cbor::decode_from_cbor(&hex::decode(MAIN_HEADER).unwrap()).unwrap();
+        let slot = hdr.consensus.slot_id.clone();
+        let leader = StakeholderId::new(&hdr.consensus.leader_key);
+        let other = StakeholderId::new(&hdr.consensus.leader_key.derive(wallet_crypto::hdwallet::DerivationScheme::V2, 0).unwrap());
+
+        let mut body = genesis::Body { slot_leaders: vec![other; slot.slotid as usize] };
+        assert_eq!(verify_slot_leader(&body, &hdr), Err(Error::NoSlotLeader(slot.clone())));
+
+        body.slot_leaders.push(other);
+        assert_eq!(verify_slot_leader(&body, &hdr), Err(Error::WrongSlotLeader(slot.clone(), other, leader)));
+
+        body.slot_leaders[slot.slotid as usize] = leader;
+        assert_eq!(body.get_slot_leader(slot.slotid), Some(&leader));
+        assert_eq!(verify_slot_leader(&body, &hdr), Ok(()));
+
+        let decoded : genesis::Body = cbor::decode_from_cbor(&cbor::encode_to_cbor(&body).unwrap()).unwrap();
+        assert_eq!(decoded.slot_leaders, body.slot_leaders);
+    }
+}
@@ -1,7 +1,12 @@
 pub mod net {
     use blockchain::{HeaderHash,EpochId};
-    use wallet_crypto::config::{ProtocolMagic};
-    use std::{path::{Path}, fs::{self, File}, fmt, slice::{Iter}, ops::{Deref, DerefMut}};
+    use blockchain::validation;
+    use blockchain::time::{TimeParameters};
+    use blockchain::genesis_data::{GenesisData};
+    use wallet_crypto::config::{ProtocolMagic, RequiresNetworkMagic};
+    use wallet_crypto::tx::fee::{LinearFee};
+    use wallet_crypto::tx::validation as tx_validation;
+    use std::{path::{Path}, fs::{self, File}, fmt, time::{Duration, UNIX_EPOCH}, slice::{Iter}, ops::{Deref, DerefMut}};
     use storage::tmpfile::{TmpFile};
     use serde_yaml;
     use serde;
@@ -182,14 +187,58 @@ pub mod net {
         }
     }
 
+    /// the number of slots in an epoch and the size limits of the blocks
+    /// and of the transactions of a network, as set by its genesis data
+    /// (the mainnet's by default)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct BlockParameters {
+        pub epoch_slots: u32,
+        pub max_header_size: usize,
+        pub max_block_size: usize,
+        #[serde(default = "default_max_tx_size")]
+        pub max_tx_size: usize,
+    }
+    fn default_max_tx_size() -> usize { tx_validation::MAX_TX_SIZE }
+    impl Default for BlockParameters {
+        fn default() -> Self {
+            let params = validation::Parameters::default();
+            BlockParameters { epoch_slots: params.epoch_slots, max_header_size: params.max_header_size, max_block_size: params.max_block_size, max_tx_size: default_max_tx_size() }
+        }
+    }
+    impl<'a> From<&'a GenesisData> for BlockParameters {
+        fn from(genesis: &'a GenesisData) -> Self {
+            let params = genesis.validation_parameters();
+            let tx_params = genesis.tx_validation_parameters();
+            BlockParameters { epoch_slots: params.epoch_slots, max_header_size: params.max_header_size, max_block_size: params.max_block_size, max_tx_size: tx_params.max_tx_size }
+        }
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Config {
         pub genesis: HeaderHash,
         pub genesis_prev: HeaderHash,
         pub protocol_magic: ProtocolMagic,
+        /// do the addresses of the network carry its protocol magic
+        #[serde(default)]
+        pub requires_network_magic: RequiresNetworkMagic,
         pub epoch_start: EpochId,
-        pub peers: Peers
+        pub peers: Peers,
+        /// the transaction fee policy of the network: the one of the
+        /// genesis data (see `with_genesis_data`, the mainnet's by
+        /// default), until an update proposal changes it
+        #[serde(default)]
+        pub tx_fee_policy: LinearFee,
+        #[serde(default)]
+        pub block_parameters: BlockParameters,
+        /// start of the network in seconds since the unix epoch, as set by
+        /// its genesis data (`None` if unknown)
+        #[serde(default)]
+        pub system_start: Option<u64>,
+        /// duration of a slot in milliseconds
+        #[serde(default = "default_slot_duration")]
+        pub slot_duration: u64,
     }
+    fn default_slot_duration() -> u64 { 20000 }
     impl Config {
         pub fn mainnet() -> Self {
             let mut peers = Peers::new();
@@ -199,8 +248,13 @@ pub mod net {
                 genesis: HeaderHash::from_hex(&"89D9B5A5B8DDC8D7E5A6795E9774D97FAF1EFEA59B2CAF7EAF9F8C5B32059DF4").unwrap(),
                 genesis_prev: HeaderHash::from_hex(&"5f20df933584822601f9e3f8c024eb5eb252fe8cefb24d1317dc3d432e940ebb").unwrap(),
                 protocol_magic: ProtocolMagic::default(),
+                requires_network_magic: RequiresNetworkMagic::RequiresNoMagic,
                 epoch_start: 0,
-                peers: peers
+                peers: peers,
+                tx_fee_policy: LinearFee::default(),
+                block_parameters: BlockParameters::default(),
+                system_start: Some(1506203091),
+                slot_duration: default_slot_duration(),
             }
         }
 
@@ -212,11 +266,54 @@ pub mod net {
                 genesis: HeaderHash::from_hex(&"B365F1BE6863B453F12B93E1810909B10C79A95EE44BF53414888513FE172C90").unwrap(),
                 genesis_prev: HeaderHash::from_hex(&"c6a004d3d178f600cd8caa10abbebe1549bef878f0665aea2903472d5abf7323").unwrap(),
                 protocol_magic: ProtocolMagic::new(633343913),
+                requires_network_magic: RequiresNetworkMagic::RequiresNoMagic,
                 epoch_start: 0,
-                peers: peers
+                peers: peers,
+                tx_fee_policy: LinearFee::default(),
+                block_parameters: BlockParameters::default(),
+                system_start: None,
+                slot_duration: default_slot_duration(),
             }
         }
 
+        /// set the parameters of the network from its genesis data: its
+        /// protocol magic, transaction fee policy, block and transaction
+        /// limits and time parameters
+        pub fn with_genesis_data(mut self, genesis: &GenesisData) -> Self {
+            self.protocol_magic = genesis.protocol_constants.protocol_magic;
+            self.requires_network_magic = genesis.protocol_constants.requires_network_magic;
+            self.tx_fee_policy = genesis.block_version_data.tx_fee_policy.linear_fee();
+            self.block_parameters = BlockParameters::from(genesis);
+            self.system_start = Some(genesis.start_time);
+            self.slot_duration = genesis.block_version_data.slot_duration;
+            self
+        }
+
+        /// the parameters to validate the blocks of the network with
+        pub fn validation_parameters(&self) -> validation::Parameters {
+            validation::Parameters {
+                protocol_magic: self.protocol_magic,
+                epoch_slots: self.block_parameters.epoch_slots,
+                max_header_size: self.block_parameters.max_header_size,
+                max_block_size: self.block_parameters.max_block_size,
+            }
+        }
+
+        /// the limits of the transactions of the network
+        pub fn tx_validation_parameters(&self) -> tx_validation::Parameters {
+            tx_validation::Parameters { max_tx_size: self.block_parameters.max_tx_size, .. tx_validation::Parameters::default() }
+        }
+
+        /// the parameters relating the block dates to the wall clock, if
+        /// the start of the network is known
+        pub fn time_parameters(&self) -> Option<TimeParameters> {
+            self.system_start.map(|start| TimeParameters {
+                system_start: UNIX_EPOCH + Duration::from_secs(start),
+                slot_duration: Duration::from_millis(self.slot_duration),
+                epoch_slots: self.block_parameters.epoch_slots,
+            })
+        }
+
         pub fn from_file<P: AsRef<Path>>(p: P) -> Option<Self> {
             let path = p.as_ref();
             if ! path.is_file() {
@@ -1,6 +1,7 @@
 use std::{io};
 use protocol::{self, ntt};
 use wallet_crypto::{cbor};
+use blockchain::{validation, BlockDate, EpochId};
 use hyper;
 
 #[derive(Debug)]
@@ -10,7 +11,18 @@ pub enum Error {
     ProtocolError(protocol::Error),
     CborError(cbor::Value, cbor::Error),
     HyperError(hyper::Error),
+    ValidationError(validation::Error),
     ConnectionTimedOut,
+    /// a command sent to the peer failed
+    CommandFailed(&'static str),
+    /// the peer returned no headers or no blocks
+    NoBlocks,
+    /// the headers returned by the peer do not follow the expected block
+    /// date (first): the earliest header returned is at the second date
+    HeadersGap(BlockDate, BlockDate),
+    /// the block (of the given date) does not belong to the epoch being
+    /// downloaded
+    UnexpectedEpoch(EpochId, BlockDate),
 }
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self { Error::IoError(e) }
@@ -24,6 +36,9 @@ impl From<hyper::Error> for Error {
 impl From<ntt::Error> for Error {
     fn from(e: ntt::Error) -> Self { Error::NttError(e) }
 }
+impl From<validation::Error> for Error {
+    fn from(e: validation::Error) -> Self { Error::ValidationError(e) }
+}
 impl From<(cbor::Value, cbor::Error)> for Error {
     fn from((v, e): (cbor::Value, cbor::Error)) -> Self { Error::CborError(v, e) }
 }
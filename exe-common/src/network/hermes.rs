@@ -1,4 +1,4 @@
-use blockchain::{BlockHeader, Block, HeaderHash};
+use blockchain::{BlockHeader, Block, HeaderHash, validation};
 use storage::{self, Storage, types::{PackHash}, tmpfile::{TmpFile}};
 use std::io::{Write, Seek, SeekFrom};
 use std::time::{SystemTime, Duration};
@@ -39,7 +39,7 @@ impl Api for HermesEndPoint {
         unimplemented!()
     }
 
-    fn fetch_epoch(&mut self, _config: &net::Config, storage: &mut Storage, fep: FetchEpochParams) -> Result<FetchEpochResult> {
+    fn fetch_epoch(&mut self, config: &net::Config, storage: &mut Storage, fep: FetchEpochParams) -> Result<FetchEpochResult> {
         let path = format!("epoch/{}", fep.epoch_id);
 
         let mut tmppack = TmpFile::create(storage.config.get_filetype_dir(storage::types::StorageFileType::Pack))?;
@@ -62,12 +62,12 @@ impl Api for HermesEndPoint {
         tmppack.seek(SeekFrom::Start(0))?;
         let mut packfile = storage::pack::PackReader::from(tmppack);
         let mut packwriter = storage::pack::PackWriter::init(&storage.config);
+        let mut validator = validation::Validator::new(config.validation_parameters(), fep.previous_header_hash.clone());
         let mut last = None;
         while let Some(rblock) = packfile.get_next() {
-            let rhdr = rblock.to_header();
-            // TODO: do some checks: let block = rblock.decode()?;
-            last = Some(rhdr.decode()?);
-            packwriter.append(rhdr.compute_hash().bytes(), rblock.as_ref());
+            let hdr = validator.validate_block(&rblock)?.get_header();
+            packwriter.append(hdr.compute_hash().bytes(), rblock.as_ref());
+            last = Some(hdr);
         }
 
         let (packhash, index) = packwriter.finalize();
@@ -82,8 +82,9 @@ impl Api for HermesEndPoint {
         let time_elapsed = now.elapsed().unwrap();
         info!("Processing EPOCH in {}sec", time_elapsed.as_secs());
 
+        // the next epoch's blocks are validated against the last block of this one
         Ok(FetchEpochResult {
-            previous_last_header_hash: last_hdr.get_previous_header(),
+            previous_last_header_hash: last_hdr.compute_hash(),
             last_header_hash: last_hdr.compute_hash(),
             packhash: packhash
         })
@@ -3,7 +3,7 @@ use mstream::{MStream, MetricStart, MetricStats};
 use wallet_crypto::{config::{ProtocolMagic}, util::{hex}, cbor};
 use rand;
 use std::{net::{SocketAddr, ToSocketAddrs}, ops::{Deref, DerefMut}};
-use blockchain::{self, BlockHeader, Block, HeaderHash, EpochId, BlockDate, SlotId};
+use blockchain::{self, BlockHeader, Block, HeaderHash, EpochId, BlockDate, SlotId, validation};
 use storage::{self, Storage, types::{PackHash}};
 use protocol::command::*;
 use std::time::{SystemTime, Duration};
@@ -112,25 +112,27 @@ impl OpenPeer {
 }
 impl Api for OpenPeer {
     fn get_tip(&mut self) -> Result<BlockHeader> {
-        let block_headers_raw = GetBlockHeader::tip().execute(&mut self.0).expect("to get one header at least");
+        let block_headers_raw = GetBlockHeader::tip().execute(&mut self.0).map_err(Error::CommandFailed)?;
 
-        let block_headers = block_headers_raw.decode()?;
+        let mut block_headers = block_headers_raw.decode()?;
 
         if block_headers.len() != 1 {
-            panic!("get head header return more than 1 header")
+            return Err(Error::CommandFailed("get head header did not return exactly 1 header"));
         }
-        Ok(block_headers[0].clone())
+        Ok(block_headers.remove(0))
     }
 
     fn get_block(&mut self, hash: HeaderHash) -> Result<Block> {
-        let b = GetBlock::only(&hash).execute(&mut self.0)
-            .expect("to get one block at least");
+        let b = GetBlock::only(&hash).execute(&mut self.0).map_err(Error::CommandFailed)?;
 
-        Ok(cbor::decode_from_cbor(b[0].as_ref())?)
+        match b.first() {
+            None => Err(Error::NoBlocks),
+            Some(block_raw) => Ok(cbor::decode_from_cbor(block_raw.as_ref())?),
+        }
     }
 
-    fn fetch_epoch(&mut self, _config: &net::Config, storage: &mut Storage, fep: FetchEpochParams) -> Result<FetchEpochResult> {
-        let result = download_epoch(storage, self, fep.epoch_id, &fep.start_header_hash, &fep.previous_header_hash, &fep.upper_bound_hash);
+    fn fetch_epoch(&mut self, config: &net::Config, storage: &mut Storage, fep: FetchEpochParams) -> Result<FetchEpochResult> {
+        let result = download_epoch(config, storage, self, fep.epoch_id, &fep.start_header_hash, &fep.previous_header_hash, &fep.upper_bound_hash)?;
         Ok(FetchEpochResult {
             previous_last_header_hash: result.0,
             last_header_hash: result.1,
@@ -139,30 +141,31 @@ impl Api for OpenPeer {
     }
 }
 
-fn network_get_blocks_headers(net: &mut OpenPeer, from: &blockchain::HeaderHash, to: &blockchain::HeaderHash) -> blockchain::RawBlockHeaderMultiple {
-    let mbh = GetBlockHeader::range(&vec![from.clone()], to.clone()).execute(&mut net.0).expect("to get one header at least");
-    mbh
+fn network_get_blocks_headers(net: &mut OpenPeer, from: &blockchain::HeaderHash, to: &blockchain::HeaderHash) -> Result<blockchain::RawBlockHeaderMultiple> {
+    GetBlockHeader::range(&vec![from.clone()], to.clone()).execute(&mut net.0).map_err(Error::CommandFailed)
 }
 
-fn download_epoch(storage: &Storage, net: &mut OpenPeer,
+fn download_epoch(config: &net::Config, storage: &Storage, net: &mut OpenPeer,
                   epoch_id: EpochId,
                   x_start_hash: &HeaderHash,
                   x_previous_headerhash: &HeaderHash,
-                  tip_hash: &HeaderHash) -> (HeaderHash, HeaderHash, PackHash) {
+                  tip_hash: &HeaderHash) -> Result<(HeaderHash, HeaderHash, PackHash)> {
     let mut start_hash = x_start_hash.clone();
     let mut found_epoch_boundary = None;
     let mut writer = storage::pack::PackWriter::init(&storage.config);
     let mut previous_headerhash = x_previous_headerhash.clone();
+    let mut validator = validation::Validator::new(config.validation_parameters(), previous_headerhash.clone());
     let epoch_time_start = SystemTime::now();
     let mut expected_slotid = blockchain::BlockDate::Genesis(epoch_id);
 
     loop {
         info!("  ### slotid={} from={}", expected_slotid, start_hash);
         let metrics = net.read_start();
-        let block_headers_raw = network_get_blocks_headers(net, &start_hash, tip_hash);
+        let block_headers_raw = network_get_blocks_headers(net, &start_hash, tip_hash)?;
         let hdr_metrics = net.read_elapsed(&metrics);
-        let block_headers = block_headers_raw.decode().unwrap();
+        let block_headers = block_headers_raw.decode()?;
         info!("  got {} headers  ( {} )", block_headers.len(), hdr_metrics);
+        if block_headers.is_empty() { return Err(Error::NoBlocks); }
 
         let mut start = 0;
         let mut end = block_headers.len() - 1;
@@ -203,38 +206,37 @@ fn download_epoch(storage: &Storage, net: &mut OpenPeer,
         } else if first_block.get_blockdate() == expected_slotid.next() {
             first_block.get_previous_header()
         } else {
-            panic!("not matching. gap")
+            return Err(Error::HeadersGap(expected_slotid, first_block.get_blockdate()));
         };
 
         let metrics = net.read_start();
         let blocks_raw = GetBlock::from(&download_start_hash, &latest_block.compute_hash())
                                 .execute(&mut net.0)
-                                .expect("to get one block at least");
+                                .map_err(Error::CommandFailed)?;
         let blocks_metrics = net.read_elapsed(&metrics);
         info!("  got {} blocks  ( {} )", blocks_raw.len(), blocks_metrics);
 
-        let first_block = blocks_raw[0].decode().unwrap();
-        let first_block_hdr = first_block.get_header();
+        let first_block_hdr = match blocks_raw.first() {
+            None => return Err(Error::NoBlocks),
+            Some(block_raw) => block_raw.decode()?.get_header(),
+        };
         debug!("first block {} {} prev {}", first_block_hdr.compute_hash(), first_block_hdr.get_blockdate(), first_block_hdr.get_previous_header());
 
         for block_raw in blocks_raw.iter() {
-            let block = block_raw.decode().unwrap();
+            let block = validator.validate_block(block_raw)?;
             let hdr = block.get_header();
             let date = hdr.get_blockdate();
             let blockhash = hdr.compute_hash();
             let block_previous_header = hdr.get_previous_header();
 
             if date.get_epochid() != epoch_id {
-                panic!("trying to append a block of different epoch id {}", date.get_epochid())
-            }
-
-            if previous_headerhash != block_previous_header {
-                panic!("previous header doesn't match: hash {} date {} got {} expected {}",
-                       blockhash, date, block_previous_header, previous_headerhash)
+                return Err(Error::UnexpectedEpoch(epoch_id, date));
             }
 
+            // the validator checked the date follows the previous block's:
+            // the slots in between had no block
             if &date != &expected_slotid {
-                println!("  WARNING: not contiguous. addr {} found, expected {} {}", date, expected_slotid, block_previous_header);
+                debug!("  empty slots: {} found, expected {} {}", date, expected_slotid, block_previous_header);
             }
 
             match date {
@@ -259,11 +261,11 @@ fn download_epoch(storage: &Storage, net: &mut OpenPeer,
                 // write packfile
                 let (packhash, index) = writer.finalize();
                 let (_, tmpfile) = storage::pack::create_index(storage, &index);
-                tmpfile.render_permanent(&storage.config.get_index_filepath(&packhash)).unwrap();
-                let epoch_time_elapsed = epoch_time_start.elapsed().unwrap();
+                tmpfile.render_permanent(&storage.config.get_index_filepath(&packhash))?;
+                let epoch_time_elapsed = epoch_time_start.elapsed().unwrap_or(Duration::from_secs(0));
                 info!("=> pack {} written for epoch {} in {}", hex::encode(&packhash[..]), epoch_id, duration_print(epoch_time_elapsed));
                 storage::tag::write(storage, &storage::tag::get_epoch_tag(epoch_id), &packhash[..]);
-                return (previous_headerhash, b, packhash)
+                return Ok((previous_headerhash, b, packhash))
             },
         }
     }
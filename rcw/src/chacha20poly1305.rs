@@ -12,18 +12,34 @@ use poly1305::Poly1305;
 use mac::Mac;
 use cryptoutil::{write_u64_le};
 use util::fixed_time_eq;
+/// ChaCha20-Poly1305 AEAD.
+///
+/// With a 8 bytes nonce, the original construction (draft-agl-tls-chacha20poly1305)
+/// is used. With a 12 bytes nonce, the RFC 7539 one: the additional data
+/// and the cipher text are padded to 16 bytes and their lengths are
+/// authenticated at the end.
 #[derive(Clone, Copy)]
 pub struct ChaCha20Poly1305 {
     cipher  : ChaCha20,
     mac: Poly1305,
     finished: bool,
-    data_len: usize
+    data_len: usize,
+    rfc7539: bool,
+    aad_len: usize
+}
+
+fn pad16(mac: &mut Poly1305, len: usize) {
+    if len % 16 != 0 {
+        let zeros = [0u8; 16];
+        mac.input(&zeros[0..16 - len % 16]);
+    }
 }
 
 impl ChaCha20Poly1305 {
   pub fn new(key: &[u8], nonce: &[u8], aad: &[u8]) -> ChaCha20Poly1305 {
       assert!(key.len() == 16 || key.len() == 32);
       assert!(nonce.len() == 8 || nonce.len() == 12);
+      let rfc7539 = nonce.len() == 12;
 
       let mut cipher = ChaCha20::new(key, nonce);
       let mut mac_key = [0u8; 64];
@@ -32,16 +48,34 @@ impl ChaCha20Poly1305 {
 
       let mut mac = Poly1305::new(&mac_key[..32]);
       mac.input(aad);
-      let mut aad_len = [0u8; 8];
-      let aad_len_uint: u64 = aad.len() as u64;
-      write_u64_le(&mut aad_len, aad_len_uint);
-      mac.input(&aad_len);
+      if rfc7539 {
+          pad16(&mut mac, aad.len());
+      } else {
+          let mut aad_len = [0u8; 8];
+          let aad_len_uint: u64 = aad.len() as u64;
+          write_u64_le(&mut aad_len, aad_len_uint);
+          mac.input(&aad_len);
+      }
       ChaCha20Poly1305 {
         cipher: cipher,
         mac: mac,
         finished: false,
-        data_len: 0
+        data_len: 0,
+        rfc7539: rfc7539,
+        aad_len: aad.len()
+      }
+  }
+
+  // authenticate the lengths, once the cipher text is authenticated
+  fn finish_mac(&mut self) {
+      let mut len_buf = [0u8; 8];
+      if self.rfc7539 {
+          pad16(&mut self.mac, self.data_len);
+          write_u64_le(&mut len_buf, self.aad_len as u64);
+          self.mac.input(&len_buf);
       }
+      write_u64_le(&mut len_buf, self.data_len as u64);
+      self.mac.input(&len_buf);
   }
 }
 
@@ -53,9 +87,7 @@ impl AeadEncryptor for ChaCha20Poly1305 {
         self.data_len += input.len();
         self.mac.input(output);
         self.finished = true;
-        let mut data_len_buf = [0u8; 8];
-        write_u64_le(&mut data_len_buf, self.data_len as u64);
-        self.mac.input(&data_len_buf);
+        self.finish_mac();
         self.mac.raw_result(out_tag);
     }
 }
@@ -70,10 +102,7 @@ impl AeadDecryptor for ChaCha20Poly1305 {
         self.mac.input(input);
 
         self.data_len += input.len();
-        let mut data_len_buf = [0u8; 8];
-
-        write_u64_le(&mut data_len_buf, self.data_len as u64);
-        self.mac.input(&data_len_buf);
+        self.finish_mac();
 
         let mut calc_tag =  [0u8; 16];
         self.mac.raw_result(&mut calc_tag);
@@ -129,6 +158,37 @@ mod test {
       assert!(result);
     }
   }
+  // RFC 7539, section 2.8.2
+  #[test]
+  fn test_chacha20_256_poly1305_rfc7539() {
+    let key : Vec<u8> = (0x80..0xa0).collect();
+    let nonce = [0x07, 0x00, 0x00, 0x00, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47];
+    let aad = [0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7];
+    let plain_text = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+    let cipher_text = [
+      0xd3, 0x1a, 0x8d, 0x34, 0x64, 0x8e, 0x60, 0xdb, 0x7b, 0x86, 0xaf, 0xbc, 0x53, 0xef, 0x7e, 0xc2,
+      0xa4, 0xad, 0xed, 0x51, 0x29, 0x6e, 0x08, 0xfe, 0xa9, 0xe2, 0xb5, 0xa7, 0x36, 0xee, 0x62, 0xd6,
+      0x3d, 0xbe, 0xa4, 0x5e, 0x8c, 0xa9, 0x67, 0x12, 0x82, 0xfa, 0xfb, 0x69, 0xda, 0x92, 0x72, 0x8b,
+      0x1a, 0x71, 0xde, 0x0a, 0x9e, 0x06, 0x0b, 0x29, 0x05, 0xd6, 0xa5, 0xb6, 0x7e, 0xcd, 0x3b, 0x36,
+      0x92, 0xdd, 0xbd, 0x7f, 0x2d, 0x77, 0x8b, 0x8c, 0x98, 0x03, 0xae, 0xe3, 0x28, 0x09, 0x1b, 0x58,
+      0xfa, 0xb3, 0x24, 0xe4, 0xfa, 0xd6, 0x75, 0x94, 0x55, 0x85, 0x80, 0x8b, 0x48, 0x31, 0xd7, 0xbc,
+      0x3f, 0xf4, 0xde, 0xf0, 0x8e, 0x4b, 0x7a, 0x9d, 0xe5, 0x76, 0xd2, 0x65, 0x86, 0xce, 0xc6, 0x4b,
+      0x61, 0x16];
+    let tag = [0x1a, 0xe1, 0x0b, 0x59, 0x4f, 0x09, 0xe2, 0x6a, 0x7e, 0x90, 0x2e, 0xcb, 0xd0, 0x60, 0x06, 0x91];
+
+    let mut c = ChaCha20Poly1305::new(&key, &nonce, &aad);
+    let mut output: Vec<u8> = repeat(0).take(plain_text.len()).collect();
+    let mut out_tag = [0u8; 16];
+    c.encrypt(&plain_text[..], &mut output[..], &mut out_tag);
+    assert_eq!(&output[..], &cipher_text[..]);
+    assert_eq!(out_tag, tag);
+
+    let mut c = ChaCha20Poly1305::new(&key, &nonce, &aad);
+    let mut output: Vec<u8> = repeat(0).take(plain_text.len()).collect();
+    assert!(c.decrypt(&cipher_text[..], &mut output[..], &tag));
+    assert_eq!(&output[..], &plain_text[..]);
+  }
+
   fn get_test_vectors()-> Vec<TestVector>{
     vec!(
       TestVector {
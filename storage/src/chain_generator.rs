@@ -0,0 +1,259 @@
+//! deterministic synthetic chains, to test the storage without a network
+//!
+//! The generated chain starts from a `genesis_prev` hash and is made of
+//! full epochs: an epoch boundary block followed by main blocks, some
+//! slots being left empty. The main blocks carry transactions spending
+//! the outputs of the previous ones.
+//!
+//! The same parameters always generate the same chain.
+//!
+
+use rand::{Rng, SeedableRng, ChaChaRng};
+
+use wallet_crypto::{hdwallet, tx};
+use wallet_crypto::address::{ExtendedAddr, AddrType, SpendingData, Attributes, StakeholderId};
+use wallet_crypto::coin::{Coin};
+use wallet_crypto::config::{Config, ProtocolMagic};
+use blockchain::{BlockHeader, HeaderHash, RawBlock, SlotId, EpochId};
+use blockchain::builder::{BlockBuilder, GenesisBlockBuilder};
+use blockchain::normal::{TxPayload};
+
+use super::{Storage, PackHash, pack, tag, epoch, header_to_blockhash};
+
+#[derive(Debug, Clone)]
+pub struct ChainParameters {
+    pub protocol_magic: ProtocolMagic,
+    pub genesis_prev: HeaderHash,
+    /// number of slots in an epoch
+    pub epoch_slots: u32,
+    /// probability for a slot to be empty, in percent
+    pub empty_slots: u32,
+    /// maximum number of transactions in a block
+    pub max_txs: u32,
+    pub seed: u32,
+}
+impl Default for ChainParameters {
+    fn default() -> Self {
+        ChainParameters {
+            protocol_magic: ProtocolMagic::new(0),
+            genesis_prev: HeaderHash::new(b"genesis"),
+            epoch_slots: 20,
+            empty_slots: 30,
+            max_txs: 3,
+            seed: 0,
+        }
+    }
+}
+
+/// generator of the blocks of a synthetic chain
+///
+/// All the blocks are issued by the same slot leader, which also owns all
+/// the funds: initially one output of the pseudo transaction identified by
+/// the hash of its address (as the genesis UTxOs).
+pub struct ChainGenerator {
+    params: ChainParameters,
+    rng: ChaChaRng,
+    leader: hdwallet::XPrv,
+    address: ExtendedAddr,
+    utxos: Vec<(tx::TxIn, Coin)>,
+    tip: Option<BlockHeader>,
+}
+impl ChainGenerator {
+    pub fn new(params: ChainParameters) -> Self {
+        let mut rng = ChaChaRng::from_seed(&[params.seed]);
+        let mut seed = [0u8; hdwallet::SEED_SIZE];
+        rng.fill_bytes(&mut seed);
+        let leader = hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes(seed));
+        let pk = leader.public();
+        let address = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk), Attributes::new_bootstrap_era(None));
+        let initial = (tx::TxIn::new(tx::TxId::new(&address.to_bytes()), 0), Coin::new(1_000_000_000).unwrap());
+        ChainGenerator {
+            params: params,
+            rng: rng,
+            leader: leader,
+            address: address,
+            utxos: vec![initial],
+            tip: None,
+        }
+    }
+
+    /// the address owning all the funds of the chain
+    pub fn address(&self) -> &ExtendedAddr { &self.address }
+
+    /// the header of the last generated block
+    pub fn tip(&self) -> Option<&BlockHeader> { self.tip.as_ref() }
+
+    /// the next epoch to be generated
+    pub fn next_epoch(&self) -> EpochId {
+        match self.tip {
+            None => 0,
+            Some(ref hdr) => hdr.get_blockdate().get_epochid() + 1,
+        }
+    }
+
+    /// generate the blocks of the next epoch
+    pub fn generate_epoch(&mut self) -> Vec<RawBlock> {
+        let pm = self.params.protocol_magic;
+        let epoch = self.next_epoch();
+        let leaders = vec![StakeholderId::new(&self.leader.public()); self.params.epoch_slots as usize];
+        let ebb = match self.tip {
+            None => GenesisBlockBuilder::first(pm, &self.params.genesis_prev, leaders),
+            Some(ref hdr) => GenesisBlockBuilder::new(pm, hdr, leaders),
+        }.make_raw();
+        self.tip = Some(ebb.decode().unwrap().get_header());
+
+        let mut blocks = vec![ebb];
+        for slotid in 0..self.params.epoch_slots {
+            if self.rng.gen_range(0, 100) < self.params.empty_slots { continue; }
+
+            let txs = self.generate_txs();
+            let slot = SlotId { epoch: epoch, slotid: slotid };
+            let raw = BlockBuilder::new(pm, self.tip.as_ref().unwrap(), slot, self.leader.clone(), txs).make_raw();
+            self.tip = Some(raw.decode().unwrap().get_header());
+            blocks.push(raw);
+        }
+        blocks
+    }
+
+    /// transactions splitting randomly chosen outputs in two
+    fn generate_txs(&mut self) -> TxPayload {
+        let nb = self.rng.gen_range(0, self.params.max_txs + 1);
+        let cfg = Config::new(self.params.protocol_magic);
+        let mut txauxs = Vec::new();
+        for _ in 0..nb {
+            let idx = self.rng.gen_range(0, self.utxos.len());
+            let (txin, value) = self.utxos.swap_remove(idx);
+            let value = u64::from(value);
+            let first = value / 2;
+
+            let mut tx = tx::Tx::new();
+            tx.add_input(txin);
+            tx.add_output(tx::TxOut::new(self.address.clone(), Coin::new(first).unwrap()));
+            tx.add_output(tx::TxOut::new(self.address.clone(), Coin::new(value - first).unwrap()));
+            let witness = tx::TxInWitness::new(&cfg, &self.leader, &tx);
+            let id = tx.id();
+            self.utxos.push((tx::TxIn::new(id.clone(), 0), Coin::new(first).unwrap()));
+            self.utxos.push((tx::TxIn::new(id, 1), Coin::new(value - first).unwrap()));
+            txauxs.push(tx::TxAux::new(tx, vec![witness]));
+        }
+        TxPayload::new(txauxs.into_iter().collect())
+    }
+}
+
+/// write the blocks of an epoch into a pack of the storage, tag it as the
+/// epoch's pack and create the epoch's refpack.
+pub fn write_epoch(storage: &mut Storage, epochid: EpochId, blocks: &[RawBlock]) -> PackHash {
+    let mut writer = pack::PackWriter::init(&storage.config);
+    for raw in blocks {
+        let hash = raw.decode().unwrap().get_header().compute_hash();
+        writer.append(&header_to_blockhash(&hash), raw.as_ref());
+    }
+    let (packhash, index) = writer.finalize();
+    let (lookup, tmpfile) = pack::create_index(storage, &index);
+    tmpfile.render_permanent(&storage.config.get_index_filepath(&packhash)).unwrap();
+    storage.lookups.insert(packhash, lookup);
+
+    tag::write(storage, &tag::get_epoch_tag(epochid), &packhash[..]);
+    epoch::epoch_create(&storage.config, &packhash, epochid);
+    packhash
+}
+
+/// generate `epochs` epochs of a chain and write them into the storage,
+/// setting the `HEAD` tag to the last block.
+pub fn generate(storage: &mut Storage, params: ChainParameters, epochs: u32) -> ChainGenerator {
+    let mut generator = ChainGenerator::new(params);
+    for _ in 0..epochs {
+        let epochid = generator.next_epoch();
+        let blocks = generator.generate_epoch();
+        write_epoch(storage, epochid, &blocks);
+    }
+    if let Some(hdr) = generator.tip() {
+        tag::write_hash(storage, &tag::HEAD, &hdr.compute_hash());
+    }
+    generator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use blockchain;
+    use blockchain::validation::{Validator, Parameters};
+    use config::{StorageConfig};
+    use refpack;
+
+    fn storage(name: &str) -> Storage {
+        let mut path = env::temp_dir();
+        path.push(format!("storage-test-{}-{}", name, ::rand::random::<u64>()));
+        Storage::init(&StorageConfig::new(&path)).unwrap()
+    }
+
+    #[test]
+    fn deterministic() {
+        let mut g1 = ChainGenerator::new(ChainParameters::default());
+        let mut g2 = ChainGenerator::new(ChainParameters::default());
+        let b1 : Vec<Vec<u8>> = g1.generate_epoch().into_iter().map(|b| b.0).collect();
+        let b2 : Vec<Vec<u8>> = g2.generate_epoch().into_iter().map(|b| b.0).collect();
+        assert_eq!(b1, b2);
+    }
+
+    #[test]
+    fn valid_chain() {
+        let params = ChainParameters::default();
+        let mut validator = Validator::new(
+            Parameters { epoch_slots: params.epoch_slots, .. Parameters::new(params.protocol_magic) },
+            params.genesis_prev.clone());
+        let mut generator = ChainGenerator::new(params);
+        let mut nb_txs = 0;
+        for _ in 0..3 {
+            for raw in generator.generate_epoch() {
+                if let blockchain::Block::MainBlock(blk) = validator.validate_block(&raw).unwrap() {
+                    nb_txs += blk.body.tx.iter().count();
+                }
+            }
+        }
+        assert!(nb_txs > 0);
+    }
+
+    #[test]
+    fn epoch_packs() {
+        let mut storage = storage("epoch-packs");
+        let params = ChainParameters::default();
+        let generator = generate(&mut storage, params.clone(), 3);
+
+        ::integrity_check(&storage, params.genesis_prev.clone(), 3);
+        assert_eq!(tag::read_hash(&storage, &tag::HEAD), generator.tip().map(|hdr| hdr.compute_hash()));
+
+        for epochid in 0..3 {
+            let name = tag::get_epoch_tag(epochid);
+            ::refpack_epoch_pack(&storage, &name).unwrap();
+            let rp = refpack::read_refpack(&storage.config, &name).unwrap();
+            let (packhash, epoch_rp) = epoch::epoch_read(&storage.config, epochid).unwrap();
+            assert_eq!(&packhash[..], &tag::read(&storage, &name).unwrap()[..]);
+
+            // both refpacks list the blocks at the position of their slot
+            let mut reader = pack::PackReader::init(&storage.config, &packhash);
+            while let Some(raw) = reader.get_next() {
+                let hdr = raw.decode().unwrap().get_header();
+                assert!(refpack_has_block(&rp, &hdr.get_blockdate()));
+                assert!(refpack_has_block(&epoch_rp, &hdr.get_blockdate()));
+            }
+            assert_eq!(rp.iter().filter(|bh| *bh != &[0u8; ::HASH_SIZE]).count(),
+                       epoch_rp.iter().filter(|bh| *bh != &[0u8; ::HASH_SIZE]).count());
+
+            let leaders = epoch::epoch_read_slot_leaders(&storage.config, epochid).unwrap().unwrap();
+            assert_eq!(leaders.slot_leaders.len(), params.epoch_slots as usize);
+        }
+
+        fs::remove_dir_all(storage.config.get_path()).unwrap();
+    }
+
+    /// does the given block date exist in the refpack of its epoch
+    fn refpack_has_block(rp: &refpack::RefPack, date: &blockchain::BlockDate) -> bool {
+        let idx = match date {
+            &blockchain::BlockDate::Genesis(_) => 0,
+            &blockchain::BlockDate::Normal(ref slot) => slot.slotid as usize + 1,
+        };
+        rp.iter().nth(idx).map(|bh| bh != &[0u8; ::HASH_SIZE]).unwrap_or(false)
+    }
+}
@@ -2,6 +2,7 @@ use std::fs;
 use std::io;
 use std::io::{Read};
 use wallet_crypto::util::{hex};
+use wallet_crypto::address::{StakeholderId};
 
 use blockchain;
 
@@ -81,3 +82,27 @@ pub fn epoch_read(config: &StorageConfig, epochid: blockchain::EpochId) -> io::R
         }
     }
 }
+
+/// read the slot leaders scheduled for the given epoch, from the epoch
+/// boundary block the epoch pack starts with.
+///
+/// returns `None` if the pack does not start with an epoch boundary block
+pub fn epoch_read_slot_leaders(config: &StorageConfig, epochid: blockchain::EpochId) -> io::Result<Option<blockchain::genesis::Body>> {
+    let ph = epoch_read_pack(config, epochid)?;
+    let mut reader = PackReader::init(config, &ph);
+    let rblk = match reader.get_next() {
+        None => return Ok(None),
+        Some(rblk) => rblk,
+    };
+    match rblk.decode() {
+        Ok(blockchain::Block::GenesisBlock(blk)) => Ok(Some(blk.body)),
+        Ok(_) => Ok(None),
+        Err((_, err)) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid block in the pack of epoch {}: {:?}", epochid, err))),
+    }
+}
+
+/// the stakeholder expected to issue the block of the given slot
+pub fn epoch_get_slot_leader(config: &StorageConfig, slot: &blockchain::SlotId) -> io::Result<Option<StakeholderId>> {
+    let leaders = epoch_read_slot_leaders(config, slot.epoch)?;
+    Ok(leaders.and_then(|body| body.get_slot_leader(slot.slotid).cloned()))
+}
@@ -5,6 +5,11 @@ extern crate wallet_crypto;
 extern crate blockchain;
 extern crate rand;
 extern crate flate2;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+#[cfg(test)]
+extern crate serde_json;
 
 pub mod block;
 pub mod types;
@@ -14,6 +19,11 @@ pub mod tag;
 pub mod epoch;
 pub mod refpack;
 pub mod tmpfile;
+pub mod metrics;
+pub mod wallet_state;
+pub mod wallet_history;
+#[cfg(any(test, feature = "with-chain-generator"))]
+pub mod chain_generator;
 mod compression;
 mod bitmap;
 mod bloom;
@@ -320,7 +330,7 @@ pub fn refpack_epoch_pack<S: AsRef<str>>(storage: &Storage, tag: &S) -> Result<(
                             current_slotid += 1;
                         }
                         rp.push_back(hash.clone().into_bytes());
-                        current_state = Some((current_epoch, current_slotid, hash));
+                        current_state = Some((current_epoch, current_slotid + 1, hash));
                     },
                 }
             },
@@ -385,7 +395,7 @@ fn epoch_integrity_check(storage: &Storage, epochid: u32, last_known_hash: Heade
                         while current_slotid < slotid.slotid {
                             current_slotid += 1;
                         }
-                        current_state = Some((current_epoch, current_slotid, hash));
+                        current_state = Some((current_epoch, current_slotid + 1, hash));
                     },
                 }
             },
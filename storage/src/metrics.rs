@@ -0,0 +1,134 @@
+//! chain quality metrics of the stored epochs
+//!
+//! Everything is computed from what is already on disk: the epoch's
+//! refpack for the filled and empty slots, the epoch's pack for the
+//! content of the blocks.
+//!
+
+use std::collections::BTreeMap;
+use std::ops::{Range};
+
+use wallet_crypto::address::{StakeholderId};
+use blockchain::{self, EpochId, ChainDifficulty};
+
+use super::{Result, HASH_SIZE};
+use config::{StorageConfig};
+use pack::{PackReader};
+use epoch;
+
+/// metrics of one epoch, the epoch boundary block aside
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpochMetrics {
+    pub epoch: EpochId,
+    /// number of slots in the epoch
+    pub slots: u32,
+    /// number of slots with a main block
+    pub blocks: u32,
+    pub empty_slots: u32,
+    /// ratio of the slots with a main block
+    pub density: f64,
+    /// chain difficulty at the epoch boundary block
+    pub start_difficulty: ChainDifficulty,
+    /// chain difficulty at the last block of the epoch
+    pub end_difficulty: ChainDifficulty,
+    /// number of main blocks issued by each slot leader
+    pub blocks_by_leader: BTreeMap<StakeholderId, u32>,
+    /// size of the main blocks, in bytes
+    pub total_size: u64,
+    pub average_block_size: f64,
+    pub transactions: u64,
+    pub average_transactions: f64,
+}
+
+/// compute the metrics of the given stored epoch, made of `epoch_slots` slots.
+///
+/// The epoch may not be complete (e.g. the current epoch): the slots after
+/// its last block are then counted as empty.
+pub fn epoch_metrics(config: &StorageConfig, epochid: EpochId, epoch_slots: u32) -> Result<EpochMetrics> {
+    let (packhash, refpack) = epoch::epoch_read(config, epochid)?;
+
+    // the first reference is the epoch boundary block
+    let blocks = refpack.iter().skip(1).filter(|bh| *bh != &[0u8; HASH_SIZE]).count() as u32;
+
+    let mut start_difficulty = ChainDifficulty::from(0);
+    let mut end_difficulty = start_difficulty;
+    let mut blocks_by_leader = BTreeMap::new();
+    let mut total_size = 0;
+    let mut transactions = 0;
+
+    let mut reader = PackReader::init(config, &packhash);
+    while let Some(raw) = reader.get_next() {
+        match raw.decode()? {
+            blockchain::Block::GenesisBlock(blk) => {
+                start_difficulty = blk.header.consensus.chain_difficulty;
+                end_difficulty = start_difficulty;
+            },
+            blockchain::Block::MainBlock(blk) => {
+                let leader = StakeholderId::new(&blk.header.consensus.leader_key);
+                *blocks_by_leader.entry(leader).or_insert(0) += 1;
+                total_size += raw.as_ref().len() as u64;
+                transactions += blk.body.tx.iter().count() as u64;
+                end_difficulty = blk.header.consensus.chain_difficulty;
+            },
+        }
+    }
+
+    Ok(EpochMetrics {
+        epoch: epochid,
+        slots: epoch_slots,
+        blocks: blocks,
+        empty_slots: epoch_slots.saturating_sub(blocks),
+        density: ratio(blocks as u64, epoch_slots),
+        start_difficulty: start_difficulty,
+        end_difficulty: end_difficulty,
+        blocks_by_leader: blocks_by_leader,
+        total_size: total_size,
+        average_block_size: ratio(total_size, blocks),
+        transactions: transactions,
+        average_transactions: ratio(transactions, blocks),
+    })
+}
+
+/// compute the metrics of each of the given stored epochs
+pub fn chain_metrics(config: &StorageConfig, epochs: Range<EpochId>, epoch_slots: u32) -> Result<Vec<EpochMetrics>> {
+    epochs.map(|epochid| epoch_metrics(config, epochid, epoch_slots)).collect()
+}
+
+fn ratio(n: u64, d: u32) -> f64 {
+    if d == 0 { 0.0 } else { n as f64 / d as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use config::{StorageConfig};
+    use chain_generator::{self, ChainParameters};
+    use Storage;
+
+    #[test]
+    fn generated_chain_metrics() {
+        let mut path = env::temp_dir();
+        path.push(format!("storage-test-metrics-{}", ::rand::random::<u64>()));
+        let mut storage = Storage::init(&StorageConfig::new(&path)).unwrap();
+        let params = ChainParameters::default();
+        chain_generator::generate(&mut storage, params.clone(), 2);
+
+        let metrics = chain_metrics(&storage.config, 0..2, params.epoch_slots).unwrap();
+        assert_eq!(metrics.len(), 2);
+        for m in metrics.iter() {
+            assert!(m.blocks > 0 && m.blocks <= params.epoch_slots);
+            assert_eq!(m.blocks + m.empty_slots, params.epoch_slots);
+            assert_eq!(m.blocks_by_leader.values().sum::<u32>(), m.blocks);
+            assert_eq!(u64::from(m.end_difficulty) - u64::from(m.start_difficulty), m.blocks as u64);
+            assert!(m.average_block_size > 0.0);
+        }
+        assert_eq!(metrics[0].end_difficulty, metrics[1].start_difficulty);
+
+        let json = ::serde_json::to_value(&metrics[0]).unwrap();
+        assert_eq!(json["blocks"], metrics[0].blocks);
+        assert_eq!(json["blocks_by_leader"].as_object().unwrap().len(), 1);
+
+        fs::remove_dir_all(storage.config.get_path()).unwrap();
+    }
+}
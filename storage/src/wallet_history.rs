@@ -0,0 +1,153 @@
+//! wallet transaction history
+//!
+//! The transactions sent by the wallet are recorded as pending, then
+//! reconciled against the blocks as they are processed by the wallet
+//! state (see `wallet_state::WalletState`): the transactions found in a
+//! block (sent by the wallet or not) are recorded with their block and
+//! depth, the pending transactions not found after `DEFAULT_PENDING_TTL`
+//! blocks are expired.
+//!
+
+use std::fmt;
+
+use wallet_crypto::address::{ExtendedAddr};
+use wallet_crypto::coin::{Coin};
+use wallet_crypto::tx::{TxId};
+use blockchain::{BlockDate, HeaderHash, ChainDifficulty};
+
+/// number of blocks after which a pending transaction not found in a
+/// block is expired
+pub const DEFAULT_PENDING_TTL : u64 = 2160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// the transaction spends outputs of the wallet
+    Outgoing,
+    /// the transaction only sends funds to the wallet
+    Incoming,
+}
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Direction::Outgoing => write!(f, "outgoing"),
+            &Direction::Incoming => write!(f, "incoming"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    /// sent, not found in a block yet. The chain difficulty of the last
+    /// processed block when the transaction was sent, or of the first
+    /// block processed after it if none was processed yet.
+    Pending(Option<ChainDifficulty>),
+    /// in the given block, `depth` blocks deep
+    InBlock { date: BlockDate, block: HeaderHash, difficulty: ChainDifficulty, depth: u64 },
+    /// not found in a block in time
+    Expired,
+}
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Status::Pending(_) => write!(f, "pending"),
+            &Status::InBlock { ref date, depth, .. } => write!(f, "in block {} (depth {})", date, depth),
+            &Status::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub txid: TxId,
+    pub direction: Direction,
+    /// the value sent to others (outgoing) or received (incoming)
+    pub amount: Coin,
+    /// the fee of the transaction, known if all its inputs belong to the wallet
+    pub fee: Option<Coin>,
+    /// the addresses of the wallet spent or credited by the transaction
+    pub addresses: Vec<ExtendedAddr>,
+    pub status: Status,
+}
+
+/// the transactions of the wallet, in the order they were recorded
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct History {
+    entries: Vec<Entry>,
+}
+impl History {
+    pub fn new() -> Self { History::default() }
+
+    pub fn entries(&self) -> &[Entry] { &self.entries }
+
+    pub fn get(&self, txid: &TxId) -> Option<&Entry> {
+        self.entries.iter().find(|entry| &entry.txid == txid)
+    }
+
+    /// record a transaction sent by the wallet, unless already recorded
+    pub fn record_pending(&mut self, entry: Entry) {
+        if self.get(&entry.txid).is_none() {
+            self.entries.push(entry);
+        }
+    }
+
+    /// record a transaction found in a block. The status of an already
+    /// recorded transaction (e.g. pending) is updated.
+    pub fn record_in_block(&mut self, entry: Entry) {
+        if let Some(e) = self.entries.iter_mut().find(|e| e.txid == entry.txid) {
+            e.status = entry.status;
+            return;
+        }
+        self.entries.push(entry);
+    }
+
+    /// update the depth of the transactions in a block and expire the
+    /// pending ones, given the chain difficulty of the last processed block.
+    ///
+    /// The transactions sent before any block was processed start their
+    /// time to live at the given tip.
+    ///
+    pub fn update_tip(&mut self, tip: ChainDifficulty, pending_ttl: u64) {
+        let tip = u64::from(tip);
+        for entry in self.entries.iter_mut() {
+            let expired = match entry.status {
+                Status::InBlock { difficulty, ref mut depth, .. } => {
+                    *depth = tip.saturating_sub(u64::from(difficulty));
+                    false
+                },
+                Status::Pending(Some(since)) => tip.saturating_sub(u64::from(since)) > pending_ttl,
+                Status::Pending(ref mut since @ None) => {
+                    *since = Some(ChainDifficulty::from(tip));
+                    false
+                },
+                Status::Expired => false,
+            };
+            if expired { entry.status = Status::Expired; }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(since: Option<ChainDifficulty>) -> Entry {
+        Entry { txid: TxId::new(&[]), direction: Direction::Outgoing, amount: Coin::zero(), fee: None, addresses: vec![], status: Status::Pending(since) }
+    }
+
+    #[test]
+    fn expire_pending() {
+        let mut history = History::new();
+        history.record_pending(pending(None));
+        let txid = history.entries()[0].txid.clone();
+
+        // sent before any processed block: the time to live starts at the
+        // next processed block, however high it is
+        let tip = 1_000_000;
+        history.update_tip(ChainDifficulty::from(tip), 10);
+        assert_eq!(history.get(&txid).unwrap().status, Status::Pending(Some(ChainDifficulty::from(tip))));
+        history.update_tip(ChainDifficulty::from(tip + 10), 10);
+        assert_eq!(history.get(&txid).unwrap().status, Status::Pending(Some(ChainDifficulty::from(tip))));
+        history.update_tip(ChainDifficulty::from(tip + 11), 10);
+        assert_eq!(history.get(&txid).unwrap().status, Status::Expired);
+    }
+}
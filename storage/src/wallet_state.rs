@@ -0,0 +1,519 @@
+//! wallet state: the unspent outputs of a wallet, from the stored blocks
+//!
+//! The blocks are processed forward, the outputs sent to the wallet's
+//! addresses (recognized by an `AddrLookup`) are added to the wallet's
+//! UTxOs and the wallet's UTxOs spent by the transactions are removed.
+//! The state remembers the last processed block so it can be updated
+//! incrementally as new epochs are stored.
+//!
+//! The transactions of the wallet are recorded in its `History`.
+//!
+//! The state is saved as a `Checkpoint` (with the history alongside) and
+//! restored from it, so the blocks are only processed once.
+//!
+
+use std::collections::BTreeMap;
+use std::{fmt, result};
+
+use wallet_crypto::{coin, tx, wallet, bip44, hdpayload};
+use wallet_crypto::address::{ExtendedAddr};
+use wallet_crypto::bip44::{Addressing, AddrType};
+use wallet_crypto::coin::{Coin};
+use wallet_crypto::config::{Config};
+use wallet_crypto::tx::{TxIn, TxOut};
+use blockchain::{self, BlockDate, HeaderHash, ChainDifficulty};
+
+use super::{Storage};
+use pack::{PackReader};
+use tag;
+use wallet_history::{self, History, Entry, Direction, Status};
+
+#[derive(Debug)]
+pub enum Error {
+    StorageError(super::Error),
+    WalletError(wallet::Error),
+    CoinError(coin::Error),
+    /// the block (second) does not follow the last processed block (first)
+    UnexpectedBlock(HeaderHash, HeaderHash),
+}
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Self { Error::StorageError(e) }
+}
+impl From<wallet::Error> for Error {
+    fn from(e: wallet::Error) -> Self { Error::WalletError(e) }
+}
+impl From<bip44::Error> for Error {
+    fn from(e: bip44::Error) -> Self { Error::WalletError(wallet::Error::from(e)) }
+}
+impl From<coin::Error> for Error {
+    fn from(e: coin::Error) -> Self { Error::CoinError(e) }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::StorageError(ref err) => write!(f, "Storage error: {:?}", err),
+            &Error::WalletError(ref err) => write!(f, "Wallet error: {}", err),
+            &Error::CoinError(ref err) => write!(f, "Coin error: {}", err),
+            &Error::UnexpectedBlock(ref expected, ref got) => write!(f, "block {} does not follow the last processed block {}", got, expected),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// recognize the addresses of a wallet
+pub trait AddrLookup {
+    /// how the wallet retrieves the key of one of its addresses
+    type Addressing: Clone;
+
+    /// the addressing of the given address if it belongs to the wallet
+    fn lookup(&mut self, addr: &ExtendedAddr) -> Result<Option<Self::Addressing>>;
+}
+
+/// lookup of the addresses of BIP44 accounts
+///
+/// the addresses are generated ahead of the last used address of each
+/// chain (external and internal) of each account, up to the gap limit.
+///
+pub struct Bip44Lookup {
+    config: Config,
+    gap_limit: u32,
+    /// the accounts and the next index to generate on their external
+    /// and internal chains
+    accounts: Vec<(wallet::Account, [u32; 2])>,
+    addresses: BTreeMap<ExtendedAddr, Addressing>,
+}
+impl Bip44Lookup {
+    pub fn new(config: Config, accounts: Vec<wallet::Account>, gap_limit: u32) -> Result<Self> {
+        let mut lookup = Bip44Lookup {
+            config: config,
+            gap_limit: gap_limit,
+            accounts: accounts.into_iter().map(|account| (account, [0, 0])).collect(),
+            addresses: BTreeMap::new(),
+        };
+        for account in 0..lookup.accounts.len() {
+            for addr_type in [AddrType::External, AddrType::Internal].iter() {
+                lookup.generate_up_to(account, *addr_type, gap_limit)?;
+            }
+        }
+        Ok(lookup)
+    }
+
+    /// generate the addresses of the given chain of the given account,
+    /// up to (excluded) the given index
+    fn generate_up_to(&mut self, account: usize, addr_type: AddrType, end: u32) -> Result<()> {
+        let chain = match addr_type { AddrType::External => 0, AddrType::Internal => 1 };
+        let (ref account, ref mut next) = self.accounts[account];
+        let end = ::std::cmp::min(end, 0x80000000);
+        if next[chain] >= end { return Ok(()); }
+
+        let start = account.account.change(addr_type)?.index(next[chain])?;
+        let chunk = start.next_chunks((end - next[chain]) as usize)?;
+        let indices = chunk.iter().map(|addressing| addressing.index).collect();
+        let addresses = account.gen_network_addresses(&self.config, addr_type, indices)?;
+        self.addresses.extend(addresses.into_iter().zip(chunk.into_iter()));
+        next[chain] = end;
+        Ok(())
+    }
+}
+impl AddrLookup for Bip44Lookup {
+    type Addressing = Addressing;
+
+    fn lookup(&mut self, addr: &ExtendedAddr) -> Result<Option<Addressing>> {
+        let addressing = match self.addresses.get(addr) {
+            None => return Ok(None),
+            Some(addressing) => *addressing,
+        };
+        if let Some(account) = self.accounts.iter().position(|&(ref account, _)| account.account == addressing.account) {
+            let end = addressing.index.saturating_add(1).saturating_add(self.gap_limit);
+            self.generate_up_to(account, addressing.address_type(), end)?;
+        }
+        Ok(Some(addressing))
+    }
+}
+
+/// lookup of the addresses of a random index wallet (the derivation path
+/// is stored, encrypted, in the address)
+pub struct RandomIndexLookup(wallet::RandomIndexWallet);
+impl RandomIndexLookup {
+    pub fn new(wallet: wallet::RandomIndexWallet) -> Self { RandomIndexLookup(wallet) }
+
+    pub fn wallet(&self) -> &wallet::RandomIndexWallet { &self.0 }
+}
+impl AddrLookup for RandomIndexLookup {
+    type Addressing = hdpayload::Path;
+
+    fn lookup(&mut self, addr: &ExtendedAddr) -> Result<Option<hdpayload::Path>> {
+        Ok(self.0.recognize(addr))
+    }
+}
+
+/// an unspent output of the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utxo<A> {
+    pub ptr: TxIn,
+    pub value: TxOut,
+    pub addressing: A,
+}
+
+/// the saved state of a wallet, to restore it with `WalletState::restore`:
+/// the lookup is rebuilt from the wallet's keys and the history is saved
+/// on its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<A> {
+    pub utxos: Vec<Utxo<A>>,
+    pub last_block: Option<(BlockDate, HeaderHash)>,
+    pub difficulty: Option<ChainDifficulty>,
+}
+
+pub struct WalletState<L: AddrLookup> {
+    lookup: L,
+    utxos: BTreeMap<TxIn, Utxo<L::Addressing>>,
+    last_block: Option<(BlockDate, HeaderHash)>,
+    /// chain difficulty of the last processed block
+    difficulty: Option<ChainDifficulty>,
+    history: History,
+}
+impl<L: AddrLookup> WalletState<L> {
+    /// the state of a wallet before the first block
+    pub fn new(lookup: L) -> Self {
+        Self::with_history(lookup, History::new())
+    }
+
+    /// the state of a wallet before the first block, with the previously
+    /// recorded history of the wallet (e.g. its pending transactions)
+    pub fn with_history(lookup: L, history: History) -> Self {
+        WalletState { lookup: lookup, utxos: BTreeMap::new(), last_block: None, difficulty: None, history: history }
+    }
+
+    /// the state of a wallet saved by `checkpoint`, with the wallet's
+    /// history.
+    ///
+    /// The addresses of the UTxOs and of the history are looked up again
+    /// so the lookup recognizes the addresses following them.
+    ///
+    pub fn restore(mut lookup: L, checkpoint: Checkpoint<L::Addressing>, history: History) -> Result<Self> {
+        for entry in history.entries() {
+            for addr in entry.addresses.iter() {
+                lookup.lookup(addr)?;
+            }
+        }
+        for utxo in checkpoint.utxos.iter() {
+            lookup.lookup(&utxo.value.address)?;
+        }
+        Ok(WalletState {
+            lookup: lookup,
+            utxos: checkpoint.utxos.into_iter().map(|utxo| (utxo.ptr.clone(), utxo)).collect(),
+            last_block: checkpoint.last_block,
+            difficulty: checkpoint.difficulty,
+            history: history,
+        })
+    }
+
+    /// the state to save, restored with `restore` (the history is saved
+    /// on its own)
+    pub fn checkpoint(&self) -> Checkpoint<L::Addressing> {
+        Checkpoint {
+            utxos: self.utxos.values().cloned().collect(),
+            last_block: self.last_block.clone(),
+            difficulty: self.difficulty,
+        }
+    }
+
+    pub fn lookup(&self) -> &L { &self.lookup }
+
+    pub fn utxos(&self) -> &BTreeMap<TxIn, Utxo<L::Addressing>> { &self.utxos }
+
+    /// the date and hash of the last processed block
+    pub fn last_block(&self) -> Option<&(BlockDate, HeaderHash)> { self.last_block.as_ref() }
+
+    pub fn history(&self) -> &History { &self.history }
+
+    /// record a transaction sent by the wallet as pending, until it is
+    /// found in a block
+    pub fn record_sent(&mut self, tx: &tx::Tx) -> Result<()> {
+        if let Some(entry) = self.history_entry(tx, Status::Pending(self.difficulty))? {
+            self.history.record_pending(entry);
+        }
+        Ok(())
+    }
+
+    /// the total value of the wallet's unspent outputs
+    pub fn balance(&self) -> Result<Coin> {
+        let mut total = Coin::zero();
+        for utxo in self.utxos.values() {
+            total = (total + utxo.value.value)?;
+        }
+        Ok(total)
+    }
+
+    /// process the transactions of the given block, following the last
+    /// processed block
+    pub fn forward(&mut self, blk: &blockchain::Block) -> Result<()> {
+        let hdr = blk.get_header();
+        if let Some((_, ref last)) = self.last_block {
+            if &hdr.get_previous_header() != last {
+                return Err(Error::UnexpectedBlock(last.clone(), hdr.compute_hash()));
+            }
+        }
+
+        if let &blockchain::Block::MainBlock(ref mblk) = blk {
+            for txaux in mblk.body.tx.iter() {
+                let status = Status::InBlock { date: hdr.get_blockdate(), block: hdr.compute_hash(), difficulty: hdr.get_chain_difficulty(), depth: 0 };
+                if let Some(entry) = self.history_entry(&txaux.tx, status)? {
+                    self.history.record_in_block(entry);
+                }
+                let id = txaux.tx.id();
+                for txin in txaux.tx.inputs.iter() {
+                    self.utxos.remove(txin);
+                }
+                for (index, txout) in txaux.tx.outputs.iter().enumerate() {
+                    if let Some(addressing) = self.lookup.lookup(&txout.address)? {
+                        let ptr = TxIn::new(id.clone(), index as u32);
+                        self.utxos.insert(ptr.clone(), Utxo { ptr: ptr, value: txout.clone(), addressing: addressing });
+                    }
+                }
+            }
+        }
+
+        self.last_block = Some((hdr.get_blockdate(), hdr.compute_hash()));
+        self.difficulty = Some(hdr.get_chain_difficulty());
+        self.history.update_tip(hdr.get_chain_difficulty(), wallet_history::DEFAULT_PENDING_TTL);
+        Ok(())
+    }
+
+    /// the history entry of the transaction, if it spends or credits the
+    /// wallet (before the wallet's UTxOs are updated with it)
+    fn history_entry(&mut self, tx: &tx::Tx, status: Status) -> Result<Option<Entry>> {
+        let mut addresses = vec![];
+        let mut spent = Coin::zero();
+        let mut spent_inputs = 0;
+        for txin in tx.inputs.iter() {
+            if let Some(utxo) = self.utxos.get(txin) {
+                spent = (spent + utxo.value.value)?;
+                spent_inputs += 1;
+                addresses.push(utxo.value.address.clone());
+            }
+        }
+        let mut received = Coin::zero();
+        let mut sent = Coin::zero();
+        for txout in tx.outputs.iter() {
+            if self.lookup.lookup(&txout.address)?.is_some() {
+                received = (received + txout.value)?;
+                addresses.push(txout.address.clone());
+            } else {
+                sent = (sent + txout.value)?;
+            }
+        }
+        if addresses.is_empty() { return Ok(None); }
+        addresses.sort();
+        addresses.dedup();
+
+        let (direction, amount, fee) = if spent_inputs == 0 {
+            (Direction::Incoming, received, None)
+        } else if spent_inputs == tx.inputs.len() {
+            (Direction::Outgoing, sent, spent - (sent + received)?)
+        } else {
+            (Direction::Outgoing, sent, None)
+        };
+        Ok(Some(Entry { txid: tx.id(), direction: direction, amount: amount, fee: fee, addresses: addresses, status: status }))
+    }
+
+    /// process the blocks of the stored epochs, following the last
+    /// processed block.
+    ///
+    /// Only the packed epochs are read: the blocks of the current epoch
+    /// are processed once the epoch is packed.
+    ///
+    /// returns the number of processed blocks
+    pub fn forward_storage(&mut self, storage: &Storage) -> Result<usize> {
+        let mut epoch_id = self.last_block.as_ref().map(|&(ref date, _)| date.get_epochid()).unwrap_or(0);
+        let mut processed = 0;
+        while let Some(h) = tag::read_hash(storage, &tag::get_epoch_tag(epoch_id)) {
+            debug!("wallet state: looking in epoch {}", epoch_id);
+            let mut reader = PackReader::init(&storage.config, &h.into_bytes());
+            while let Some(raw) = reader.get_next() {
+                let blk = raw.decode().map_err(super::Error::from)?;
+                let date = blk.get_header().get_blockdate();
+                if self.last_block.as_ref().map(|&(ref last, _)| &date <= last).unwrap_or(false) {
+                    continue;
+                }
+                self.forward(&blk)?;
+                processed += 1;
+            }
+            epoch_id += 1;
+        }
+        Ok(processed)
+    }
+}
+impl WalletState<Bip44Lookup> {
+    /// the unspent outputs of the wallet, ready for `Wallet::new_transaction`
+    pub fn inputs(&self) -> tx::Inputs {
+        self.utxos.values().map(|utxo| tx::Input::new(utxo.ptr.clone(), utxo.value.clone(), utxo.addressing)).collect()
+    }
+}
+impl WalletState<RandomIndexLookup> {
+    /// the unspent outputs of the wallet, ready for
+    /// `RandomIndexWallet::new_transaction` (which does not use the inputs'
+    /// BIP44 addressing)
+    pub fn inputs(&self) -> tx::Inputs {
+        let addressing = Addressing::new(0, AddrType::External).unwrap();
+        self.utxos.values().map(|utxo| tx::Input::new(utxo.ptr.clone(), utxo.value.clone(), addressing)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use wallet_crypto::{hdwallet};
+    use config::{StorageConfig};
+    use chain_generator::{self, ChainParameters, ChainGenerator};
+    use blockchain::ledger::{Ledger};
+
+    struct SingleAddress(ExtendedAddr);
+    impl AddrLookup for SingleAddress {
+        type Addressing = ();
+        fn lookup(&mut self, addr: &ExtendedAddr) -> Result<Option<()>> {
+            Ok(if addr == &self.0 { Some(()) } else { None })
+        }
+    }
+
+    #[test]
+    fn bip44_lookup_gap() {
+        let wallet = wallet::Wallet::new_from_seed(&hdwallet::Seed::from_bytes([3;32]));
+        let account = wallet.account(0).unwrap();
+        let mut lookup = Bip44Lookup::new(wallet.config.clone(), vec![account.clone()], 5).unwrap();
+
+        let far = account.gen_network_addresses(&wallet.config, AddrType::External, vec![7]).unwrap().remove(0);
+        let near = account.gen_network_addresses(&wallet.config, AddrType::External, vec![4]).unwrap().remove(0);
+        assert_eq!(lookup.lookup(&far).unwrap(), None);
+        assert_eq!(lookup.lookup(&near).unwrap().map(|a| a.index), Some(4));
+        assert_eq!(lookup.lookup(&far).unwrap().map(|a| a.index), Some(7));
+    }
+
+    #[test]
+    fn bip44_restore_lookup() {
+        let wallet = wallet::Wallet::new_from_seed(&hdwallet::Seed::from_bytes([3;32]));
+        let account = wallet.account(0).unwrap();
+        let lookup = Bip44Lookup::new(wallet.config.clone(), vec![account.clone()], 5).unwrap();
+
+        let near = account.gen_network_addresses(&wallet.config, AddrType::External, vec![4]).unwrap().remove(0);
+        let far = account.gen_network_addresses(&wallet.config, AddrType::External, vec![7]).unwrap().remove(0);
+        let ptr = TxIn::new(tx::TxId::new(&near.to_bytes()), 0);
+        let utxo = Utxo { ptr: ptr, value: TxOut::new(near, Coin::new(1).unwrap()), addressing: Addressing::new(0, AddrType::External).unwrap().incr(4).unwrap() };
+        let checkpoint = Checkpoint { utxos: vec![utxo], last_block: None, difficulty: None };
+
+        // the addresses following the restored UTxOs are recognized
+        let mut state = WalletState::restore(lookup, checkpoint.clone(), History::new()).unwrap();
+        assert_eq!(state.checkpoint(), checkpoint);
+        assert_eq!(state.balance().unwrap(), Coin::new(1).unwrap());
+        assert_eq!(state.lookup.lookup(&far).unwrap().map(|a| a.index), Some(7));
+    }
+
+    #[test]
+    fn track_generated_chain() {
+        let mut path = env::temp_dir();
+        path.push(format!("storage-test-wallet-state-{}", ::rand::random::<u64>()));
+        let mut storage = Storage::init(&StorageConfig::new(&path)).unwrap();
+        let params = ChainParameters::default();
+        let mut generator = chain_generator::generate(&mut storage, params.clone(), 1);
+        let address = generator.address().clone();
+
+        let mut state = WalletState::new(SingleAddress(address.clone()));
+        let first = state.forward_storage(&storage).unwrap();
+        assert!(first > 0);
+        assert_eq!(state.forward_storage(&storage).unwrap(), 0);
+
+        // incremental update with a new epoch, from the saved state
+        let json = ::serde_json::to_string(&state.checkpoint()).unwrap();
+        let checkpoint : Checkpoint<()> = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(checkpoint, state.checkpoint());
+        let mut state = WalletState::restore(SingleAddress(address.clone()), checkpoint, state.history().clone()).unwrap();
+        assert_eq!(state.forward_storage(&storage).unwrap(), 0);
+        next_epoch(&mut storage, &mut generator);
+        assert!(state.forward_storage(&storage).unwrap() > 0);
+
+        let mut fresh = WalletState::new(SingleAddress(address.clone()));
+        fresh.forward_storage(&storage).unwrap();
+        assert_eq!(fresh.utxos(), state.utxos());
+        assert_eq!(fresh.last_block(), state.last_block());
+
+        // all the funds belong to the generator's address, only the initial
+        // output is not in a block. The generated transactions pay no fee.
+        let initial = TxIn::new(tx::TxId::new(&address.to_bytes()), 0);
+        let mut utxos = BTreeMap::new();
+        utxos.insert(initial.clone(), TxOut::new(address, Coin::new(1_000_000_000).unwrap()));
+        let mut ledger = Ledger::new(Config::new(params.protocol_magic), tx::fee::LinearFee::new(tx::fee::Coeff::from_nanos(0), tx::fee::Coeff::from_nanos(0)), utxos);
+        let mut epoch_id = 0;
+        while let Some(h) = tag::read_hash(&storage, &tag::get_epoch_tag(epoch_id)) {
+            let mut reader = PackReader::init(&storage.config, &h.into_bytes());
+            while let Some(raw) = reader.get_next() {
+                ledger.apply_block(&raw.decode().unwrap()).unwrap();
+            }
+            epoch_id += 1;
+        }
+        let expected : Vec<&TxIn> = ledger.utxos().keys().filter(|txin| *txin != &initial).collect();
+        assert_eq!(state.utxos().keys().collect::<Vec<_>>(), expected);
+        assert!(state.balance().unwrap() > Coin::zero());
+
+        fs::remove_dir_all(storage.config.get_path()).unwrap();
+    }
+
+    #[test]
+    fn reconcile_sent_transactions() {
+        let mut generator = ChainGenerator::new(ChainParameters::default());
+        let blocks0 = generator.generate_epoch();
+        let blocks1 = generator.generate_epoch();
+
+        let mut state = WalletState::new(SingleAddress(generator.address().clone()));
+        for raw in blocks0.iter() {
+            state.forward(&raw.decode().unwrap()).unwrap();
+        }
+
+        // the first transaction of the next epoch, and a conflicting one
+        // which will never be in a block
+        let mut sent = None;
+        for raw in blocks1.iter() {
+            if let blockchain::Block::MainBlock(mblk) = raw.decode().unwrap() {
+                if let Some(txaux) = mblk.body.tx.iter().next() {
+                    sent = Some(txaux.tx.clone());
+                    break;
+                }
+            }
+        }
+        let sent = sent.unwrap();
+        let mut lost = sent.clone();
+        lost.add_output(TxOut::new(generator.address().clone(), Coin::new(1).unwrap()));
+        let sent_at = state.difficulty;
+        state.record_sent(&sent).unwrap();
+        state.record_sent(&lost).unwrap();
+        assert_eq!(state.history().get(&sent.id()).unwrap().status, Status::Pending(sent_at));
+        assert_eq!(state.history().get(&sent.id()).unwrap().direction, Direction::Outgoing);
+
+        for raw in blocks1.iter() {
+            state.forward(&raw.decode().unwrap()).unwrap();
+        }
+        let tip = state.difficulty.unwrap();
+        match state.history().get(&sent.id()).unwrap().status {
+            Status::InBlock { difficulty, depth, .. } => assert_eq!(depth, u64::from(tip) - u64::from(difficulty)),
+            ref status => panic!("unexpected status {}", status),
+        }
+        // the generated transactions pay no fee
+        assert_eq!(state.history().get(&sent.id()).unwrap().fee, Some(Coin::zero()));
+
+        let json = ::serde_json::to_string(state.history()).unwrap();
+        let mut history : History = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(&history, state.history());
+        assert_eq!(history.get(&lost.id()).unwrap().status, Status::Pending(sent_at));
+        history.update_tip(ChainDifficulty::from(u64::from(tip) + wallet_history::DEFAULT_PENDING_TTL), wallet_history::DEFAULT_PENDING_TTL);
+        assert_eq!(history.get(&lost.id()).unwrap().status, Status::Expired);
+        assert!(history.get(&sent.id()).is_some());
+    }
+
+    fn next_epoch(storage: &mut Storage, generator: &mut ChainGenerator) {
+        let epochid = generator.next_epoch();
+        let blocks = generator.generate_epoch();
+        chain_generator::write_epoch(storage, epochid, &blocks);
+    }
+}
@@ -7,6 +7,11 @@ use storage::types::{PackHash};
 use storage::{pack_blobs, block_location, block_read_location, pack, PackParameters};
 //use storage::tag::{HEAD};
 use blockchain;
+use blockchain::time;
+use blockchain::genesis_data;
+use std::fs;
+use std::time::{SystemTime};
+use serde_json;
 use config::{Config};
 use std::io::{Write, stdout};
 
@@ -181,6 +186,9 @@ impl HasCommand for Blockchain {
                 .arg(Arg::with_name("template")
                         .long("template").help("the template for the new blockchain").required(false)
                         .possible_values(&["mainnet", "testnet"]).default_value("mainnet"))
+                .arg(Arg::with_name("genesis")
+                        .long("genesis").help("the genesis data (JSON) of the network, to take its parameters (e.g. its fee policy) from").required(false)
+                        .takes_value(true).value_name("FILE"))
                 .arg(blockchain_name_arg(1))
             )
             .subcommand(SubCommand::with_name("get-block-header")
@@ -199,6 +207,7 @@ impl HasCommand for Blockchain {
             .subcommand(SubCommand::with_name("cat")
                 .about("show content of a block")
                 .arg(Arg::with_name("noparse").long("raw").help("cat the binary encoded block, no pretty print"))
+                .arg(Arg::with_name("json").long("json").conflicts_with("noparse").help("cat the block as JSON (hashes in hexadecimal, addresses in base58)"))
                 .arg(blockchain_name_arg(1))
                 .arg(Arg::with_name("blockid").help("hexadecimal encoded block id").index(2).required(true))
             )
@@ -270,6 +279,14 @@ impl HasCommand for Blockchain {
                         panic!("invalid template option")
                     }
                 };
+                let net_cfg = match opts.value_of("genesis") {
+                    None => net_cfg,
+                    Some(path) => {
+                        let json = fs::read(path).unwrap();
+                        let genesis = genesis_data::parse(&json, &net_cfg.genesis_prev).unwrap();
+                        net_cfg.with_genesis_data(&genesis)
+                    }
+                };
                 let config = resolv_network_by_name(&opts);
                 let storage_config = config.get_storage_config();
                 let _ = Storage::init(&storage_config).unwrap();
@@ -284,6 +301,7 @@ impl HasCommand for Blockchain {
                 let mut net = get_native_peer(config.network, &net_cfg);
                 let mbh = net.get_tip().unwrap();
                 println!("prv block header: {}", mbh.get_previous_header());
+                display_block_time(&net_cfg, &mbh.get_blockdate());
             },
             ("get-block", Some(opts)) => {
                 let config = resolv_network_by_name(&opts);
@@ -453,13 +471,20 @@ impl HasCommand for Blockchain {
                                 if opts.is_present("noparse") {
                                     stdout().write(rblk.as_ref()).unwrap();
                                     stdout().flush().unwrap();
+                                } else if opts.is_present("json") {
+                                    let blk = rblk.decode().unwrap();
+                                    println!("{}", serde_json::to_string_pretty(&blk).unwrap());
                                 } else {
                                     let blk = rblk.decode().unwrap();
                                     let hdr = blk.get_header();
                                     let hash = hdr.compute_hash();
                                     println!("blk location: {:?}", loc);
                                     println!("hash computed: {} expected: {}", hash, hh);
-                                    display_block(&blk)
+                                    display_block(&blk);
+                                    let netcfg_file = config.get_storage_config().get_config_file();
+                                    if let Some(net_cfg) = net::Config::from_file(&netcfg_file) {
+                                        display_block_time(&net_cfg, &hdr.get_blockdate());
+                                    }
                                 }
                             }
                         }
@@ -590,6 +615,22 @@ fn display_block(blk: &blockchain::Block) {
     println!("{}", blk.to_pretty());
 }
 
+/// display when the block of the given date was minted and how far
+/// behind the current time it is
+fn display_block_time(net_cfg: &net::Config, date: &blockchain::BlockDate) {
+    match net_cfg.time_parameters() {
+        None => println!("minted at: unknown (no system start in the network config)"),
+        Some(params) => {
+            let now = SystemTime::now();
+            println!("minted at: {}", time::format_utc(params.slot_start_time(date)));
+            let slots = params.slot_at(now)
+                .and_then(|slot| params.slots_between(date, &blockchain::BlockDate::Normal(slot)))
+                .unwrap_or(0);
+            println!("lag: {}s ({} slots)", params.lag(date, now).as_secs(), slots);
+        }
+    }
+}
+
 mod internal {
     use std::str::FromStr;
 
@@ -52,7 +52,8 @@ impl HasCommand for Generate {
             }
         };
 
-        let addresses = account.gen_addresses(addr_type, indices).unwrap();
+        let config = wallet.wallet().unwrap().config;
+        let addresses = account.gen_network_addresses(&config, addr_type, indices).unwrap();
         for addr in addresses {
             println!("{}", base58::encode(&addr.to_bytes()));
         };
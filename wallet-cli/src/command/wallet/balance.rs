@@ -0,0 +1,43 @@
+use wallet_crypto::{wallet};
+use wallet_crypto::util::base58;
+use command::{HasCommand};
+use clap::{ArgMatches, Arg, App};
+use storage::{self, wallet_state::{Bip44Lookup}};
+
+use super::config;
+
+pub struct Balance;
+
+impl HasCommand for Balance {
+    type Output = ();
+    type Config = ();
+
+    const COMMAND : &'static str = "balance";
+
+    fn clap_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+        app.about("retrieve the balance and the unspent outputs of the wallet's accounts in what have been synced from the network")
+            .arg(Arg::with_name("WALLET NAME").help("the name of the wallet").index(1).required(true))
+            .arg(Arg::with_name("utxos").long("utxos").help("display the unspent outputs"))
+    }
+    fn run(_: Self::Config, args: &ArgMatches) -> Self::Output {
+        let name = value_t!(args.value_of("WALLET NAME"), String).unwrap();
+
+        let wallet_cfg = config::Config::from_file(&name).unwrap();
+        let wallet = wallet_cfg.wallet().unwrap();
+        let known_accounts = config::Accounts::from_files(&name).unwrap();
+        let accounts = (0..known_accounts.iter().count() as u32).map(|index| wallet.account(index).unwrap()).collect();
+        let storage = storage::Storage::init(&wallet_cfg.blockchain_storage_config().unwrap()).unwrap();
+
+        let lookup = Bip44Lookup::new(wallet.config.clone(), accounts, wallet::DEFAULT_GAP_LIMIT).unwrap();
+        let mut state = config::state_from_file(&name, lookup).unwrap();
+        state.forward_storage(&storage).unwrap();
+        config::state_to_file(&name, &state).unwrap();
+
+        if args.is_present("utxos") {
+            for utxo in state.utxos().values() {
+                println!("{} {} (account {} {:?} {}): {}", utxo.ptr, base58::encode(&utxo.value.address.to_bytes()), utxo.addressing.account, utxo.addressing.address_type(), utxo.addressing.index, utxo.value.value);
+            }
+        }
+        println!("balance: {}", state.balance().unwrap());
+    }
+}
@@ -8,8 +8,8 @@
 
 use wallet_crypto::{
     self,
-    hdwallet::{XPrv},
-    tx::fee::{SelectionPolicy},
+    hdwallet::{XPrv, DerivationScheme},
+    tx::fee::{SelectionPolicy, LinearFee},
     wallet::{self, Wallet, Account},
     bip44
 };
@@ -17,6 +17,9 @@ use exe_common::config::{net};
 use std::{io, slice::{Iter}, result, path::{PathBuf, Path}, env::{VarError, self, home_dir}, fs};
 use std::{num::{ParseIntError}, collections::{BTreeMap}};
 use storage::tmpfile::{TmpFile};
+use storage::config::{StorageConfig};
+use storage::wallet_history::{History};
+use storage::wallet_state::{self, WalletState, Bip44Lookup};
 use serde_yaml;
 
 #[derive(Debug)]
@@ -29,7 +32,11 @@ pub enum Error {
     ParseIntError(ParseIntError),
     AccountIndexNotFound(bip44::Account),
     AccountAliasNotFound(String),
-    BlockchainConfigError(&'static str)
+    BlockchainConfigError(&'static str),
+    WalletStateError(wallet_state::Error)
+}
+impl From<wallet_state::Error> for Error {
+    fn from(e: wallet_state::Error) -> Error { Error::WalletStateError(e) }
 }
 impl From<VarError> for Error {
     fn from(e: VarError) -> Error { Error::VarError(e) }
@@ -53,6 +60,21 @@ impl From<serde_yaml::Error> for Error {
 pub type Result<T> = result::Result<T, Error>;
 
 static FILENAME : &'static str = "config.yml";
+static HISTORY_FILENAME : &'static str = "history.yml";
+static STATE_FILENAME : &'static str = "state.yml";
+
+/// the fee algorithm of a wallet
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum FeeAlgorithm {
+    /// the fee policy of the wallet's blockchain (see its configuration)
+    Network,
+    /// the given linear fee policy, e.g. for a network with a fee policy
+    /// not (yet) in its configuration
+    Linear(LinearFee),
+}
+impl Default for FeeAlgorithm {
+    fn default() -> Self { FeeAlgorithm::Network }
+}
 
 /// config of a given Wallet
 ///
@@ -66,6 +88,14 @@ pub struct Config {
     /// useful for spending, so far only
     pub selection_fee_policy: SelectionPolicy,
 
+    /// the derivation scheme of the wallet's keys
+    #[serde(default)]
+    pub derivation_scheme: DerivationScheme,
+
+    /// how the fee of the wallet's transactions is computed
+    #[serde(default)]
+    pub fee_algorithm: FeeAlgorithm,
+
     /// TODO, this needs to be encrypted in the very near future
     pub cached_root_key: XPrv,
 }
@@ -76,6 +106,8 @@ impl Config {
         Config {
             blockchain: blockchain.into(),
             selection_fee_policy: wallet.selection_policy,
+            derivation_scheme: wallet.derivation_scheme,
+            fee_algorithm: FeeAlgorithm::default(),
             cached_root_key: wallet.cached_root_key
         }
     }
@@ -92,11 +124,25 @@ impl Config {
         }
     }
 
+    /// retrieve the storage configuration of the blockchain associated to the wallet
+    pub fn blockchain_storage_config(&self) -> Result<StorageConfig> {
+        let path = ariadne_path()?.join("networks").join(&self.blockchain);
+        Ok(StorageConfig::new(&path))
+    }
+
     /// construct the wallet object from the wallet configuration
     pub fn wallet(&self) -> Result<Wallet> {
         let blockchain_config = self.blockchain_config()?;
-        let wallet_cfg = wallet_crypto::config::Config::new(blockchain_config.protocol_magic);
-        Ok(Wallet::new(self.cached_root_key.clone(), wallet_cfg, self.selection_fee_policy))
+        let wallet_cfg = wallet_crypto::config::Config::new_with_network_magic(
+            blockchain_config.protocol_magic,
+            blockchain_config.requires_network_magic
+        );
+        let fee_policy = match self.fee_algorithm {
+            FeeAlgorithm::Network => blockchain_config.tx_fee_policy,
+            FeeAlgorithm::Linear(fee_policy) => fee_policy,
+        };
+        Ok(Wallet::new(self.cached_root_key.clone(), wallet_cfg, self.selection_fee_policy, self.derivation_scheme).with_fee_policy(fee_policy)
+            .with_validation_parameters(blockchain_config.tx_validation_parameters()))
     }
 
     pub fn to_file<P: AsRef<Path>>(&self, name: &P) -> Result<()> {
@@ -195,6 +241,48 @@ impl Accounts {
     }
 }
 
+/// read the transaction history of the given wallet, empty if no
+/// transaction was recorded yet
+pub fn history_from_file<P: AsRef<Path>>(name: &P) -> Result<History> {
+    let path = ariadne_path()?.join("wallets").join(name).join(HISTORY_FILENAME);
+    if ! path.is_file() { return Ok(History::new()); }
+    let mut file = fs::File::open(path)?;
+    serde_yaml::from_reader(&mut file).map_err(Error::YamlError)
+}
+
+/// write the transaction history of the given wallet, alongside its
+/// configuration
+pub fn history_to_file<P: AsRef<Path>>(name: &P, history: &History) -> Result<()> {
+    let path = ariadne_path()?.join("wallets").join(name);
+    fs::DirBuilder::new().recursive(true).create(path.clone())?;
+    let mut tmpfile = TmpFile::create(path.clone())?;
+    serde_yaml::to_writer(&mut tmpfile, history)?;
+    tmpfile.render_permanent(&path.join(HISTORY_FILENAME))?;
+    Ok(())
+}
+
+/// the state of the given wallet as saved by `state_to_file`, or the state
+/// before the first block if it was never saved
+pub fn state_from_file<P: AsRef<Path>>(name: &P, lookup: Bip44Lookup) -> Result<WalletState<Bip44Lookup>> {
+    let history = history_from_file(name)?;
+    let path = ariadne_path()?.join("wallets").join(name).join(STATE_FILENAME);
+    if ! path.is_file() { return Ok(WalletState::with_history(lookup, history)); }
+    let mut file = fs::File::open(path)?;
+    let checkpoint = serde_yaml::from_reader(&mut file)?;
+    Ok(WalletState::restore(lookup, checkpoint, history)?)
+}
+
+/// write the state of the given wallet (and its history), alongside its
+/// configuration
+pub fn state_to_file<P: AsRef<Path>>(name: &P, state: &WalletState<Bip44Lookup>) -> Result<()> {
+    history_to_file(name, state.history())?;
+    let path = ariadne_path()?.join("wallets").join(name);
+    let mut tmpfile = TmpFile::create(path.clone())?;
+    serde_yaml::to_writer(&mut tmpfile, &state.checkpoint())?;
+    tmpfile.render_permanent(&path.join(STATE_FILENAME))?;
+    Ok(())
+}
+
 pub mod account {
     use wallet_crypto::{bip44, coin::Coin, wallet::{Account}, hdwallet::{XPub}};
 
@@ -253,3 +341,45 @@ pub fn ariadne_path() -> Result<PathBuf> {
         Err(err) => Err(Error::VarError(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockchain::{genesis_data, HeaderHash};
+    use wallet_crypto::hdwallet::{Seed};
+    use wallet_crypto::tx::fee::{Coeff};
+
+    // a network with its own fees and transaction size limit
+    const GENESIS_JSON : &str = r#"{
+  "avvmDistr": {},
+  "nonAvvmBalances": {},
+  "bootStakeholders": {},
+  "heavyDelegation": {},
+  "protocolConsts": { "k": 10, "protocolMagic": 42, "vssMinTTL": 2, "vssMaxTTL": 6 },
+  "blockVersionData": { "maxBlockSize": "2000000", "maxHeaderSize": "2000000", "maxTxSize": "8192", "slotDuration": "7000",
+                        "txFeePolicy": { "summand": "100000000000000", "multiplier": "20500000000" } },
+  "startTime": 1530000000
+}"#;
+
+    #[test]
+    fn network_parameters_from_genesis() {
+        let mut path = env::temp_dir();
+        path.push(format!("ariadne-test-config-{}", ::rand::random::<u64>()));
+        env::set_var(ARIADNE_PATH_ENV, &path);
+
+        let genesis = genesis_data::parse(GENESIS_JSON.as_bytes(), &HeaderHash::new(GENESIS_JSON.as_bytes())).unwrap();
+        let net_cfg = net::Config::testnet().with_genesis_data(&genesis);
+        let net_dir = path.join("networks").join("custom");
+        fs::DirBuilder::new().recursive(true).create(&net_dir).unwrap();
+        net_cfg.to_file(&net_dir.join("config.yml"));
+
+        let wallet = Wallet::new_from_seed(&Seed::from_bytes([0;32]));
+        let config = Config::from_wallet(wallet, "custom");
+        let wallet = config.wallet().unwrap();
+        assert_eq!(wallet.fee_policy, LinearFee::new(Coeff::new(100000, 0).unwrap(), Coeff::new(20, 500_000_000).unwrap()));
+        assert_eq!(wallet.validation_parameters.max_tx_size, 8192);
+        assert_eq!(wallet.config.protocol_magic, wallet_crypto::config::ProtocolMagic::new(42));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use wallet_crypto::{wallet, address::{ExtendedAddr}};
+use wallet_crypto::util::base58;
+use command::{HasCommand};
+use clap::{ArgMatches, Arg, App};
+use storage::{self, tag, pack};
+use blockchain::{Block};
+use std::collections::{BTreeSet};
+
+use super::config;
+
+pub struct Discover;
+
+impl HasCommand for Discover {
+    type Output = ();
+    type Config = ();
+
+    const COMMAND : &'static str = "discover";
+
+    fn clap_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+        app.about("discover the used accounts and addresses of the wallet in what have been synced from the network (see BIP44)")
+            .arg(Arg::with_name("WALLET NAME").help("the name of the wallet").index(1).required(true))
+            .arg(Arg::with_name("gap_limit")
+                .long("gap-limit")
+                .takes_value(true)
+                .value_name("GAP LIMIT")
+                .help("number of consecutive unused addresses after which the discovery stops")
+            )
+    }
+    fn run(_: Self::Config, args: &ArgMatches) -> Self::Output {
+        let name      = value_t!(args.value_of("WALLET NAME"), String).unwrap();
+        let gap_limit = if args.is_present("gap_limit") {
+            value_t!(args.value_of("gap_limit"), u32).unwrap_or_else(|e| e.exit())
+        } else {
+            wallet::DEFAULT_GAP_LIMIT
+        };
+
+        let wallet_cfg = config::Config::from_file(&name).unwrap();
+        let wallet = wallet_cfg.wallet().unwrap();
+        let storage = storage::Storage::init(&wallet_cfg.blockchain_storage_config().unwrap()).unwrap();
+
+        // all the addresses receiving funds in the packed epochs
+        let mut used_addresses = BTreeSet::new();
+        let mut epoch_id = 0;
+        while let Some(h) = tag::read_hash(&storage, &tag::get_epoch_tag(epoch_id)) {
+            info!("looking in epoch {}", epoch_id);
+            let mut reader = pack::PackReader::init(&storage.config, &h.into_bytes());
+            while let Some(blk_bytes) = reader.get_next() {
+                if let Block::MainBlock(mblk) = blk_bytes.decode().unwrap() {
+                    for txaux in mblk.body.tx.iter() {
+                        for txout in &txaux.tx.outputs {
+                            used_addresses.insert(txout.address.to_bytes());
+                        }
+                    }
+                }
+            }
+            epoch_id += 1;
+        }
+
+        let mut is_used = |addr: &ExtendedAddr| used_addresses.contains(&addr.to_bytes());
+        let accounts = wallet.discover_accounts(gap_limit, &mut is_used).unwrap();
+        for (account, addresses) in accounts {
+            println!("account {}", account.account);
+            for (addressing, addr) in addresses {
+                println!("  {:?} {}: {}", addressing.address_type(), addressing.index, base58::encode(&addr.to_bytes()));
+            }
+        }
+    }
+}
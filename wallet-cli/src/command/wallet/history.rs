@@ -0,0 +1,47 @@
+use wallet_crypto::{wallet};
+use wallet_crypto::util::base58;
+use command::{HasCommand};
+use clap::{ArgMatches, Arg, App};
+use storage::{self, wallet_state::{Bip44Lookup}};
+
+use super::config;
+
+pub struct History;
+
+impl HasCommand for History {
+    type Output = ();
+    type Config = ();
+
+    const COMMAND : &'static str = "history";
+
+    fn clap_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+        app.about("reconcile the wallet's transactions with what have been synced from the network and display the wallet's statement")
+            .arg(Arg::with_name("WALLET NAME").help("the name of the wallet").index(1).required(true))
+    }
+    fn run(_: Self::Config, args: &ArgMatches) -> Self::Output {
+        let name = value_t!(args.value_of("WALLET NAME"), String).unwrap();
+
+        let wallet_cfg = config::Config::from_file(&name).unwrap();
+        let wallet = wallet_cfg.wallet().unwrap();
+        let known_accounts = config::Accounts::from_files(&name).unwrap();
+        let accounts = (0..known_accounts.iter().count() as u32).map(|index| wallet.account(index).unwrap()).collect();
+        let storage = storage::Storage::init(&wallet_cfg.blockchain_storage_config().unwrap()).unwrap();
+
+        let lookup = Bip44Lookup::new(wallet.config.clone(), accounts, wallet::DEFAULT_GAP_LIMIT).unwrap();
+        let mut state = config::state_from_file(&name, lookup).unwrap();
+        state.forward_storage(&storage).unwrap();
+        config::state_to_file(&name, &state).unwrap();
+
+        for entry in state.history().entries() {
+            let fee = match entry.fee {
+                None => "unknown".to_owned(),
+                Some(fee) => format!("{}", fee),
+            };
+            println!("{} {} {} (fee: {}) {}", entry.txid, entry.direction, entry.amount, fee, entry.status);
+            for addr in entry.addresses.iter() {
+                println!("  {}", base58::encode(&addr.to_bytes()));
+            }
+        }
+        println!("balance: {}", state.balance().unwrap());
+    }
+}
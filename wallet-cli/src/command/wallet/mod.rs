@@ -6,6 +6,9 @@ mod definition;
 mod new;
 mod recover;
 mod address;
+mod discover;
+mod balance;
+mod history;
 mod find_address;
 mod util;
 
@@ -25,6 +28,9 @@ impl HasCommand for Wallet {
             .subcommand(new::CommandNewWallet::mk_command())
             .subcommand(recover::Recover::mk_command())
             .subcommand(address::Generate::mk_command())
+            .subcommand(discover::Discover::mk_command())
+            .subcommand(balance::Balance::mk_command())
+            .subcommand(history::History::mk_command())
             // TODO: move this command to the blockchain
             // .subcommand(FindAddress::mk_command())
     }
@@ -33,6 +39,9 @@ impl HasCommand for Wallet {
             (new::CommandNewWallet::COMMAND, Some(opts)) => new::CommandNewWallet::run((), opts),
             (recover::Recover::COMMAND, Some(opts)) => recover::Recover::run((), opts),
             (address::Generate::COMMAND, Some(opts)) => address::Generate::run((), opts),
+            (discover::Discover::COMMAND, Some(opts)) => discover::Discover::run((), opts),
+            (balance::Balance::COMMAND, Some(opts)) => balance::Balance::run((), opts),
+            (history::History::COMMAND, Some(opts)) => history::History::run((), opts),
             /*
             (FindAddress::COMMAND, Some(opts)) => {
                 FindAddress::run((), opts);
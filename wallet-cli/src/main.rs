@@ -6,6 +6,7 @@ extern crate serde_derive;
 extern crate log;
 extern crate env_logger;
 extern crate serde_yaml;
+extern crate serde_json;
 extern crate rcw;
 extern crate wallet_crypto;
 extern crate exe_common;
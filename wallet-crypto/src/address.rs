@@ -1,5 +1,4 @@
-use std::fmt;
-use std::collections::BTreeMap;
+use std::{fmt, result};
 use serde;
 
 use rcw::digest::Digest;
@@ -7,14 +6,16 @@ use rcw::blake2b::Blake2b;
 use rcw::sha3::Sha3;
 
 use redeem;
-use util::{base58};
+use util::{base58, hex};
 use cbor;
 use cbor::{ExtendedResult};
 use hdwallet::{XPub};
 use hdpayload::{HDAddressPayload};
+use attributes::{AttributesMap};
+use config::{Config, ProtocolMagic};
 
 /// Digest of the composition of `Blake2b_224 . Sha3_256`
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 pub struct DigestBlake2b224([u8;28]);
 impl DigestBlake2b224 {
     /// create digest from the given inputs by computing the SHA3_256 and
@@ -70,6 +71,55 @@ impl cbor::CborValue for DigestBlake2b224 {
         }).embed("while decoding DigestBlake2b224")
     }
 }
+impl serde::Serialize for DigestBlake2b224
+{
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(self.0.as_ref()))
+        } else {
+            serializer.serialize_bytes(self.0.as_ref())
+        }
+    }
+}
+struct DigestBlake2b224Visitor();
+impl<'de> serde::de::Visitor<'de> for DigestBlake2b224Visitor {
+    type Value = DigestBlake2b224;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Expecting a Blake2b_224 digest (`DigestBlake2b224`)")
+    }
+
+    fn visit_str<'a, E>(self, v: &'a str) -> result::Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        let bytes = hex::decode(v).map_err(|err| E::custom(format!("{}", err)))?;
+        self.visit_bytes(&bytes)
+    }
+
+    fn visit_bytes<'a, E>(self, v: &'a [u8]) -> result::Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        match DigestBlake2b224::from_slice(v) {
+            Some(digest) => Ok(digest),
+            None => Err(E::invalid_length(v.len(), &"28 bytes")),
+        }
+    }
+}
+impl<'de> serde::Deserialize<'de> for DigestBlake2b224
+{
+    fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(DigestBlake2b224Visitor())
+        } else {
+            deserializer.deserialize_bytes(DigestBlake2b224Visitor())
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 pub enum AddrType {
@@ -116,6 +166,9 @@ impl StakeholderId {
         let buf = cbor::encode_to_cbor(pubk).unwrap();
         StakeholderId(DigestBlake2b224::new(buf.as_ref()))
     }
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        DigestBlake2b224::from_slice(bytes).map(StakeholderId)
+    }
 }
 impl cbor::CborValue for StakeholderId {
     fn encode(&self) -> cbor::Value { cbor::CborValue::encode(&self.0) }
@@ -195,61 +248,88 @@ impl cbor::CborValue for StakeDistribution {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Attributes {
     pub derivation_path: Option<HDAddressPayload>,
-    pub stake_distribution: StakeDistribution
-    // attr_remains ? whatever...
+    pub stake_distribution: StakeDistribution,
+    /// the network the address belongs to, for the networks requiring it
+    /// (see `config::RequiresNetworkMagic`)
+    #[serde(default)]
+    pub protocol_magic: Option<ProtocolMagic>,
+    /// the attributes we do not know about, kept as they are
+    #[serde(default)]
+    pub remains: AttributesMap,
 }
 impl Attributes {
     pub fn new_bootstrap_era(hdap: Option<HDAddressPayload>) -> Self {
         Attributes {
             derivation_path: hdap,
-            stake_distribution: StakeDistribution::BootstrapEraDistr
+            stake_distribution: StakeDistribution::BootstrapEraDistr,
+            protocol_magic: None,
+            remains: AttributesMap::new(),
         }
     }
     pub fn new_single_key(pubk: &XPub, hdap: Option<HDAddressPayload>) -> Self {
         Attributes {
             derivation_path: hdap,
-            stake_distribution: StakeDistribution::new_single_key(pubk)
+            stake_distribution: StakeDistribution::new_single_key(pubk),
+            protocol_magic: None,
+            remains: AttributesMap::new(),
         }
     }
+
+    /// tag the address with the protocol magic of its network
+    pub fn with_protocol_magic(mut self, protocol_magic: Option<ProtocolMagic>) -> Self {
+        self.protocol_magic = protocol_magic;
+        self
+    }
 }
 const ATTRIBUTE_NAME_TAG_STAKE : u64 = 0;
 const ATTRIBUTE_NAME_TAG_DERIVATION : u64 = 1;
+const ATTRIBUTE_NAME_TAG_PROTOCOL_MAGIC : u64 = 2;
+
+// the protocol magic attribute is the cbor encoded protocol magic, wrapped in bytes
+fn encode_protocol_magic(pm: &ProtocolMagic) -> cbor::Bytes {
+    cbor::Bytes::new(cbor::encode_to_cbor(pm).unwrap())
+}
+fn decode_protocol_magic(bytes: cbor::Bytes) -> cbor::Result<ProtocolMagic> {
+    cbor::decode_from_cbor(bytes.as_ref()).embed("while decoding the serialised protocol magic")
+}
 
 impl cbor::CborValue for Attributes {
     fn encode(&self) -> cbor::Value {
-        let mut map = BTreeMap::new();
+        let mut map = self.remains.clone();
         match &self.stake_distribution {
             &StakeDistribution::BootstrapEraDistr => { /**/ },
             &StakeDistribution::SingleKeyDistr(_) => {
-                map.insert(
-                    cbor::ObjectKey::Integer(ATTRIBUTE_NAME_TAG_STAKE),
-                    cbor::CborValue::encode(&self.stake_distribution)
-                );
+                map.set(ATTRIBUTE_NAME_TAG_STAKE, &self.stake_distribution);
             }
         };
         match &self.derivation_path {
             &Some(ref dp) => {
-                map.insert(
-                    cbor::ObjectKey::Integer(ATTRIBUTE_NAME_TAG_DERIVATION),
-                    cbor::CborValue::encode(dp)
-                );
+                map.set(ATTRIBUTE_NAME_TAG_DERIVATION, dp);
             },
             &None => {
                 /* insert nothing */
             }
         }
-        cbor::Value::Object(map)
+        if let Some(ref pm) = self.protocol_magic {
+            map.set(ATTRIBUTE_NAME_TAG_PROTOCOL_MAGIC, &encode_protocol_magic(pm));
+        }
+        cbor::CborValue::encode(&map)
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
-        value.object().and_then(|object| {
-            let (object, stake_distribution) = cbor::object_decode_elem(object, cbor::ObjectKey::Integer(ATTRIBUTE_NAME_TAG_STAKE))
-                .or_else(|(val, _)| val.object().map(|obj| (obj, StakeDistribution::BootstrapEraDistr)))?;
-            let (object, derivation_path) = cbor::object_decode_elem(object, cbor::ObjectKey::Integer(ATTRIBUTE_NAME_TAG_DERIVATION))
-                .or_else(|(val, _)| val.object().map(|obj| (obj, None)))?;
-            if object.len() != 0 {
-                return cbor::Result::object(object, cbor::Error::UnparsedValues);
-            }
-            Ok(Attributes { derivation_path: derivation_path, stake_distribution: stake_distribution })
+        cbor::CborValue::decode(value).and_then(|mut map: AttributesMap| {
+            let stake_distribution = map.take(ATTRIBUTE_NAME_TAG_STAKE)?
+                .unwrap_or(StakeDistribution::BootstrapEraDistr);
+            let derivation_path = map.take(ATTRIBUTE_NAME_TAG_DERIVATION)?;
+            let protocol_magic = match map.take(ATTRIBUTE_NAME_TAG_PROTOCOL_MAGIC)? {
+                None => None,
+                Some(bytes) => Some(decode_protocol_magic(bytes)?),
+            };
+            Ok(Attributes {
+                derivation_path: derivation_path,
+                stake_distribution: stake_distribution,
+                protocol_magic: protocol_magic,
+                remains: map,
+            })
         }).embed("while decoding `Attributes`")
     }
 }
@@ -294,6 +374,20 @@ impl ExtendedAddr {
         }
     }
 
+    /// the redeem address of the given redeem public key (e.g. to redeem an
+    /// AVVM certificate)
+    pub fn new_redeem(pk: &redeem::PublicKey, protocol_magic: Option<ProtocolMagic>) -> Self {
+        let attrs = Attributes::new_bootstrap_era(None).with_protocol_magic(protocol_magic);
+        ExtendedAddr::new(AddrType::ATRedeem, SpendingData::RedeemASD(*pk), attrs)
+    }
+
+    /// check the address belongs to the network of the given configuration:
+    /// it carries the network's protocol magic if the network requires it,
+    /// and no protocol magic otherwise.
+    pub fn is_for_network(&self, config: &Config) -> bool {
+        self.attributes.protocol_magic == config.address_protocol_magic()
+    }
+
     /// encode an `ExtendedAddr` to cbor with the extra details and `crc32`
     ///
     /// ```
@@ -423,11 +517,41 @@ impl<'de> serde::Deserialize<'de> for ExtendedAddr
     }
 }
 
-pub type Script = [u8;32]; // TODO
+/// a versioned script, as found in the script addresses (the validator
+/// script) and the script witnesses (the validator and redeemer scripts).
+///
+/// The scripts are kept serialized, they are not evaluated.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Script {
+    pub version: u16,
+    pub script: Vec<u8>,
+}
+impl Script {
+    pub fn new(version: u16, script: Vec<u8>) -> Self {
+        Script { version: version, script: script }
+    }
+}
+impl cbor::CborValue for Script {
+    fn encode(&self) -> cbor::Value {
+        cbor::Value::Array(
+            vec![ cbor::CborValue::encode(&self.version)
+                , cbor::Value::Bytes(cbor::Bytes::from_slice(&self.script))
+                ]
+        )
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.array().and_then(|array| {
+            let (array, version) = cbor::array_decode_elem(array, 0).embed("version")?;
+            let (array, script) : (Vec<cbor::Value>, cbor::Bytes) = cbor::array_decode_elem(array, 0).embed("script")?;
+            if !array.is_empty() { return cbor::Result::array(array, cbor::Error::UnparsedValues); }
+            Ok(Script::new(version, script.to_vec()))
+        }).embed("while decoding `Script`")
+    }
+}
 
 const SPENDING_DATA_TAG_PUBKEY : u64 = 0;
-const SPENDING_DATA_TAG_SCRIPT : u64 = 1; // TODO
-const SPENDING_DATA_TAG_REDEEM : u64 = 2; // TODO
+const SPENDING_DATA_TAG_SCRIPT : u64 = 1;
+const SPENDING_DATA_TAG_REDEEM : u64 = 2;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum SpendingData {
@@ -444,7 +568,10 @@ impl cbor::CborValue for SpendingData {
                 v.push(cbor::CborValue::encode(&SPENDING_DATA_TAG_PUBKEY));
                 v.push(cbor::CborValue::encode(pk));
             },
-            &SpendingData::ScriptASD(_)      => unimplemented!(),
+            &SpendingData::ScriptASD(ref script) => {
+                v.push(cbor::CborValue::encode(&SPENDING_DATA_TAG_SCRIPT));
+                v.push(cbor::CborValue::encode(script));
+            },
             &SpendingData::RedeemASD(ref pk) => {
                 v.push(cbor::CborValue::encode(&SPENDING_DATA_TAG_REDEEM));
                 v.push(cbor::CborValue::encode(pk));
@@ -463,6 +590,13 @@ impl cbor::CborValue for SpendingData {
                     return cbor::Result::array(sum_type, cbor::Error::UnparsedValues);
                 }
                 Ok(SpendingData::PubKeyASD(pk))
+            } else if n == SPENDING_DATA_TAG_SCRIPT {
+                let (sum_type, script) = cbor::array_decode_elem(sum_type, 0)
+                    .embed("while decoding the validator script")?;
+                if sum_type.len() != 0 {
+                    return cbor::Result::array(sum_type, cbor::Error::UnparsedValues);
+                }
+                Ok(SpendingData::ScriptASD(script))
             } else if n == SPENDING_DATA_TAG_REDEEM {
                 let (sum_type, pk) = cbor::array_decode_elem(sum_type, 0)
                     .embed("while decoding the public key")?;
@@ -614,4 +748,19 @@ mod tests {
         assert_eq!(r.attributes.stake_distribution, StakeDistribution::BootstrapEraDistr);
         assert_eq!(bytes, r.to_bytes());
     }
+
+    #[test]
+    fn decode_address_unknown_attributes() {
+        let seed = hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let mut attrs = Attributes::new_bootstrap_era(Some(HDAddressPayload::from_vec(vec![1,2,3])));
+        attrs.remains.insert(42, cbor::Value::Bytes(cbor::Bytes::from_slice(&[0x1a, 0, 0, 0, 1])));
+        let ea = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(pk), attrs);
+
+        let bytes = ea.to_bytes();
+        let r = ExtendedAddr::from_bytes(&bytes).unwrap();
+        assert_eq!(r.attributes.remains.len(), 1);
+        assert_eq!(r, ea);
+        assert_eq!(r.to_bytes(), bytes);
+    }
 }
@@ -0,0 +1,133 @@
+//! Byron's attributes: maps of extensible optional values
+//!
+//! Headers, addresses and transactions carry attributes. The keys we do
+//! not know (yet) must be kept as they are, so re-encoding a decoded
+//! value (and hashing it) gives the same result as on the network.
+//!
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::{Iter};
+use std::{cmp, fmt, result};
+use serde;
+
+use cbor;
+use cbor::{ExtendedResult};
+use util::{hex};
+
+/// the raw attributes map, from the attribute's key to its value
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct AttributesMap(BTreeMap<cbor::ObjectKey, cbor::Value>);
+impl AttributesMap {
+    pub fn new() -> Self { AttributesMap(BTreeMap::new()) }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+    pub fn len(&self) -> usize { self.0.len() }
+    pub fn iter<'a>(&'a self) -> Iter<'a, cbor::ObjectKey, cbor::Value> { self.0.iter() }
+
+    pub fn get(&self, key: u64) -> Option<&cbor::Value> {
+        self.0.get(&cbor::ObjectKey::Integer(key))
+    }
+    pub fn insert(&mut self, key: u64, value: cbor::Value) -> Option<cbor::Value> {
+        self.0.insert(cbor::ObjectKey::Integer(key), value)
+    }
+    pub fn remove(&mut self, key: u64) -> Option<cbor::Value> {
+        self.0.remove(&cbor::ObjectKey::Integer(key))
+    }
+
+    /// remove the attribute of the given key and decode it
+    pub fn take<T: cbor::CborValue>(&mut self, key: u64) -> cbor::Result<Option<T>> {
+        match self.remove(key) {
+            None => Ok(None),
+            Some(value) => cbor::CborValue::decode(value).map(Some),
+        }
+    }
+
+    /// encode and set the attribute of the given key
+    pub fn set<T: cbor::CborValue>(&mut self, key: u64, value: &T) {
+        self.insert(key, cbor::CborValue::encode(value));
+    }
+
+    fn to_cbor(&self) -> Vec<u8> {
+        cbor::encode_to_cbor(self).expect("to cbor-encode attributes in a vector in memory")
+    }
+}
+// the values are only ordered through their encoding
+impl PartialOrd for AttributesMap {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for AttributesMap {
+    fn cmp(&self, other: &Self) -> cmp::Ordering { self.to_cbor().cmp(&other.to_cbor()) }
+}
+impl cbor::CborValue for AttributesMap {
+    fn encode(&self) -> cbor::Value {
+        cbor::Value::Object(self.0.clone())
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.object().map(AttributesMap).embed("while decoding `AttributesMap`")
+    }
+}
+impl serde::Serialize for AttributesMap
+{
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(&self.to_cbor()))
+        } else {
+            serializer.serialize_bytes(&self.to_cbor())
+        }
+    }
+}
+struct AttributesMapVisitor();
+impl<'de> serde::de::Visitor<'de> for AttributesMapVisitor {
+    type Value = AttributesMap;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Expecting CBOR encoded attributes (`AttributesMap`)")
+    }
+
+    fn visit_str<'a, E>(self, v: &'a str) -> result::Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        let bytes = hex::decode(v).map_err(|err| E::custom(format!("{}", err)))?;
+        self.visit_bytes(&bytes)
+    }
+
+    fn visit_bytes<'a, E>(self, v: &'a [u8]) -> result::Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        cbor::decode_from_cbor(v).map_err(|(_, err)| E::custom(format!("{:?}", err)))
+    }
+}
+impl<'de> serde::Deserialize<'de> for AttributesMap
+{
+    fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(AttributesMapVisitor())
+        } else {
+            deserializer.deserialize_bytes(AttributesMapVisitor())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_attributes_roundtrip() {
+        // {0: h'4101', 7: [1, "x"], 42: h''}
+        let bytes = hex::decode("a3004241010782016178182a40").unwrap();
+        let mut attrs : AttributesMap = cbor::decode_from_cbor(&bytes).unwrap();
+        assert_eq!(cbor::encode_to_cbor(&attrs).unwrap(), bytes);
+
+        let known : Option<cbor::Bytes> = attrs.take(0).unwrap();
+        assert!(known.is_some());
+        assert_eq!(attrs.len(), 2);
+        attrs.insert(0, cbor::CborValue::encode(&known.unwrap()));
+        assert_eq!(cbor::encode_to_cbor(&attrs).unwrap(), bytes);
+    }
+}
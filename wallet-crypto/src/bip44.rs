@@ -16,6 +16,7 @@
 /// ```
 
 use hdpayload::{Path};
+use hdwallet::{self, XPrv, XPub, DerivationScheme};
 use std::{fmt, result};
 use serde;
 
@@ -212,6 +213,20 @@ impl Addressing {
         Ok(Addressing{account: change.account, change: change.change, index: index})
     }
 
+    /// derive the private key of this addressing from the wallet's root
+    /// key, already derived for the BIP44 purpose and coin type.
+    pub fn derive_xprv(&self, root_key: &XPrv, scheme: DerivationScheme) -> XPrv {
+        root_key.derive(scheme, self.account.index())
+                .derive(scheme, self.change)
+                .derive(scheme, self.index)
+    }
+
+    /// derive the public key of this addressing from the public key of
+    /// its account.
+    pub fn derive_xpub(&self, account_key: &XPub, scheme: DerivationScheme) -> hdwallet::Result<XPub> {
+        account_key.derive(scheme, self.change)?.derive(scheme, self.index)
+    }
+
     /// return a path ready for derivation
     pub fn to_path(&self) -> Path {
         Path::new(vec![BIP44_PURPOSE, BIP44_COIN_TYPE, self.account.index(), self.change, self.index])
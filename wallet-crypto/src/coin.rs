@@ -68,6 +68,9 @@ impl fmt::Display for Coin {
         write!(f, "{}", self.0)
     }
 }
+impl From<Coin> for u64 {
+    fn from(c: Coin) -> u64 { c.0 }
+}
 impl cbor::CborValue for Coin {
     fn encode(&self) -> cbor::Value { cbor::Value::U64(self.0) }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
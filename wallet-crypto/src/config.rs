@@ -41,15 +41,44 @@ impl Default for ProtocolMagic {
     fn default() -> Self { ProtocolMagic::new(764824073) }
 }
 
+/// whether the addresses of a network carry its protocol magic or not
+///
+/// # Default
+///
+/// The default is to not carry it, as on the mainnet.
+///
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum RequiresNetworkMagic {
+    RequiresNoMagic,
+    RequiresMagic,
+}
+impl Default for RequiresNetworkMagic {
+    fn default() -> Self { RequiresNetworkMagic::RequiresNoMagic }
+}
+
 /// Configuration for the wallet-crypto
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Config {
-    pub protocol_magic: ProtocolMagic
+    pub protocol_magic: ProtocolMagic,
+    #[serde(default)]
+    pub requires_network_magic: RequiresNetworkMagic,
 }
 impl Config {
     pub fn new(protocol_magic: ProtocolMagic) -> Self {
+        Config::new_with_network_magic(protocol_magic, RequiresNetworkMagic::default())
+    }
+    pub fn new_with_network_magic(protocol_magic: ProtocolMagic, requires_network_magic: RequiresNetworkMagic) -> Self {
         Config {
-            protocol_magic: protocol_magic
+            protocol_magic: protocol_magic,
+            requires_network_magic: requires_network_magic,
+        }
+    }
+
+    /// the protocol magic the addresses of the network are tagged with, if any
+    pub fn address_protocol_magic(&self) -> Option<ProtocolMagic> {
+        match self.requires_network_magic {
+            RequiresNetworkMagic::RequiresNoMagic => None,
+            RequiresNetworkMagic::RequiresMagic => Some(self.protocol_magic),
         }
     }
 }
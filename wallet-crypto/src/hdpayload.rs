@@ -28,10 +28,17 @@ impl Path {
     }
     fn cbor(&self) -> Vec<u8> { cbor::encode_to_cbor(self).unwrap() }
 }
+// the path is encoded as an indefinite length array, as the lists of the
+// legacy (Haskell) implementation, so the addresses are the ones Daedalus
+// creates. Both lengths are decoded.
 impl cbor::CborValue for Path {
-    fn encode(&self) -> cbor::Value { cbor::Value::Array(self.0.iter().map(cbor::CborValue::encode).collect()) }
+    fn encode(&self) -> cbor::Value { cbor::Value::IArray(self.0.iter().map(cbor::CborValue::encode).collect()) }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
-        value.array().and_then(|vec| {
+        let elements : cbor::Result<Vec<cbor::Value>> = match value {
+            cbor::Value::IArray(list) => Ok(list.into_iter().collect()),
+            value => value.array(),
+        };
+        elements.and_then(|vec| {
             let mut v = vec![];
             for el in vec.iter() { v.push(cbor::CborValue::decode(el.clone())?); }
             Ok(Path::new(v))
@@ -156,7 +163,9 @@ mod tests {
     fn path_cbor_encoding() {
         let path = Path::new(vec![0,1,2]);
         let cbor = path.cbor();
-        assert_eq!(Ok(path), Path::from_cbor(cbor.as_ref()));
+        assert_eq!(cbor, vec![0x9f, 0, 1, 2, 0xff]);
+        assert_eq!(Ok(path.clone()), Path::from_cbor(cbor.as_ref()));
+        assert_eq!(Ok(path), Path::from_cbor(&[0x83, 0, 1, 2]));
     }
 
     #[test]
@@ -4,7 +4,7 @@ use self::rcw::digest::Digest;
 use self::rcw::sha2::Sha512;
 use self::rcw::hmac::Hmac;
 use self::rcw::mac::Mac;
-use self::rcw::curve25519::{GeP3, ge_scalarmult_base};
+use self::rcw::curve25519::{GeP3, ge_scalarmult_base, sc_muladd};
 use self::rcw::ed25519::signature_extended;
 use self::rcw::ed25519;
 use self::rcw::util::fixed_time_eq;
@@ -131,7 +131,16 @@ impl XPrv {
     /// ```
     ///
     pub fn generate_from_seed(seed: &Seed) -> Self {
-        let mut mac = Hmac::new(Sha512::new(), seed.as_ref());
+        Self::generate_from_daedalus_seed(seed.as_ref())
+    }
+
+    /// create a new `XPrv` the way the legacy (Daedalus) wallets do: the
+    /// given bytes are the HMAC key of the `Root Seed Chain` iterations.
+    ///
+    /// Daedalus uses the CBOR encoding of its 32 bytes seed.
+    ///
+    pub fn generate_from_daedalus_seed(seed: &[u8]) -> Self {
+        let mut mac = Hmac::new(Sha512::new(), seed);
 
         let mut iter = 1;
         let mut out = [0u8; XPRV_SIZE];
@@ -234,8 +243,8 @@ impl XPrv {
         xpub.verify(message, signature)
     }
 
-    pub fn derive(&self, index: DerivationIndex) -> Self {
-        derive_private(self, index, DerivationScheme::V2)
+    pub fn derive(&self, scheme: DerivationScheme, index: DerivationIndex) -> Self {
+        derive_private(self, index, scheme)
     }
 }
 impl PartialEq for XPrv {
@@ -363,8 +372,8 @@ impl XPub {
         ed25519::verify(message, &self.as_ref()[0..32], signature.as_ref())
     }
 
-    pub fn derive(&self, index: DerivationIndex) -> Result<Self> {
-        derive_public(self, index, DerivationScheme::V2)
+    pub fn derive(&self, scheme: DerivationScheme, index: DerivationIndex) -> Result<Self> {
+        derive_public(self, index, scheme)
     }
 }
 impl PartialEq for XPub {
@@ -602,6 +611,17 @@ fn le32(i: u32) -> [u8; 4] {
     [i as u8, (i >> 8) as u8, (i >> 16) as u8, (i >> 24) as u8]
 }
 
+fn be32(i: u32) -> [u8; 4] {
+    [(i >> 24) as u8, (i >> 16) as u8, (i >> 8) as u8, i as u8]
+}
+
+fn serialize_index(i: u32, scheme: DerivationScheme) -> [u8; 4] {
+    match scheme {
+        DerivationScheme::V1 => be32(i),
+        DerivationScheme::V2 => le32(i),
+    }
+}
+
 fn mk_xprv(out: &mut [u8; XPRV_SIZE], kl: &[u8], kr: &[u8], cc: &[u8]) {
     assert!(kl.len() == 32);
     assert!(kr.len() == 32);
@@ -620,7 +640,26 @@ fn mk_xpub(out: &mut [u8; XPUB_SIZE], pk: &[u8], cc: &[u8]) {
     out[32..64].clone_from_slice(cc);
 }
 
-fn add_256bits(x: &[u8], y: &[u8]) -> [u8; 32] {
+fn add_256bits(x: &[u8], y: &[u8], scheme: DerivationScheme) -> [u8; 32] {
+    match scheme {
+        DerivationScheme::V1 => add_256bits_v1(x, y),
+        DerivationScheme::V2 => add_256bits_v2(x, y),
+    }
+}
+
+// V1 does not propagate the carry between the bytes
+fn add_256bits_v1(x: &[u8], y: &[u8]) -> [u8; 32] {
+    assert!(x.len() == 32);
+    assert!(y.len() == 32);
+
+    let mut out = [0u8; 32];
+    for i in 0..32 {
+        out[i] = x[i].wrapping_add(y[i]);
+    }
+    out
+}
+
+fn add_256bits_v2(x: &[u8], y: &[u8]) -> [u8; 32] {
     assert!(x.len() == 32);
     assert!(y.len() == 32);
 
@@ -634,7 +673,35 @@ fn add_256bits(x: &[u8], y: &[u8]) -> [u8; 32] {
     out
 }
 
-fn add_28_mul8(x: &[u8], y: &[u8]) -> [u8; 32] {
+fn add_28_mul8(x: &[u8], y: &[u8], scheme: DerivationScheme) -> [u8; 32] {
+    match scheme {
+        DerivationScheme::V1 => add_28_mul8_v1(x, y),
+        DerivationScheme::V2 => add_28_mul8_v2(x, y),
+    }
+}
+
+// V1 multiplies the whole 32 bytes by 8, but the bits shifted out of each
+// byte are lost (as in the legacy implementation), and adds them modulo
+// the order of the curve's group
+fn add_28_mul8_v1(x: &[u8], y: &[u8]) -> [u8; 32] {
+    assert!(x.len() == 32);
+    assert!(y.len() == 32);
+
+    let mut y8 = [0u8; 32];
+    let mut acc = 0;
+    for i in 0..32 {
+        y8[i] = (y[i] << 3) + (acc & 0x8);
+        acc = y[i] >> 5;
+    }
+
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    let mut out = [0u8; 32];
+    sc_muladd(&mut out, &one, &y8, x);
+    out
+}
+
+fn add_28_mul8_v2(x: &[u8], y: &[u8]) -> [u8; 32] {
     assert!(x.len() == 32);
     assert!(y.len() == 32);
 
@@ -654,13 +721,20 @@ fn add_28_mul8(x: &[u8], y: &[u8]) -> [u8; 32] {
     out
 }
 
-#[derive(Clone, Copy)]
+/// the scheme of the Ed25519-BIP32 derivation.
+///
+/// `V1` is the legacy scheme of the first Daedalus (random index) wallets,
+/// `V2` the one of the BIP44 wallets.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum DerivationScheme {
     V1,
     V2,
 }
+impl Default for DerivationScheme {
+    fn default() -> Self { DerivationScheme::V2 }
+}
 
-fn derive_private(xprv: &XPrv, index: DerivationIndex, _scheme: DerivationScheme) -> XPrv {
+fn derive_private(xprv: &XPrv, index: DerivationIndex, scheme: DerivationScheme) -> XPrv {
     /*
      * If so (hardened child):
      *    let Z = HMAC-SHA512(Key = cpar, Data = 0x00 || ser256(left(kpar)) || ser32(i)).
@@ -677,7 +751,7 @@ fn derive_private(xprv: &XPrv, index: DerivationIndex, _scheme: DerivationScheme
 
     let mut zmac = Hmac::new(Sha512::new(), &chaincode);
     let mut imac = Hmac::new(Sha512::new(), &chaincode);
-    let seri = le32(index);
+    let seri = serialize_index(index, scheme);
     match to_type(index) {
         DerivationType::Soft(_) => {
             let pk = mk_public_key(ekey);
@@ -704,9 +778,9 @@ fn derive_private(xprv: &XPrv, index: DerivationIndex, _scheme: DerivationScheme
     let zr = &zout[32..64];
 
     // left = kl + 8 * trunc28(zl)
-    let left = add_28_mul8(kl, zl);
+    let left = add_28_mul8(kl, zl, scheme);
     // right = zr + kr
-    let right = add_256bits(kr, zr);
+    let right = add_256bits(kr, zr, scheme);
 
     let mut iout = [0u8; 64];
     imac.raw_result(&mut iout);
@@ -721,9 +795,9 @@ fn derive_private(xprv: &XPrv, index: DerivationIndex, _scheme: DerivationScheme
     XPrv::from_bytes(out)
 }
 
-fn point_of_trunc28_mul8(sk: &[u8]) -> [u8;32] {
+fn point_of_trunc28_mul8(sk: &[u8], scheme: DerivationScheme) -> [u8;32] {
     assert!(sk.len() == 32);
-    let copy = add_28_mul8(&[0u8;32], sk);
+    let copy = add_28_mul8(&[0u8;32], sk, scheme);
     let a = ge_scalarmult_base(&copy);
     a.to_bytes()
 }
@@ -743,13 +817,13 @@ fn point_plus(p1: &[u8], p2: &[u8]) -> Result<[u8;32]> {
     Ok(r)
 }
 
-fn derive_public(xpub: &XPub, index: DerivationIndex, _scheme: DerivationScheme) -> Result<XPub> {
+fn derive_public(xpub: &XPub, index: DerivationIndex, scheme: DerivationScheme) -> Result<XPub> {
     let pk = &xpub.as_ref()[0..32];
     let chaincode = &xpub.as_ref()[32..64];
 
     let mut zmac = Hmac::new(Sha512::new(), &chaincode);
     let mut imac = Hmac::new(Sha512::new(), &chaincode);
-    let seri = le32(index);
+    let seri = serialize_index(index, scheme);
     match to_type(index) {
         DerivationType::Soft(_) => {
             zmac.input(&[0x2]);
@@ -770,7 +844,7 @@ fn derive_public(xpub: &XPub, index: DerivationIndex, _scheme: DerivationScheme)
     let _zr = &zout[32..64];
 
     // left = kl + 8 * trunc28(zl)
-    let left = point_plus(pk, &point_of_trunc28_mul8(zl))?;
+    let left = point_plus(pk, &point_of_trunc28_mul8(zl, scheme))?;
 
     let mut iout = [0u8; 64];
     imac.raw_result(&mut iout);
@@ -866,9 +940,62 @@ mod tests {
         let derivation_index = 0x10000000;
         let prv = XPrv::from_bytes(D1);
         let xpub = prv.public();
-        let child_prv = prv.derive(derivation_index);
-        let child_xpub = xpub.derive(derivation_index).unwrap();
-        assert_eq!(child_prv.public(), child_xpub);
+        for scheme in [DerivationScheme::V1, DerivationScheme::V2].iter() {
+            let child_prv = prv.derive(*scheme, derivation_index);
+            let child_xpub = xpub.derive(*scheme, derivation_index).unwrap();
+            assert_eq!(child_prv.public(), child_xpub);
+        }
+    }
+
+    #[test]
+    fn xprv_derive_v1() {
+        let prv = XPrv::from_bytes(D1);
+        let child_v1 = prv.derive(DerivationScheme::V1, 0x80000000);
+        let child_v2 = prv.derive(DerivationScheme::V2, 0x80000000);
+        assert!(child_v1 != child_v2);
+
+        let grandchild = child_v1.derive(DerivationScheme::V1, 0x80000001).derive(DerivationScheme::V1, 2);
+        let signature : Signature<Vec<u8>> = grandchild.sign(MSG);
+        assert!(grandchild.public().verify(MSG, &signature));
+    }
+
+    // V1 known answers: the root key of the all zero seed and its children,
+    // computed with a separate implementation of the legacy derivation of
+    // cardano-crypto (`DERIVATION_V1` in cbits/encrypted_sign.c): big
+    // endian index, kl = kl + mul8_v1(zl) mod l, kr = kr + zr without carry
+    const V1_ROOT : &str = "301604045de9138b8b23b6730495f7e34b5151d29ba3456bc9b332f6f084a551d646bc30cf126fa8ed776c05a8932a5ab35c8bac41eb01bb9a16cfe229b94b405d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b12";
+    const V1_VECTORS : [(&[u32], &str, &str); 5] = [
+        (&[0x80000000],
+         "92cff2d6341ef312036004b39f83bef00a1a22b3d4d3d5eb09a4d39e71e5050acfaf755bbfd679b2abd6b3c3762bb2bb7cae9e929cde011938f6d3fd2cd1020cc2e0de5b417711d73593a8db7d330de923d4e2f10024f5c61c135b4a312b6aa7",
+         "9bba7d4cdf537c7333ec3e421e97ec9b9d496ea019ccdeaa2eb9a6bbdc4112b6c2e0de5b417711d73593a8db7d330de923d4e2f10024f5c61c135b4a312b6aa7"),
+        (&[0x80000001],
+         "4b2b8807b8b55e4be3081271e96439d83bba81a294a44db3d93be3feb85dae0959f8c18de0bffc1b9ff6ac11f6a1a99885a73edb96f68b92f34f630ab877e0674a7f134c4b75467066cdb71c6c8dd61979af70430b372c81337a1caa2d6e10ac",
+         "1692a2e42ab62cc7fad51ced0e83e99d9c2ccff16835ed6d170c0428e63a851a4a7f134c4b75467066cdb71c6c8dd61979af70430b372c81337a1caa2d6e10ac"),
+        (&[1],
+         "2dfcc499f10a0922fd7a5c9759d117c3124a9a12ec73865b82f422ff78750602f2b1b9161d9d23f55fa42ca8af66dcdb774affab1ef37a69592f6ab420fa3a564c8aca70f135fd76817191bffbd74811c6f5c0a24bf0d80b02481e3900addd1e",
+         "c0c3d88ca102db1c24887634081e585bc0b3a3c03c5ff7a4743c9f7ebc83292a4c8aca70f135fd76817191bffbd74811c6f5c0a24bf0d80b02481e3900addd1e"),
+        (&[0x80000000, 0],
+         "f35c7bf83a5c39b2f53239839f5ff2331202cba355dc5dc45aa45b07e2e5050210d5628f788f645bd2e54e6dabb541b2175ef5c54bbf015eb3ead42df6d07dfb20c147193a763dadce7c015207d34c19d3e107149b7ceb43180ceaa0a52a6815",
+         "2bcd6e0d64d1a6482733c5dc8d7eb636f78f2fc5d3bde3bbf378489ae0640a0620c147193a763dadce7c015207d34c19d3e107149b7ceb43180ceaa0a52a6815"),
+        (&[0x80000000, 0x92345678, 1],
+         "b0d9425f834f91323ac22476a0fa39b2da42eb73bd04ffdc5aa5c4375abede02c8a5c63e4fa5a68920f0be36cf569e46356b49ffab5f09ff3d7b3ba66e268d35e80115ee4d007c038d2bb94aafd342544785c7d155c98a6eb94675c3feb027c3",
+         "ea0fb77674d1a1026d840102360b62b24952e855229d5c68be758b6dc64cc9f2e80115ee4d007c038d2bb94aafd342544785c7d155c98a6eb94675c3feb027c3"),
+    ];
+
+    #[test]
+    fn xprv_derive_v1_vectors() {
+        let root = XPrv::from_hex(V1_ROOT).unwrap();
+        assert_eq!(root.public(), XPub::from_hex("1c0c3ae1825e90b6ddda3f40a122c007e1008e83b2e102c142baefb721d72c1a5d3661deb9064f2d0e03fe85d68070b2fe33b4916059658e28ac7f7f91ca4b12").unwrap());
+        for &(path, xprv, xpub) in V1_VECTORS.iter() {
+            let (last, parent) = path.split_last().unwrap();
+            let parent = parent.iter().fold(root.clone(), |key, index| key.derive(DerivationScheme::V1, *index));
+            let child = parent.derive(DerivationScheme::V1, *last);
+            assert_eq!(child, XPrv::from_hex(xprv).unwrap(), "xprv of {:?}", path);
+            assert_eq!(child.public(), XPub::from_hex(xpub).unwrap(), "xpub of {:?}", path);
+            if *last < 0x80000000 {
+                assert_eq!(parent.public().derive(DerivationScheme::V1, *last).unwrap(), child.public(), "public derivation of {:?}", path);
+            }
+        }
     }
 
     #[test]
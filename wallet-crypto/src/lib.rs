@@ -3,12 +3,16 @@ extern crate serde_derive;
 extern crate serde;
 #[cfg(test)]
 extern crate serde_json;
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
 
 #[macro_use]
 extern crate log;
 
 extern crate bit_vec;
 extern crate bitreader;
+extern crate rand;
 
 extern crate rcw;
 
@@ -19,7 +23,9 @@ pub mod hdwallet;
 pub mod paperwallet;
 pub mod address;
 pub mod hdpayload;
+pub mod attributes;
 pub mod tx;
+pub mod partialtx;
 pub mod coin;
 pub mod redeem;
 pub mod hash;
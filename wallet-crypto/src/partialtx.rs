@@ -0,0 +1,207 @@
+//! partially signed transactions
+//!
+//! When the inputs of a transaction belong to different wallets (possibly
+//! on different machines) none of them can sign the whole transaction in
+//! one go. A `PartialTx` holds the transaction, the outputs spent by each
+//! of its inputs and the witnesses collected so far. It is passed from one
+//! signer to the next (it can be serialized with serde or CBOR), each
+//! signer adding the witnesses of the inputs it owns, until it is complete
+//! and can be finalized into a `TxAux`.
+//!
+
+use std::{fmt, result};
+
+use cbor;
+use cbor::{ExtendedResult};
+use config::{Config};
+use hdwallet::{XPrv};
+use tx::{Tx, TxId, TxOut, TxAux, TxInWitness};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// the number of spent outputs (first) does not match the number
+    /// of inputs of the transaction (second)
+    InputsMismatch(usize, usize),
+    /// there is no input at the given index
+    InvalidInputIndex(usize),
+    /// the witness does not sign the transaction for the address spent
+    /// by the input at the given index
+    InvalidWitness(usize),
+    /// the partial transactions are not for the same transaction
+    TxMismatch(TxId, TxId),
+    /// the input at the given index is not signed yet
+    MissingWitness(usize),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::InputsMismatch(spent, inputs) => write!(f, "{} spent outputs given for {} transaction inputs", spent, inputs),
+            &Error::InvalidInputIndex(index) => write!(f, "No transaction input at index {}", index),
+            &Error::InvalidWitness(index) => write!(f, "Invalid witness for the transaction input at index {}", index),
+            &Error::TxMismatch(ref expected, ref given) => write!(f, "Expected partial transaction {} but received {}", expected, given),
+            &Error::MissingWitness(index) => write!(f, "The transaction input at index {} is not signed", index),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct PartialTx {
+    tx: Tx,
+    /// the outputs spent by the transaction's inputs, in the same order
+    spent: Vec<TxOut>,
+    witnesses: Vec<Option<TxInWitness>>,
+}
+impl PartialTx {
+    /// start collecting the witnesses of the given transaction.
+    ///
+    /// `spent` are the outputs (address and value) spent by each of
+    /// the transaction's inputs, in the same order.
+    pub fn new(tx: Tx, spent: Vec<TxOut>) -> Result<Self> {
+        if spent.len() != tx.inputs.len() {
+            return Err(Error::InputsMismatch(spent.len(), tx.inputs.len()));
+        }
+        let witnesses = spent.iter().map(|_| None).collect();
+        Ok(PartialTx { tx: tx, spent: spent, witnesses: witnesses })
+    }
+
+    pub fn id(&self) -> TxId { self.tx.id() }
+    pub fn tx(&self) -> &Tx { &self.tx }
+    pub fn spent_outputs(&self) -> &[TxOut] { &self.spent }
+    pub fn witnesses(&self) -> &[Option<TxInWitness>] { &self.witnesses }
+
+    /// the indices of the inputs not signed yet
+    pub fn missing(&self) -> Vec<usize> {
+        self.witnesses.iter().enumerate().filter(|&(_, w)| w.is_none()).map(|(i, _)| i).collect()
+    }
+
+    pub fn is_complete(&self) -> bool { self.witnesses.iter().all(|w| w.is_some()) }
+
+    /// add the witness of the input at the given index. The witness is
+    /// checked against the address spent by the input.
+    pub fn add_witness(&mut self, cfg: &Config, index: usize, witness: TxInWitness) -> Result<()> {
+        if index >= self.spent.len() { return Err(Error::InvalidInputIndex(index)); }
+        if ! witness.verify(cfg, &self.spent[index].address, &self.tx) {
+            return Err(Error::InvalidWitness(index));
+        }
+        self.witnesses[index] = Some(witness);
+        Ok(())
+    }
+
+    /// sign the input at the given index with the given private key
+    pub fn sign(&mut self, cfg: &Config, index: usize, key: &XPrv) -> Result<()> {
+        let witness = TxInWitness::new(cfg, key, &self.tx);
+        self.add_witness(cfg, index, witness)
+    }
+
+    /// collect the witnesses of `other`, signed in parallel of this
+    /// partial transaction, for the inputs not signed here.
+    pub fn merge(&mut self, cfg: &Config, other: &PartialTx) -> Result<()> {
+        if self.tx != other.tx || self.spent != other.spent {
+            return Err(Error::TxMismatch(self.id(), other.id()));
+        }
+        for (index, witness) in other.witnesses.iter().enumerate() {
+            if let &Some(ref witness) = witness {
+                if self.witnesses[index].is_none() {
+                    self.add_witness(cfg, index, witness.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// finalize the transaction, all the inputs must be signed
+    pub fn finalize(self) -> Result<TxAux> {
+        if let Some(index) = self.missing().first() {
+            return Err(Error::MissingWitness(*index));
+        }
+        let witnesses = self.witnesses.into_iter().filter_map(|w| w).collect();
+        Ok(TxAux::new(self.tx, witnesses))
+    }
+}
+impl fmt::Display for PartialTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Tx:\n{}", self.tx)?;
+        writeln!(f, "signed: {}/{}", self.witnesses.len() - self.missing().len(), self.witnesses.len())
+    }
+}
+// the missing witnesses are encoded as empty arrays, the others as
+// single element arrays (like an Haskell `Maybe`).
+impl cbor::CborValue for PartialTx {
+    fn encode(&self) -> cbor::Value {
+        let witnesses = self.witnesses.iter().map(|witness| {
+            cbor::Value::Array(witness.iter().map(cbor::CborValue::encode).collect())
+        }).collect();
+        cbor::Value::Array(
+            vec![ cbor::CborValue::encode(&self.tx)
+                , cbor::CborValue::encode(&self.spent)
+                , cbor::Value::Array(witnesses)
+                ]
+        )
+    }
+    fn decode(value: cbor::Value) -> cbor::Result<Self> {
+        value.array().and_then(|array| {
+            let (array, tx) = cbor::array_decode_elem(array, 0).embed("tx")?;
+            let (array, spent) = cbor::array_decode_elem(array, 0).embed("spent outputs")?;
+            let (array, witnesses) : (Vec<cbor::Value>, Vec<Vec<TxInWitness>>) = cbor::array_decode_elem(array, 0).embed("witnesses")?;
+            if ! array.is_empty() {
+                return cbor::Result::array(array, cbor::Error::UnparsedValues);
+            }
+            let (tx, spent) : (Tx, Vec<TxOut>) = (tx, spent);
+            if spent.len() != tx.inputs.len() || witnesses.len() != tx.inputs.len() || witnesses.iter().any(|w| w.len() > 1) {
+                return cbor::Result::array(vec![], cbor::Error::InvalidSize(tx.inputs.len()));
+            }
+            let witnesses = witnesses.into_iter().map(|mut w| w.pop()).collect();
+            Ok(PartialTx { tx: tx, spent: spent, witnesses: witnesses })
+        }).embed("while decoding PartialTx")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tx::{TxIn};
+    use address::{ExtendedAddr, AddrType, SpendingData, Attributes};
+    use coin::{Coin};
+    use hash::{Blake2b256};
+
+    fn key_and_address(seed: u8) -> (XPrv, ExtendedAddr) {
+        let key = XPrv::generate_from_seed(&::hdwallet::Seed::from_bytes([seed;32]));
+        let addr = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key.public()), Attributes::new_bootstrap_era(None));
+        (key, addr)
+    }
+
+    #[test]
+    fn collect_witnesses_from_two_signers() {
+        let cfg = Config::default();
+        let (key1, addr1) = key_and_address(1);
+        let (key2, addr2) = key_and_address(2);
+
+        let mut tx = Tx::new();
+        tx.add_input(TxIn::new(Blake2b256::new(&[1]), 0));
+        tx.add_input(TxIn::new(Blake2b256::new(&[2]), 1));
+        tx.add_output(TxOut::new(addr1.clone(), Coin::new(10).unwrap()));
+        let spent = vec![TxOut::new(addr1, Coin::new(7).unwrap()), TxOut::new(addr2, Coin::new(5).unwrap())];
+
+        let mut ptx = PartialTx::new(tx, spent).unwrap();
+        assert_eq!(ptx.sign(&cfg, 0, &key2), Err(Error::InvalidWitness(0)));
+        ptx.sign(&cfg, 0, &key1).unwrap();
+        assert_eq!(ptx.missing(), vec![1]);
+        assert_eq!(ptx.clone().finalize(), Err(Error::MissingWitness(1)));
+
+        // the second signer receives the partial transaction encoded
+        let bytes = cbor::encode_to_cbor(&ptx).unwrap();
+        let mut other : PartialTx = cbor::decode_from_cbor(&bytes).unwrap();
+        assert_eq!(other, ptx);
+        other.sign(&cfg, 1, &key2).unwrap();
+
+        ptx.merge(&cfg, &other).unwrap();
+        assert!(ptx.is_complete());
+        let spent = ptx.spent_outputs().to_vec();
+        let txaux = ptx.finalize().unwrap();
+        for (witness, output) in txaux.witnesses.iter().zip(spent.iter()) {
+            assert!(witness.verify(&cfg, &output.address, &txaux.tx));
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{fmt, ops, iter, vec, slice, convert};
-use std::collections::{LinkedList, BTreeMap};
+use std::collections::{LinkedList};
 
 use hash::{Blake2b256};
 
@@ -9,7 +9,8 @@ use config::{Config};
 use redeem;
 
 use hdwallet::{Signature, XPub, XPrv};
-use address::{ExtendedAddr, SpendingData};
+use address::{ExtendedAddr, SpendingData, Script};
+use attributes::{AttributesMap};
 use hdpayload;
 use bip44::{Addressing};
 use coin;
@@ -56,9 +57,10 @@ impl cbor::CborValue for TxOut {
     }
 }
 
-type TODO = u8;
-type ValidatorScript = TODO;
-type RedeemerScript = TODO;
+/// the script locking the funds of a script address
+pub type ValidatorScript = Script;
+/// the script provided to unlock the funds of a script address
+pub type RedeemerScript = Script;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum TxInWitness {
@@ -73,15 +75,32 @@ impl fmt::Display for TxInWitness {
         write!(f, "{:?}", self)
     }
 }
+/// signing tag of the transactions signed by a public key witness
+const TAG_SIGN_TX: u8 = 0x01;
+/// signing tag of the transactions signed by a redeem witness
+const TAG_SIGN_REDEEM_TX: u8 = 0x02;
+
+/// the data signed by the witnesses of the transaction, prefixed by the
+/// signing tag of the witness
+fn tx_sign_data(cfg: &Config, tag: u8, tx: &Tx) -> Vec<u8> {
+    let txid = cbor::encode_to_cbor(&tx.id()).unwrap();
+
+    let mut vec = vec![ tag ];
+    vec.extend_from_slice(&cbor::encode_to_cbor(&cfg.protocol_magic).unwrap());
+    vec.extend_from_slice(&txid);
+    vec
+}
+
 impl TxInWitness {
     /// create a TxInWitness from a given private key `XPrv` for the given transaction `Tx`.
     pub fn new(cfg: &Config, key: &XPrv, tx: &Tx) -> Self {
-        let txid = cbor::encode_to_cbor(&tx.id()).unwrap();
+        TxInWitness::PkWitness(key.public(), key.sign(&tx_sign_data(cfg, TAG_SIGN_TX, tx)))
+    }
 
-        let mut vec = vec![ 0x01 ]; // this is the tag for TxSignature
-        vec.extend_from_slice(&cbor::encode_to_cbor(&cfg.protocol_magic).unwrap());
-        vec.extend_from_slice(&txid);
-        TxInWitness::PkWitness(key.public(), key.sign(&vec))
+    /// create a TxInWitness from a given redeem private key for the given
+    /// transaction `Tx`, spending the funds of the associated redeem address.
+    pub fn new_redeem(cfg: &Config, key: &redeem::PrivateKey, tx: &Tx) -> Self {
+        TxInWitness::RedeemWitness(key.public(), key.sign(&tx_sign_data(cfg, TAG_SIGN_REDEEM_TX, tx)))
     }
 
     /// verify a given extended address is associated to the witness.
@@ -94,7 +113,12 @@ impl TxInWitness {
 
                 &ea == address
             },
-            &TxInWitness::ScriptWitness(_, _) => { unimplemented!() },
+            &TxInWitness::ScriptWitness(ref validator, _) => {
+                let sd = SpendingData::ScriptASD(validator.clone());
+                let ea = ExtendedAddr::new(address.addr_type, sd, address.attributes.clone());
+
+                &ea == address
+            },
             &TxInWitness::RedeemWitness(ref pk, _) => {
                 let sd = SpendingData::RedeemASD(pk.clone());
                 let ea = ExtendedAddr::new(address.addr_type, sd, address.attributes.clone());
@@ -106,26 +130,16 @@ impl TxInWitness {
 
     /// verify the signature against the given transation `Tx`
     ///
+    /// the scripts are not evaluated: a `ScriptWitness` never verifies.
+    ///
     pub fn verify_tx(&self, cfg: &Config, tx: &Tx) -> bool {
         match self {
             &TxInWitness::PkWitness(ref pk, ref sig) => {
-                let txid = cbor::encode_to_cbor(&tx.id()).unwrap();
-
-                let mut vec = vec![ 0x01 ]; // this is the tag for TxSignature
-                vec.extend_from_slice(&cbor::encode_to_cbor(&cfg.protocol_magic).unwrap());
-                vec.extend_from_slice(&txid);
-
-                pk.verify(&vec, sig)
+                pk.verify(&tx_sign_data(cfg, TAG_SIGN_TX, tx), sig)
             },
-            &TxInWitness::ScriptWitness(_, _) => { unimplemented!() },
+            &TxInWitness::ScriptWitness(_, _) => { false },
             &TxInWitness::RedeemWitness(ref pk, ref sig) => {
-                let txid = cbor::encode_to_cbor(&tx.id()).unwrap();
-
-                let mut vec = vec![ 0x01 ]; // this is the tag for TxSignature
-                vec.extend_from_slice(&cbor::encode_to_cbor(&cfg.protocol_magic).unwrap());
-                vec.extend_from_slice(&txid);
-
-                pk.verify(sig, &vec)
+                pk.verify(sig, &tx_sign_data(cfg, TAG_SIGN_REDEEM_TX, tx))
             },
         }
     }
@@ -146,7 +160,14 @@ impl cbor::CborValue for TxInWitness {
                 );
                 (0u64, cbor::encode_to_cbor(&v).unwrap())
             },
-            &TxInWitness::ScriptWitness(_, _) => { unimplemented!() },
+            &TxInWitness::ScriptWitness(ref validator, ref redeemer) => {
+                let v = cbor::Value::Array(
+                    vec![ cbor::CborValue::encode(validator)
+                        , cbor::CborValue::encode(redeemer)
+                        ]
+                );
+                (1u64, cbor::encode_to_cbor(&v).unwrap())
+            },
             &TxInWitness::RedeemWitness(ref pk, ref sig) => {
                 let v = cbor::Value::Array(
                     vec![ cbor::CborValue::encode(pk)
@@ -177,6 +198,17 @@ impl cbor::CborValue for TxInWitness {
                         Ok(TxInWitness::PkWitness(pk, sig))
                     }).embed("while decoding `TxInWitness::PkWitness`")
                 },
+                1u64 => {
+                    let (sum_type, tag) : (Vec<cbor::Value>, cbor::Value) = cbor::array_decode_elem(sum_type, 0).embed("sum_type's value")?;
+                    if !sum_type.is_empty() { return cbor::Result::array(sum_type, cbor::Error::UnparsedValues); }
+                    tag.tag().and_then(|(t, v)| {
+                        if t != 24 { return cbor::Result::tag(t, v, cbor::Error::InvalidTag(t)); }
+                        (*v).bytes()
+                    }).and_then(|bytes| {
+                        let (validator, redeemer) = cbor::decode_from_cbor(bytes.as_ref())?;
+                        Ok(TxInWitness::ScriptWitness(validator, redeemer))
+                    }).embed("while decoding `TxInWitness::ScriptWitness`")
+                },
                 2u64 => {
                     let (sum_type, tag) : (Vec<cbor::Value>, cbor::Value) = cbor::array_decode_elem(sum_type, 0).embed("sum_type's value")?;
                     if !sum_type.is_empty() { return cbor::Result::array(sum_type, cbor::Error::UnparsedValues); }
@@ -196,7 +228,7 @@ impl cbor::CborValue for TxInWitness {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct TxIn {
     pub id: TxId,
     pub index: u32,
@@ -239,9 +271,10 @@ impl cbor::CborValue for TxIn {
 pub struct Tx {
     pub inputs: LinkedList<TxIn>,
     pub outputs: LinkedList<TxOut>,
-    // attributes: TxAttributes
-    //
-    // So far, there is no TxAttributes... the structure contains only the unparsed/unknown stuff
+    /// so far there are no known transaction attributes, they are all kept
+    /// unparsed
+    #[serde(default)]
+    pub attributes: AttributesMap,
 }
 impl fmt::Display for Tx {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -257,7 +290,7 @@ impl fmt::Display for Tx {
 impl Tx {
     pub fn new() -> Self { Tx::new_with(LinkedList::new(), LinkedList::new()) }
     pub fn new_with(ins: LinkedList<TxIn>, outs: LinkedList<TxOut>) -> Self {
-        Tx { inputs: ins, outputs: outs }
+        Tx { inputs: ins, outputs: outs, attributes: AttributesMap::new() }
     }
     pub fn id(&self) -> TxId {
         let buf = cbor::encode_to_cbor(self).expect("to cbor-encode a Tx in a vector in memory");
@@ -274,7 +307,7 @@ impl cbor::CborValue for Tx {
     fn encode(&self) -> cbor::Value {
         let inputs  = cbor::CborValue::encode(&self.inputs);
         let outputs = cbor::CborValue::encode(&self.outputs);
-        let attr    = cbor::Value::Object(BTreeMap::new());
+        let attr    = cbor::CborValue::encode(&self.attributes);
         cbor::Value::Array(
             vec![ inputs
                 , outputs
@@ -283,10 +316,11 @@ impl cbor::CborValue for Tx {
         )
     }
     fn decode(value: cbor::Value) -> cbor::Result<Self> {
-        value.decode().and_then(|(input_values, output_values, _attributes) : (cbor::Value, cbor::Value, cbor::Value)| {
+        value.decode().and_then(|(input_values, output_values, attributes) : (cbor::Value, cbor::Value, cbor::Value)| {
             let inputs  = input_values.decode().embed("while decoding Tx's TxIn")?;
             let outputs = output_values.decode().embed("while decoding Tx's TxOut")?;
-            Ok(Tx::new_with(inputs, outputs))
+            let attributes = attributes.decode().embed("while decoding Tx's attributes")?;
+            Ok(Tx { inputs: inputs, outputs: outputs, attributes: attributes })
         }).embed("while decoding Tx")
     }
 
@@ -330,6 +364,10 @@ impl Inputs {
     pub fn len(&self) -> usize { self.0.len() }
     pub fn is_empty(&self) -> bool { self.0.is_empty() }
     pub fn append(&mut self, other: &mut Self) { self.0.append(&mut other.0)}
+
+    pub fn total(&self) -> coin::Result<Coin> {
+        self.iter().fold(Coin::new(0), |acc, ref i| acc.and_then(|v| v + i.value()))
+    }
 }
 impl convert::AsRef<Inputs> for Inputs {
     fn as_ref(&self) -> &Self { self }
@@ -418,6 +456,7 @@ pub mod fee {
     //! fee stabilisation related algorithm
 
     use std::{result, fmt};
+    use rand::{self, Rng};
     use super::*;
 
     /// fee
@@ -454,32 +493,302 @@ pub mod fee {
 
     pub trait Algorithm {
         fn compute(&self, policy: SelectionPolicy, inputs: &Inputs, outputs: &Outputs, change_addr: &ExtendedAddr) -> Result<(Fee, Inputs, Coin)>;
+
+        /// split the change of the transaction spending the given inputs
+        /// (as returned by `compute`) between the change addresses.
+        /// Returns the fee and the change outputs.
+        fn split_change(&self, inputs: &[Input], outputs: &Outputs, fee: Fee, change: Coin, change_addrs: &[ExtendedAddr], min_output_value: Coin) -> Result<(Fee, Vec<TxOut>)>;
     }
 
-    #[derive(Serialize, Deserialize, PartialEq, PartialOrd, Debug, Clone, Copy)]
+    /// a fee coefficient: a fixed point number with 9 decimals (as the
+    /// `Coeff` of the protocol's `TxSizeLinear` fee policy)
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+    pub struct Coeff(u64);
+    impl Coeff {
+        /// the coefficient `integral.nanos`, `nanos` being the fractional
+        /// part in 10^-9. `None` if `nanos` is not lower than
+        /// `1_000_000_000` or if the coefficient does not fit in 64 bits of
+        /// nanos.
+        pub fn new(integral: u64, nanos: u64) -> Option<Self> {
+            if nanos >= NANOS_PER_UNIT { return None; }
+            integral.checked_mul(NANOS_PER_UNIT).and_then(|v| v.checked_add(nanos)).map(Coeff)
+        }
+        pub fn from_nanos(nanos: u64) -> Self { Coeff(nanos) }
+        pub fn nanos(&self) -> u64 { self.0 }
+    }
+    impl fmt::Display for Coeff {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}.{:09}", self.0 / NANOS_PER_UNIT, self.0 % NANOS_PER_UNIT)
+        }
+    }
+    const NANOS_PER_UNIT: u64 = 1_000_000_000;
+
+    /// the fee policy of the network: `constant + coefficient * size` where
+    /// `size` is the size of the encoded `TxAux`, in bytes.
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
     pub struct LinearFee {
         /// this is the minimal fee
-        constant: f64,
+        constant: Coeff,
         /// the transaction's size coefficient fee
-        coefficient: f64
+        coefficient: Coeff
     }
     impl LinearFee {
-        pub fn new(constant: f64, coefficient: f64) -> Self {
+        pub fn new(constant: Coeff, coefficient: Coeff) -> Self {
             LinearFee { constant: constant, coefficient: coefficient }
         }
 
+        pub fn constant(&self) -> Coeff { self.constant }
+        pub fn coefficient(&self) -> Coeff { self.coefficient }
+
+        /// the minimal fee of a transaction of the given size, rounded up
+        /// to the Lovelace
         pub fn estimate(&self, sz: usize) -> Result<Fee> {
-            let fee = self.constant + self.coefficient * (sz as f64);
-            let coin = Coin::new(fee as u64)?;
-            Ok(Fee(coin))
+            let nanos = self.coefficient.0.checked_mul(sz as u64)
+                            .and_then(|v| v.checked_add(self.constant.0))
+                            .ok_or(Error::CoinError(coin::Error::OutOfBound(u64::max_value())))?;
+            let lovelaces = nanos / NANOS_PER_UNIT + if nanos % NANOS_PER_UNIT == 0 { 0 } else { 1 };
+            Ok(Fee(Coin::new(lovelaces)?))
+        }
+
+        /// the minimal fee of the transaction spending the given inputs to
+        /// the outputs and, if any, to the change output.
+        ///
+        /// the fee is estimated on the `TxAux` with witnesses of the size
+        /// of the inputs' witnesses.
+        fn tx_fee(&self, inputs: &[Input], outputs: &Outputs, change: Option<(&ExtendedAddr, Coin)>) -> Result<Fee> {
+            let change : Vec<TxOut> = change.into_iter().map(|(addr, value)| TxOut::new(addr.clone(), value)).collect();
+            self.tx_fee_with_change(inputs, outputs, &change)
+        }
+
+        /// same as `tx_fee` with any number of change outputs
+        fn tx_fee_with_change(&self, inputs: &[Input], outputs: &Outputs, change: &[TxOut]) -> Result<Fee> {
+            let mut tx = Tx::new_with(
+                inputs.iter().map(|input| input.ptr.clone()).collect(),
+                outputs.iter().cloned().collect()
+            );
+            for output in change {
+                tx.add_output(output.clone());
+            }
+            let witnesses = inputs.iter().map(|_| dummy_witness()).collect();
+            let txaux = TxAux::new(tx, witnesses);
+            self.estimate(cbor::encode_to_cbor(&txaux).unwrap().len())
+        }
+
+        /// estimate the fee of the transaction spending the given inputs
+        /// to the outputs and, if any, to the change address.
+        ///
+        /// the change is accounted for with the largest possible value, so
+        /// the fee covers the change whatever its value.
+        fn estimate_tx(&self, inputs: &[Input], outputs: &Outputs, change_addr: Option<&ExtendedAddr>) -> Result<Fee> {
+            match change_addr {
+                None => self.tx_fee(inputs, outputs, None),
+                Some(addr) => self.tx_fee(inputs, outputs, Some((addr, Coin::new(coin::MAX_COIN)?))),
+            }
+        }
+
+        /// the fee and the change of the transaction spending the given
+        /// inputs, `None` if the inputs do not cover the outputs and the fee.
+        ///
+        /// if the inputs do not cover the change output's fee, there is no
+        /// change: what is left over is given to the fee.
+        fn balance(&self, inputs: &Inputs, outputs: &Outputs, change_addr: &ExtendedAddr) -> Result<Option<(Fee, Coin)>> {
+            let available = match inputs.total()? - outputs.total()? {
+                None => return Ok(None),
+                Some(available) => available,
+            };
+            if let Some((fee, change)) = self.balance_with_change(inputs, outputs, change_addr, available)? {
+                return Ok(Some((fee, change)));
+            }
+            let fee = self.tx_fee(inputs, outputs, None)?;
+            if fee.to_coin() > available { return Ok(None); }
+            Ok(Some((Fee(available), Coin::zero())))
+        }
+
+        /// the minimal fee of the transaction with a change output, and the
+        /// change, `None` if `available` (the inputs minus the outputs) does
+        /// not cover the fee and some change.
+        ///
+        /// the size of the change output depends on the change, so on the
+        /// fee: starting from the fee with the smallest change output, the
+        /// fee is raised to the fee required with the resulting change, or
+        /// to the fee from which the change is encoded in fewer bytes, until
+        /// it covers the transaction (a fixed point). The fees skipped do
+        /// not cover their transaction, so the fee found is minimal.
+        fn balance_with_change(&self, inputs: &Inputs, outputs: &Outputs, change_addr: &ExtendedAddr, available: Coin) -> Result<Option<(Fee, Coin)>> {
+            let available = u64::from(available);
+            let mut fee = u64::from(self.tx_fee(inputs, outputs, Some((change_addr, Coin::zero())))?.to_coin());
+            while fee < available {
+                let change = available - fee;
+                let required = u64::from(self.tx_fee(inputs, outputs, Some((change_addr, Coin::new(change)?)))?.to_coin());
+                if required <= fee {
+                    return Ok(Some((Fee(Coin::new(fee)?), Coin::new(change)?)));
+                }
+                let fewer_bytes = available - cbor_uint_range_start(change) + 1;
+                fee = ::std::cmp::min(required, fewer_bytes);
+            }
+            Ok(None)
+        }
+
+        /// select the inputs in the given order until the outputs and the
+        /// fee are covered
+        fn select_greedy<'a, I>(&self, candidates: I, outputs: &Outputs, change_addr: &ExtendedAddr) -> Result<(Fee, Inputs, Coin)>
+            where I: Iterator<Item = &'a Input>
+        {
+            let mut selected_inputs = Inputs::new();
+            for input in candidates {
+                selected_inputs.push(input.clone());
+                if let Some((fee, change)) = self.balance(&selected_inputs, outputs, change_addr)? {
+                    return Ok((fee, selected_inputs, change));
+                }
+            }
+            Err(Error::NotEnoughInput)
+        }
+
+        /// random selection of inputs covering the outputs, then improved
+        /// with other random inputs to get a change close to the outputs'
+        /// value (i.e. the selected inputs close to twice the outputs, but
+        /// no more than three times).
+        fn select_random_improve(&self, inputs: &Inputs, outputs: &Outputs, change_addr: &ExtendedAddr) -> Result<(Fee, Inputs, Coin)> {
+            let mut candidates : Vec<&Input> = inputs.iter().collect();
+            rand::thread_rng().shuffle(&mut candidates);
+
+            let (mut fee, mut selected_inputs, mut change) = self.select_greedy(candidates.iter().cloned(), outputs, change_addr)?;
+
+            let target = u64::from(outputs.total()?);
+            let ideal = target.saturating_mul(2);
+            let upper = target.saturating_mul(3);
+            let distance = |v: u64| if v > ideal { v - ideal } else { ideal - v };
+
+            for input in candidates.into_iter().skip(selected_inputs.len()) {
+                let current = selected_inputs.total()?.into();
+                let improved = u64::saturating_add(current, input.value().into());
+                if improved > upper || distance(improved) >= distance(current) { continue; }
+
+                let mut improved_inputs = selected_inputs.clone();
+                improved_inputs.push(input.clone());
+                if let Some((f, c)) = self.balance(&improved_inputs, outputs, change_addr)? {
+                    fee = f;
+                    selected_inputs = improved_inputs;
+                    change = c;
+                }
+            }
+            Ok((fee, selected_inputs, change))
+        }
+
+        /// branch and bound search of the inputs paying exactly the outputs
+        /// and the fee of a transaction without change. The selected inputs
+        /// may exceed it by no more than the cost of a change output, the
+        /// excess is then given to the fee.
+        fn select_exact(&self, inputs: &Inputs, outputs: &Outputs, change_addr: &ExtendedAddr) -> Result<Option<(Fee, Inputs, Coin)>> {
+            let base_fee = u64::from(self.estimate_tx(&[], outputs, None)?.to_coin());
+            let change_cost = u64::from(self.estimate_tx(&[], outputs, Some(change_addr))?.to_coin()) - base_fee;
+
+            // the value of the inputs minus the fee of spending them, largest first
+            let mut candidates = vec![];
+            for input in inputs.iter() {
+                let input_fee = u64::from(self.estimate_tx(&[input.clone()], outputs, None)?.to_coin()) - base_fee;
+                let value = u64::from(input.value());
+                if value > input_fee { candidates.push((value - input_fee, input)); }
+            }
+            candidates.sort_by(|a, b| b.0.cmp(&a.0));
+            let remaining = candidates.iter().map(|c| c.0).sum();
+
+            let mut search = ExactSearch {
+                algorithm: self,
+                outputs: outputs,
+                candidates: candidates,
+                target: u64::from(outputs.total()?) + base_fee,
+                change_cost: change_cost,
+                tries: BNB_MAX_TRIES,
+            };
+            let mut selected_inputs = Inputs::new();
+            match search.search(0, &mut selected_inputs, 0, remaining)? {
+                None => Ok(None),
+                Some((fee, change)) => Ok(Some((fee, selected_inputs, change))),
+            }
         }
     }
+
     impl Default for LinearFee {
-        fn default() -> Self { LinearFee::new(155381.0, 43.946) }
+        fn default() -> Self { LinearFee::new(Coeff::new(155381, 0).unwrap(), Coeff::new(43, 946_000_000).unwrap()) }
+    }
+
+    struct ExactSearch<'a> {
+        algorithm: &'a LinearFee,
+        outputs: &'a Outputs,
+        candidates: Vec<(u64, &'a Input)>,
+        target: u64,
+        change_cost: u64,
+        tries: usize,
+    }
+    impl<'a> ExactSearch<'a> {
+        /// `current` is the value of the `selected` inputs, `remaining` the
+        /// value of the candidates from `index`.
+        fn search(&mut self, index: usize, selected: &mut Inputs, current: u64, remaining: u64) -> Result<Option<(Fee, Coin)>> {
+            if self.tries == 0 { return Ok(None); }
+            self.tries -= 1;
+
+            if current + remaining < self.target || current > self.target + self.change_cost {
+                return Ok(None);
+            }
+            if current >= self.target {
+                return self.balance(selected);
+            }
+            if index >= self.candidates.len() { return Ok(None); }
+
+            let (value, input) = self.candidates[index];
+            selected.push(input.clone());
+            if let Some(result) = self.search(index + 1, selected, current + value, remaining - value)? {
+                return Ok(Some(result));
+            }
+            selected.0.pop();
+            self.search(index + 1, selected, current, remaining - value)
+        }
+
+        fn balance(&self, selected: &Inputs) -> Result<Option<(Fee, Coin)>> {
+            let fee = self.algorithm.estimate_tx(selected, self.outputs, None)?;
+            match selected.total()? - self.outputs.total()? - fee.to_coin() {
+                Some(excess) if u64::from(excess) <= self.change_cost => {
+                    Ok(Some((Fee((fee.to_coin() + excess)?), Coin::zero())))
+                },
+                _ => Ok(None),
+            }
+        }
+    }
+
+    /// change below this value (in Lovelace) is considered as dust, see
+    /// `SelectionPolicy::AvoidDustChange`
+    pub const DUST_THRESHOLD: u64 = 1_000_000;
+    /// the maximum number of steps of the branch and bound search
+    const BNB_MAX_TRIES: usize = 100_000;
+
+    /// a witness of the size of the witnesses of the inputs (public key
+    /// witnesses)
+    pub(super) fn dummy_witness() -> TxInWitness {
+        TxInWitness::PkWitness(XPub::from_bytes([0; ::hdwallet::XPUB_SIZE]), Signature::from_bytes([0; ::hdwallet::SIGNATURE_SIZE]))
+    }
+
+    /// split the value in equal outputs to the given addresses, the
+    /// remainder going to the first one
+    fn split_value(addrs: &[ExtendedAddr], value: u64) -> Result<Vec<TxOut>> {
+        let count = addrs.len() as u64;
+        let mut res = vec![];
+        for (index, addr) in addrs.iter().enumerate() {
+            let share = value / count + if index == 0 { value % count } else { 0 };
+            res.push(TxOut::new(addr.clone(), Coin::new(share)?));
+        }
+        Ok(res)
+    }
+
+    /// the smallest integer encoded in CBOR with as many bytes as `v`
+    fn cbor_uint_range_start(v: u64) -> u64 {
+        if v < 24 { 0 }
+        else if v <= 0xff { 24 }
+        else if v <= 0xffff { 0x100 }
+        else if v <= 0xffff_ffff { 0x1_0000 }
+        else { 0x1_0000_0000 }
     }
 
-    const TX_IN_WITNESS_CBOR_SIZE: usize = 140;
-    const CBOR_TXAUX_OVERHEAD: usize = 51;
     impl Algorithm for LinearFee {
         fn compute( &self
                   , policy: SelectionPolicy
@@ -492,65 +801,461 @@ pub mod fee {
             if inputs.is_empty() { return Err(Error::NoInputs); }
             if outputs.is_empty() { return Err(Error::NoOutputs); }
 
-            let output_value = outputs.total()?;
-            let mut fee = self.estimate(0)?;
-            let mut input_value = Coin::zero();
-            let mut selected_inputs = Inputs::new();
+            match policy {
+                SelectionPolicy::FirstMatchFirst => {
+                    self.select_greedy(inputs.iter(), outputs, change_addr)
+                },
+                SelectionPolicy::LargestFirst => {
+                    let mut candidates : Vec<&Input> = inputs.iter().collect();
+                    candidates.sort_by(|a, b| b.value().cmp(&a.value()));
+                    self.select_greedy(candidates.into_iter(), outputs, change_addr)
+                },
+                SelectionPolicy::RandomImprove => {
+                    self.select_random_improve(inputs, outputs, change_addr)
+                },
+                SelectionPolicy::BranchAndBound => {
+                    match self.select_exact(inputs, outputs, change_addr)? {
+                        Some(selection) => Ok(selection),
+                        None => self.compute(SelectionPolicy::LargestFirst, inputs, outputs, change_addr),
+                    }
+                },
+                SelectionPolicy::AvoidDustChange => {
+                    let (fee, mut selected_inputs, change) = self.select_greedy(inputs.iter(), outputs, change_addr)?;
+                    if change == Coin::zero() || u64::from(change) >= DUST_THRESHOLD {
+                        return Ok((fee, selected_inputs, change));
+                    }
+                    // one more input to make the change worth it
+                    for input in inputs.iter().skip(selected_inputs.len()) {
+                        selected_inputs.push(input.clone());
+                        if let Some((fee, change)) = self.balance(&selected_inputs, outputs, change_addr)? {
+                            if change == Coin::zero() || u64::from(change) >= DUST_THRESHOLD {
+                                return Ok((fee, selected_inputs, change));
+                            }
+                        }
+                        selected_inputs.0.pop();
+                    }
+                    // or the dust goes to the fee
+                    Ok((Fee((fee.to_coin() + change)?), selected_inputs, Coin::zero()))
+                },
+            }
+        }
+
+        /// split the change of the transaction spending the given inputs
+        /// (as returned by `Algorithm::compute`) into one output per change
+        /// address, of equal values.
+        ///
+        /// the fee is raised to cover the extra outputs. If the change does
+        /// not cover them with outputs of at least `min_output_value`, it
+        /// is split into fewer outputs, down to a single output to the
+        /// first change address. Returns the fee and the change outputs
+        /// (none if there is no change, or no change address: the change
+        /// is then given to the fee).
+        fn split_change( &self
+                           , inputs: &[Input]
+                           , outputs: &Outputs
+                           , fee: Fee
+                           , change: Coin
+                           , change_addrs: &[ExtendedAddr]
+                           , min_output_value: Coin
+                           )
+            -> Result<(Fee, Vec<TxOut>)>
+        {
+            if change == Coin::zero() { return Ok((fee, vec![])); }
+            if change_addrs.is_empty() { return Ok((Fee((fee.to_coin() + change)?), vec![])); }
+            let available = u64::from((fee.to_coin() + change)?);
+            let min_output_value = ::std::cmp::max(u64::from(min_output_value), 1);
+
+            for count in (2..change_addrs.len() + 1).rev() {
+                // the change outputs only get smaller as the fee is raised,
+                // so the required fee only decreases: this is a fixed point
+                let mut split_fee = u64::from(fee.to_coin());
+                while split_fee < available {
+                    let change_outputs = split_value(&change_addrs[..count], available - split_fee)?;
+                    let required = u64::from(self.tx_fee_with_change(inputs, outputs, &change_outputs)?.to_coin());
+                    if required > split_fee {
+                        split_fee = required;
+                        continue;
+                    }
+                    if change_outputs.iter().all(|output| u64::from(output.value) >= min_output_value) {
+                        return Ok((Fee(Coin::new(split_fee)?), change_outputs));
+                    }
+                    break;
+                }
+            }
+            Ok((fee, vec![TxOut::new(change_addrs[0].clone(), change)]))
+        }
+    }
+
+    /// the input selection method.
+    ///
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+    pub enum SelectionPolicy {
+        /// select the first inputs that matches, no optimisation
+        FirstMatchFirst,
+        /// select the inputs of largest value first, minimising the number
+        /// of inputs (and the fee)
+        LargestFirst,
+        /// select random inputs covering the outputs, then add random inputs
+        /// so the change is about the outputs' value: the wallet keeps
+        /// inputs of useful values for the next transactions
+        RandomImprove,
+        /// look for inputs paying exactly the outputs and the fee, so there
+        /// is no change. Falls back to `LargestFirst` if there is none.
+        BranchAndBound,
+        /// same as `FirstMatchFirst` but never create a change lower than
+        /// `DUST_THRESHOLD`: one more input is selected or the change is
+        /// given to the fee.
+        AvoidDustChange,
+    }
+    impl Default for SelectionPolicy {
+        fn default() -> Self { SelectionPolicy::FirstMatchFirst }
+    }
 
-            // create the Tx on the fly
-            let mut txins = LinkedList::new();
-            let     txouts : LinkedList<TxOut> = outputs.iter().cloned().collect();
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use quickcheck::{Arbitrary, Gen, TestResult};
+        use address::{AddrType, SpendingData, Attributes};
+        use bip44;
+        use hdwallet;
+
+        impl Arbitrary for SelectionPolicy {
+            fn arbitrary<G: Gen>(g: &mut G) -> Self {
+                *g.choose(&[ SelectionPolicy::FirstMatchFirst
+                           , SelectionPolicy::LargestFirst
+                           , SelectionPolicy::RandomImprove
+                           , SelectionPolicy::BranchAndBound
+                           , SelectionPolicy::AvoidDustChange
+                           ]).unwrap()
+            }
+        }
 
-            // for now we only support this selection algorithm
-            // we need to remove this assert when we extend to more
-            // granulated selection policy
-            assert!(policy == SelectionPolicy::FirstMatchFirst);
+        fn key() -> hdwallet::XPrv {
+            hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]))
+        }
 
-            for input in inputs.iter() {
-                input_value = (input_value + input.value())?;
-                selected_inputs.push(input.clone());
-                txins.push_back(input.ptr.clone());
+        fn address() -> ExtendedAddr {
+            ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key().public()), Attributes::new_bootstrap_era(None))
+        }
 
-                // calculate fee from the Tx serialised + estimated size for signing
-                let mut tx = Tx::new_with(txins.clone(), txouts.clone());
-                let txbytes = cbor::encode_to_cbor(&tx).unwrap();
+        /// the size of the signed transaction
+        fn signed_size(selected: &Inputs, outputs: &Outputs, change: Option<(&ExtendedAddr, Coin)>) -> usize {
+            let mut tx = Tx::new_with(selected.iter().map(|i| i.ptr.clone()).collect(), outputs.iter().cloned().collect());
+            if let Some((addr, value)) = change { tx.add_output(TxOut::new(addr.clone(), value)); }
+            let witness = TxInWitness::new(&Config::default(), &key(), &tx);
+            let txaux = TxAux::new(tx, selected.iter().map(|_| witness.clone()).collect());
+            cbor::encode_to_cbor(&txaux).unwrap().len()
+        }
+
+        fn input(addr: &ExtendedAddr, index: u32, value: u64) -> Input {
+            let addressing = bip44::Account::new(0).unwrap().external().unwrap().index(0).unwrap();
+            let txin = TxIn::new(TxId::new(&[index as u8, (index >> 8) as u8]), index);
+            Input::new(txin, TxOut::new(addr.clone(), Coin::new(value).unwrap()), addressing)
+        }
 
-                let estimated_fee = (self.estimate(txbytes.len() + CBOR_TXAUX_OVERHEAD + (TX_IN_WITNESS_CBOR_SIZE * selected_inputs.len())))?;
+        // the values are given in tenth of ADA, so there are values both
+        // below and above the fee
+        fn inputs(addr: &ExtendedAddr, values: &[u16]) -> Inputs {
+            values.iter().enumerate().map(|(i, v)| input(addr, i as u32, *v as u64 * 100_000)).collect()
+        }
 
-                // add the change in the estimated fee
-                match output_value - input_value - estimated_fee.to_coin() {
-                    None => {},
-                    Some(change_value) => {
-                        tx.add_output(TxOut::new(change_addr.clone(), change_value))
+        quickcheck! {
+            fn selection_balances(policy: SelectionPolicy, input_values: Vec<u16>, output_values: Vec<u16>) -> TestResult {
+                if input_values.is_empty() || output_values.is_empty() { return TestResult::discard(); }
+                let addr = address();
+                let inputs = inputs(&addr, &input_values);
+                let outputs : Outputs = output_values.iter().map(|v| {
+                    TxOut::new(addr.clone(), Coin::new(*v as u64 * 100_000 + 1).unwrap())
+                }).collect();
+                let alg = LinearFee::default();
+
+                match alg.compute(policy, &inputs, &outputs, &addr) {
+                    // not even all the inputs cover the outputs and the fee
+                    Err(Error::NotEnoughInput) => TestResult::from_bool(alg.balance(&inputs, &outputs, &addr).unwrap().is_none()),
+                    Err(_) => TestResult::failed(),
+                    Ok((fee, selected, change)) => {
+                        let balanced = selected.total() == (outputs.total().unwrap() + fee.to_coin()).and_then(|v| v + change);
+                        let given = selected.iter().all(|i| inputs.iter().any(|j| i == j));
+                        let distinct = selected.iter().enumerate().all(|(n, i)| selected.iter().skip(n + 1).all(|j| i.ptr != j.ptr));
+
+                        // the fee covers the transaction built from the selection
+                        let change_output = if change == Coin::zero() { None } else { Some((&addr, change)) };
+                        let size = signed_size(&selected, &outputs, change_output);
+                        let enough_fee = fee.to_coin() >= alg.estimate(size).unwrap().to_coin();
+
+                        // and one Lovelace less of fee (so one more of change)
+                        // does not cover the transaction
+                        let minimal_fee = change == Coin::zero() || {
+                            let size = signed_size(&selected, &outputs, Some((&addr, (change + Coin::new(1).unwrap()).unwrap())));
+                            u64::from(fee.to_coin()) - 1 < u64::from(alg.estimate(size).unwrap().to_coin())
+                        };
+
+                        let no_dust = policy != SelectionPolicy::AvoidDustChange
+                                   || change == Coin::zero()
+                                   || u64::from(change) >= DUST_THRESHOLD;
+
+                        TestResult::from_bool(balanced && given && distinct && enough_fee && minimal_fee && no_dust)
                     }
-                };
+                }
+            }
+        }
 
-                let txbytes = cbor::encode_to_cbor(&tx).unwrap();
-                let corrected_fee = self.estimate(txbytes.len() + CBOR_TXAUX_OVERHEAD + (TX_IN_WITNESS_CBOR_SIZE * selected_inputs.len()));
+        #[test]
+        fn linear_fee_rounds_up() {
+            let alg = LinearFee::default();
+            assert_eq!(alg.estimate(0).unwrap().to_coin(), Coin::new(155381).unwrap());
+            // 155381 + 43.946 * 200 = 164170.2
+            assert_eq!(alg.estimate(200).unwrap().to_coin(), Coin::new(164171).unwrap());
+            assert_eq!(alg.estimate(500).unwrap().to_coin(), Coin::new(177354).unwrap());
+            assert_eq!(format!("{}", alg.coefficient()), "43.946000000");
+        }
 
-                fee = corrected_fee?;
+        #[test]
+        fn coeff_bounds() {
+            assert_eq!(Coeff::new(43, 946_000_000), Some(Coeff::from_nanos(43_946_000_000)));
+            assert_eq!(Coeff::new(0, 999_999_999), Some(Coeff::from_nanos(999_999_999)));
+            assert_eq!(Coeff::new(0, 1_000_000_000), None);
+            assert_eq!(Coeff::new(18_446_744_073, 709_551_615), Some(Coeff::from_nanos(u64::max_value())));
+            assert_eq!(Coeff::new(18_446_744_073, 709_551_616), None);
+            assert_eq!(Coeff::new(u64::max_value(), 0), None);
+        }
 
-                if Ok(input_value) >= (output_value + fee.to_coin()) { break; }
-            }
+        #[test]
+        fn change_fee_fixed_point() {
+            let addr = address();
+            let alg = LinearFee::default();
+            let outputs : Outputs = vec![TxOut::new(addr.clone(), Coin::new(1_000_000).unwrap())].into_iter().collect();
+
+            // the change is encoded in fewer bytes once under 65536: the
+            // minimal fee leaves a change of 65535 rather than paying the
+            // fee of the larger change output
+            let fee_large = alg.tx_fee(&[input(&addr, 0, 0)], &outputs, Some((&addr, Coin::new(65536).unwrap()))).unwrap().to_coin();
+            let fee_small = alg.tx_fee(&[input(&addr, 0, 0)], &outputs, Some((&addr, Coin::new(65535).unwrap()))).unwrap().to_coin();
+            assert!(fee_small < fee_large);
+            let value = 1_000_000 + u64::from(fee_small) + 65535 + 1;
+            let inputs : Inputs = vec![input(&addr, 0, value)].into_iter().collect();
+            let (fee, _, change) = alg.compute(SelectionPolicy::FirstMatchFirst, &inputs, &outputs, &addr).unwrap();
+            assert_eq!(change, Coin::new(65535).unwrap());
+            assert_eq!(u64::from(fee.to_coin()), u64::from(fee_small) + 1);
+        }
 
-            if Ok(input_value) < (output_value + fee.to_coin()) {
-                return Err(Error::NotEnoughInput);
-            }
+        #[test]
+        fn branch_and_bound_exact_match() {
+            let addr = address();
+            let alg = LinearFee::default();
+            let outputs : Outputs = vec![TxOut::new(addr.clone(), Coin::new(1_000_000).unwrap())].into_iter().collect();
+
+            let mut inputs = inputs(&addr, &[30, 5, 2]);
+            let fee = alg.estimate_tx(&[input(&addr, 9, 0)], &outputs, None).unwrap();
+            let exact = input(&addr, 9, u64::from((outputs.total().unwrap() + fee.to_coin()).unwrap()));
+            inputs.push(exact.clone());
+
+            let (selected_fee, selected, change) = alg.compute(SelectionPolicy::BranchAndBound, &inputs, &outputs, &addr).unwrap();
+            assert_eq!(selected.as_slice(), &[exact]);
+            assert_eq!(selected_fee, fee);
+            assert_eq!(change, Coin::zero());
+
+            // without exact match: largest first
+            let (_, selected, change) = alg.compute(SelectionPolicy::BranchAndBound, &inputs.iter().take(3).cloned().collect(), &outputs, &addr).unwrap();
+            assert_eq!(selected.as_slice(), &inputs[0..1]);
+            assert!(change > Coin::zero());
+        }
 
-            Ok((fee, selected_inputs, (input_value - output_value - fee.to_coin()).unwrap()))
+        #[test]
+        fn split_change_outputs() {
+            let addr = address();
+            let alg = LinearFee::default();
+            let outputs : Outputs = vec![TxOut::new(addr.clone(), Coin::new(1_000_000).unwrap())].into_iter().collect();
+            let inputs = inputs(&addr, &[100]);
+            let change_addrs = vec![addr.clone(), addr.clone(), addr.clone()];
+            let (fee, selected, change) = alg.compute(SelectionPolicy::FirstMatchFirst, &inputs, &outputs, &addr).unwrap();
+
+            let (split_fee, change_outputs) = alg.split_change(&selected, &outputs, fee, change, &change_addrs, Coin::new(1).unwrap()).unwrap();
+            assert_eq!(change_outputs.len(), 3);
+            assert!(split_fee.to_coin() > fee.to_coin());
+            let total = change_outputs.iter().fold(split_fee.to_coin(), |acc, output| (acc + output.value).unwrap());
+            assert_eq!(total, (fee.to_coin() + change).unwrap());
+            let required = alg.tx_fee_with_change(&selected, &outputs, &change_outputs).unwrap();
+            assert!(required.to_coin() <= split_fee.to_coin());
+
+            // outputs under the minimal value: fewer outputs
+            let min_output_value = Coin::new(u64::from(change) / 3 + 1).unwrap();
+            let (_, change_outputs) = alg.split_change(&selected, &outputs, fee, change, &change_addrs, min_output_value).unwrap();
+            assert_eq!(change_outputs.len(), 2);
+
+            // not even two: the change is kept as it is
+            let (same_fee, change_outputs) = alg.split_change(&selected, &outputs, fee, change, &change_addrs, change).unwrap();
+            assert_eq!(same_fee, fee);
+            assert_eq!(change_outputs, vec![TxOut::new(addr.clone(), change)]);
         }
     }
 
-    /// the input selection method.
-    ///
+}
+
+pub mod validation {
+    //! checks of a transaction before it is signed
+    //!
+    //! the checks the network does on the transaction's content (and
+    //! which do not depend on the UTxOs) are done before the witnesses
+    //! are produced, so an invalid transaction is never signed.
+
+    use std::{result, fmt};
+    use super::*;
+
+    /// maximum size of a signed transaction on the mainnet, in bytes (the
+    /// `maxTxSize` of the mainnet genesis data)
+    pub const MAX_TX_SIZE: usize = 4096;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    pub enum Error {
+        NoInputs,
+        NoOutputs,
+        /// the input is spent more than once by the transaction
+        DuplicateInput(TxIn),
+        /// the output at the given index has no value
+        ZeroOutput(usize),
+        /// the value (second) of the output at the given index (first) is
+        /// lower than the minimal output value (third)
+        OutputTooSmall(usize, Coin, Coin),
+        /// the output address does not belong to the network
+        ForeignAddress(ExtendedAddr),
+        /// the change address does not belong to the network
+        ForeignChangeAddress(ExtendedAddr),
+        /// the sum of the outputs is not a valid value
+        OutputsOverflow(coin::Error),
+        /// the signed transaction would be of the given size (first), over
+        /// the maximum size (second)
+        TxTooLarge(usize, usize),
+    }
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                &Error::NoInputs => write!(f, "The transaction has no inputs"),
+                &Error::NoOutputs => write!(f, "The transaction has no outputs"),
+                &Error::DuplicateInput(ref txin) => write!(f, "The input {} is spent more than once", txin),
+                &Error::ZeroOutput(index) => write!(f, "The output at index {} has no value", index),
+                &Error::OutputTooSmall(index, value, min) => write!(f, "The output at index {} is {} but the minimal output value is {}", index, value, min),
+                &Error::ForeignAddress(ref addr) => write!(f, "Address {} does not belong to the network", addr),
+                &Error::ForeignChangeAddress(ref addr) => write!(f, "Change address {} does not belong to the network", addr),
+                &Error::OutputsOverflow(err) => write!(f, "Invalid sum of the outputs: {}", err),
+                &Error::TxTooLarge(size, max) => write!(f, "The signed transaction would be {} bytes but the maximum is {} bytes", size, max),
+            }
+        }
+    }
+
+    pub type Result<T> = result::Result<T, Error>;
+
+    /// the limits of the transactions
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
-    pub enum SelectionPolicy {
-        /// select the first inputs that matches, no optimisation
-        FirstMatchFirst
+    pub struct Parameters {
+        /// the maximum size of a signed transaction, in bytes
+        pub max_tx_size: usize,
+        /// the minimal value of an output, e.g. to refuse dust outputs
+        pub min_output_value: Coin,
     }
-    impl Default for SelectionPolicy {
-        fn default() -> Self { SelectionPolicy::FirstMatchFirst }
+    impl Default for Parameters {
+        fn default() -> Self {
+            Parameters { max_tx_size: MAX_TX_SIZE, min_output_value: Coin::new(1).unwrap() }
+        }
     }
+
+    /// check the outputs of a transaction to create and its change addresses
+    pub fn validate_outputs(config: &Config, params: &Parameters, outputs: &Outputs, change_addrs: &[ExtendedAddr]) -> Result<()> {
+        if outputs.is_empty() { return Err(Error::NoOutputs); }
+        for (index, output) in outputs.iter().enumerate() {
+            if ! output.address.is_for_network(config) {
+                return Err(Error::ForeignAddress(output.address.clone()));
+            }
+            if output.value == Coin::zero() {
+                return Err(Error::ZeroOutput(index));
+            }
+            if output.value < params.min_output_value {
+                return Err(Error::OutputTooSmall(index, output.value, params.min_output_value));
+            }
+        }
+        outputs.total().map_err(Error::OutputsOverflow)?;
+        for addr in change_addrs {
+            if ! addr.is_for_network(config) {
+                return Err(Error::ForeignChangeAddress(addr.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// check the transaction to sign: its inputs, its outputs and its size
+    /// once signed (with public key witnesses)
+    pub fn validate_tx(config: &Config, params: &Parameters, tx: &Tx) -> Result<()> {
+        if tx.inputs.is_empty() { return Err(Error::NoInputs); }
+        for (index, txin) in tx.inputs.iter().enumerate() {
+            if tx.inputs.iter().skip(index + 1).any(|other| other == txin) {
+                return Err(Error::DuplicateInput(txin.clone()));
+            }
+        }
+        validate_outputs(config, params, &tx.outputs.iter().cloned().collect(), &[])?;
+
+        let witnesses = tx.inputs.iter().map(|_| fee::dummy_witness()).collect();
+        let size = cbor::encode_to_cbor(&TxAux::new(tx.clone(), witnesses)).unwrap().len();
+        if size > params.max_tx_size {
+            return Err(Error::TxTooLarge(size, params.max_tx_size));
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use address::{AddrType, SpendingData, Attributes};
+        use config::{ProtocolMagic, RequiresNetworkMagic};
+        use hdwallet;
+
+        fn address(magic: Option<ProtocolMagic>) -> ExtendedAddr {
+            let xprv = hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([0;hdwallet::SEED_SIZE]));
+            ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(xprv.public()), Attributes::new_bootstrap_era(None).with_protocol_magic(magic))
+        }
+
+        fn tx(inputs: u32, values: &[u64]) -> Tx {
+            let addr = address(None);
+            Tx::new_with(
+                (0..inputs).map(|i| TxIn::new(TxId::new(&[i as u8, (i >> 8) as u8]), i)).collect(),
+                values.iter().map(|v| TxOut::new(addr.clone(), Coin::new(*v).unwrap())).collect()
+            )
+        }
+
+        #[test]
+        fn validate_transactions() {
+            let cfg = Config::default();
+            let params = Parameters::default();
+            assert_eq!(validate_tx(&cfg, &params, &tx(2, &[1, 1000])), Ok(()));
+            assert_eq!(validate_tx(&cfg, &params, &tx(0, &[1])), Err(Error::NoInputs));
+            assert_eq!(validate_tx(&cfg, &params, &tx(1, &[])), Err(Error::NoOutputs));
+            assert_eq!(validate_tx(&cfg, &params, &tx(1, &[5, 0])), Err(Error::ZeroOutput(1)));
+            assert_eq!(validate_tx(&cfg, &params, &tx(1, &[coin::MAX_COIN, 1])), Err(Error::OutputsOverflow(coin::Error::OutOfBound(coin::MAX_COIN + 1))));
+
+            let dust = Parameters { min_output_value: Coin::new(1000).unwrap(), .. params };
+            assert_eq!(validate_tx(&cfg, &dust, &tx(1, &[1000, 999])), Err(Error::OutputTooSmall(1, Coin::new(999).unwrap(), Coin::new(1000).unwrap())));
+
+            let mut duplicate = tx(2, &[1]);
+            let txin = duplicate.inputs.front().unwrap().clone();
+            duplicate.add_input(txin.clone());
+            assert_eq!(validate_tx(&cfg, &params, &duplicate), Err(Error::DuplicateInput(txin)));
+
+            // each input adds an input and a witness, 182 bytes: 22 inputs
+            // fit in the mainnet maximum (4034 bytes), 23 do not
+            assert_eq!(validate_tx(&cfg, &params, &tx(22, &[1])), Ok(()));
+            match validate_tx(&cfg, &params, &tx(23, &[1])) {
+                Err(Error::TxTooLarge(size, 4096)) => assert!(size > 4096 && size < 4096 + 200),
+                res => panic!("unexpected result {:?}", res),
+            }
+
+            let magic_cfg = Config::new_with_network_magic(ProtocolMagic::new(42), RequiresNetworkMagic::RequiresMagic);
+            let outputs : Outputs = tx(1, &[1]).outputs.iter().cloned().collect();
+            assert_eq!(validate_outputs(&magic_cfg, &params, &outputs, &[]), Err(Error::ForeignAddress(address(None))));
+            let local : Outputs = vec![TxOut::new(address(Some(ProtocolMagic::new(42))), Coin::new(1).unwrap())].into_iter().collect();
+            assert_eq!(validate_outputs(&magic_cfg, &params, &local, &[address(None)]), Err(Error::ForeignChangeAddress(address(None))));
+            assert_eq!(validate_outputs(&magic_cfg, &params, &local, &[address(Some(ProtocolMagic::new(42)))]), Ok(()));
+        }
+    }
+
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -568,6 +1273,29 @@ impl TxAux {
     pub fn new(tx: Tx, witnesses: Vec<TxInWitness>) -> Self {
         TxAux { tx: tx, witnesses: witnesses }
     }
+
+    /// the transaction redeeming all the funds (`value`) held by the redeem
+    /// address of `key` at `txin`, to the address `to`.
+    ///
+    /// As a redemption transaction does not pay any fee, the whole value is
+    /// sent to `to`.
+    pub fn new_redeem(cfg: &Config, key: &redeem::PrivateKey, txin: TxIn, value: Coin, to: ExtendedAddr) -> Self {
+        let mut tx = Tx::new();
+        tx.add_input(txin);
+        tx.add_output(TxOut::new(to, value));
+        let witness = TxInWitness::new_redeem(cfg, key, &tx);
+        TxAux::new(tx, vec![witness])
+    }
+
+    /// a redemption transaction only spends redeem addresses
+    pub fn is_redemption(&self) -> bool {
+        ! self.witnesses.is_empty() && self.witnesses.iter().all(|witness| {
+            match witness {
+                &TxInWitness::RedeemWitness(_, _) => true,
+                _ => false,
+            }
+        })
+    }
 }
 impl cbor::CborValue for TxAux {
     fn encode(&self) -> cbor::Value {
@@ -587,7 +1315,7 @@ impl cbor::CborValue for TxAux {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TxProof {
     pub number: u32,
     pub root: Blake2b256,
@@ -601,6 +1329,47 @@ impl TxProof {
             witnesses_hash: witnesses_hash
         }
     }
+
+    /// compute the proof of the given transactions, as found in a block's payload:
+    /// the number of transactions, the merkle root of the `Tx`s and the hash
+    /// of the list of witnesses.
+    pub fn generate<'a, I>(txauxs: I) -> Self
+        where I: IntoIterator<Item = &'a TxAux>
+    {
+        let mut txs = Vec::new();
+        let mut witnesses = LinkedList::new();
+        for txaux in txauxs {
+            txs.push(&txaux.tx);
+            witnesses.push_back(txaux.witnesses.clone());
+        }
+        let witnesses_hash = Blake2b256::new(&cbor::encode_to_cbor(&witnesses).unwrap());
+        TxProof::new(txs.len() as u32, merkle_root(&txs), witnesses_hash)
+    }
+}
+
+/// merkle root of the given transactions.
+///
+/// The leaves are the hash of `0x00 | cbor(tx)`, the nodes the hash of
+/// `0x01 | left | right` where the left sub-tree holds the largest power of
+/// two strictly smaller than the number of elements. The root of an empty
+/// tree is the hash of the cbor encoded empty bytes.
+fn merkle_root(txs: &[&Tx]) -> Blake2b256 {
+    match txs.len() {
+        0 => Blake2b256::new(&cbor::encode_to_cbor(&cbor::Bytes::new(Vec::new())).unwrap()),
+        1 => {
+            let mut buf = vec![0x00];
+            buf.extend_from_slice(&cbor::encode_to_cbor(txs[0]).unwrap());
+            Blake2b256::new(&buf)
+        },
+        n => {
+            let mut i = 1;
+            while i * 2 < n { i *= 2; }
+            let mut buf = vec![0x01];
+            buf.extend_from_slice(merkle_root(&txs[..i]).as_ref());
+            buf.extend_from_slice(merkle_root(&txs[i..]).as_ref());
+            Blake2b256::new(&buf)
+        }
+    }
 }
 impl fmt::Display for TxProof {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -788,6 +1557,60 @@ mod tests {
         assert!(txinwitness.verify(&cfg, &ea, &tx));
     }
 
+    #[test]
+    fn redeem_witness_sign_verify() {
+        let cfg = Config::default();
+        let key = redeem::PrivateKey::generate(&[0;32]);
+        let ea = address::ExtendedAddr::new_redeem(&key.public(), None);
+        assert_eq!(ea.addr_type, address::AddrType::ATRedeem);
+
+        let txin = TxIn::new(TxId::new(&ea.to_bytes()), 0);
+        let to = address::ExtendedAddr::new_redeem(&redeem::PrivateKey::generate(&[1;32]).public(), None);
+        let txaux = TxAux::new_redeem(&cfg, &key, txin, Coin::new(42).unwrap(), to);
+
+        assert!(txaux.is_redemption());
+        assert!(cbor::hs::encode_decode(&txaux.witnesses[0]));
+        assert!(txaux.witnesses[0].verify(&cfg, &ea, &txaux.tx));
+        assert!(! txaux.witnesses[0].verify(&Config::new(::config::ProtocolMagic::new(0)), &ea, &txaux.tx));
+        assert!(! txaux.witnesses[0].verify_address(&txaux.tx.outputs.front().unwrap().address));
+    }
+
+    #[test]
+    fn redeem_witness_signing_tag() {
+        let cfg = Config::default();
+        let key = redeem::PrivateKey::generate(&[0;32]);
+        let ea = address::ExtendedAddr::new_redeem(&key.public(), None);
+        let txin = TxIn::new(TxId::new(&ea.to_bytes()), 0);
+        let to = address::ExtendedAddr::new_redeem(&redeem::PrivateKey::generate(&[1;32]).public(), None);
+        let txaux = TxAux::new_redeem(&cfg, &key, txin, Coin::new(42).unwrap(), to);
+
+        // 0x02 (SignRedeemTx) || cbor(protocol magic) || cbor(txid)
+        let mut data = vec![0x02];
+        data.extend_from_slice(&cbor::encode_to_cbor(&cfg.protocol_magic).unwrap());
+        data.extend_from_slice(&cbor::encode_to_cbor(&txaux.tx.id()).unwrap());
+        assert_eq!(txaux.witnesses[0], TxInWitness::RedeemWitness(key.public(), key.sign(&data)));
+
+        // signed with the tag of the public key witnesses
+        data[0] = 0x01;
+        assert!(! TxInWitness::RedeemWitness(key.public(), key.sign(&data)).verify_tx(&cfg, &txaux.tx));
+    }
+
+    #[test]
+    fn script_witness() {
+        let validator = address::Script::new(0, vec![1,2,3,4]);
+        let redeemer = address::Script::new(0, vec![5,6,7]);
+
+        let sd = address::SpendingData::ScriptASD(validator.clone());
+        let attrs = address::Attributes::new_bootstrap_era(None);
+        let ea = address::ExtendedAddr::new(address::AddrType::ATScript, sd, attrs);
+        assert_eq!(address::ExtendedAddr::from_bytes(&ea.to_bytes()).unwrap(), ea);
+
+        let txinwitness = TxInWitness::ScriptWitness(validator, redeemer.clone());
+        assert!(cbor::hs::encode_decode(&txinwitness));
+        assert!(txinwitness.verify_address(&ea));
+        assert!(! TxInWitness::ScriptWitness(redeemer.clone(), redeemer).verify_address(&ea));
+    }
+
     #[test]
     fn txaux_decode() {
         let _txaux : TxAux = cbor::decode_from_cbor(TX_AUX).expect("to decode a TxAux");
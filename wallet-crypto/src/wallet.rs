@@ -9,19 +9,29 @@
 use hdwallet;
 use address;
 use tx;
+use coin;
+use partialtx;
 use config;
 use bip39;
 use bip44;
+use hdpayload;
+use hash;
+use cbor;
 use bip44::{Addressing, AddrType, BIP44_PURPOSE, BIP44_COIN_TYPE};
 use tx::fee::Algorithm;
 
 use std::{result, fmt};
+use rand;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum Error {
     FeeCalculationError(tx::fee::Error),
     AddressingError(bip44::Error),
-    WalletError(hdwallet::Error)
+    WalletError(hdwallet::Error),
+    ValidationError(tx::validation::Error),
+    PartialTxError(partialtx::Error),
+    /// no address to send the change of a transaction to
+    NoChangeAddress,
 }
 impl From<tx::fee::Error> for Error {
     fn from(j: tx::fee::Error) -> Self { Error::FeeCalculationError(j) }
@@ -32,6 +42,12 @@ impl From<hdwallet::Error> for Error {
 impl From<bip44::Error> for Error {
     fn from(e: bip44::Error) -> Self { Error::AddressingError(e) }
 }
+impl From<tx::validation::Error> for Error {
+    fn from(e: tx::validation::Error) -> Self { Error::ValidationError(e) }
+}
+impl From<partialtx::Error> for Error {
+    fn from(e: partialtx::Error) -> Self { Error::PartialTxError(e) }
+}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -43,6 +59,15 @@ impl fmt::Display for Error {
             },
             &Error::WalletError(err) => {
                 write!(f, "HD Wallet error: {}", err)
+            },
+            &Error::ValidationError(ref err) => {
+                write!(f, "Invalid transaction: {}", err)
+            },
+            &Error::PartialTxError(err) => {
+                write!(f, "Partial transaction error: {}", err)
+            },
+            &Error::NoChangeAddress => {
+                write!(f, "No change address for the transaction")
             }
         }
     }
@@ -50,6 +75,25 @@ impl fmt::Display for Error {
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// the default number of consecutive unused addresses after which the
+/// address discovery stops (see BIP44)
+pub const DEFAULT_GAP_LIMIT : u32 = 20;
+
+/// how the change of the wallet's transactions is sent back to the wallet
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ChangePolicy {
+    /// a change lower than this value (or than the minimal output value
+    /// of the transactions) is given to the fee rather than sent back
+    pub dust_threshold: coin::Coin,
+    /// the number of outputs the change is split into, to as many
+    /// addresses of the internal chain (when they are derived by the
+    /// wallet, see `Wallet::new_transaction_with_change`)
+    pub outputs: u32,
+}
+impl Default for ChangePolicy {
+    fn default() -> Self { ChangePolicy { dust_threshold: coin::Coin::zero(), outputs: 1 } }
+}
+
 /// the Wallet object
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Wallet {
@@ -57,11 +101,41 @@ pub struct Wallet {
 
     pub config: config::Config,
     pub selection_policy: tx::fee::SelectionPolicy,
+    /// the derivation scheme of the whole BIP44 path: the scheme the
+    /// `cached_root_key` has been derived with
+    #[serde(default)]
+    pub derivation_scheme: hdwallet::DerivationScheme,
+    /// the fee policy of the wallet's network
+    #[serde(default)]
+    pub fee_policy: tx::fee::LinearFee,
+    /// the limits of the wallet's transactions, checked before signing
+    #[serde(default)]
+    pub validation_parameters: tx::validation::Parameters,
+    #[serde(default)]
+    pub change_policy: ChangePolicy,
 }
 
 impl Wallet {
-    pub fn new(cached_root_key: hdwallet::XPrv, config: config::Config, policy: tx::fee::SelectionPolicy) -> Self {
-        Wallet { cached_root_key: cached_root_key, config: config, selection_policy: policy }
+    pub fn new(cached_root_key: hdwallet::XPrv, config: config::Config, policy: tx::fee::SelectionPolicy, derivation_scheme: hdwallet::DerivationScheme) -> Self {
+        Wallet { cached_root_key: cached_root_key, config: config, selection_policy: policy, derivation_scheme: derivation_scheme, fee_policy: tx::fee::LinearFee::default(), validation_parameters: tx::validation::Parameters::default(), change_policy: ChangePolicy::default() }
+    }
+
+    /// set the fee policy of the wallet's network (the mainnet's by default)
+    pub fn with_fee_policy(mut self, fee_policy: tx::fee::LinearFee) -> Self {
+        self.fee_policy = fee_policy;
+        self
+    }
+
+    /// set the limits of the wallet's transactions (the mainnet's by default)
+    pub fn with_validation_parameters(mut self, validation_parameters: tx::validation::Parameters) -> Self {
+        self.validation_parameters = validation_parameters;
+        self
+    }
+
+    /// set how the change of the wallet's transactions is sent back
+    pub fn with_change_policy(mut self, change_policy: ChangePolicy) -> Self {
+        self.change_policy = change_policy;
+        self
     }
 
     /// create a new wallet from the given seed
@@ -70,10 +144,20 @@ impl Wallet {
     }
 
     pub fn new_from_root_xprv(key: hdwallet::XPrv) -> Self {
+        Self::new_from_root_xprv_with_derivation_scheme(key, hdwallet::DerivationScheme::V2)
+    }
+
+    /// create a new wallet from the given root key, deriving the purpose
+    /// and coin type of the BIP44 path with the given derivation scheme
+    pub fn new_from_root_xprv_with_derivation_scheme(key: hdwallet::XPrv, derivation_scheme: hdwallet::DerivationScheme) -> Self {
         Wallet {
-            cached_root_key: key.derive(BIP44_PURPOSE).derive(BIP44_COIN_TYPE),
+            cached_root_key: key.derive(derivation_scheme, BIP44_PURPOSE).derive(derivation_scheme, BIP44_COIN_TYPE),
             config: config::Config::default(),
-            selection_policy: tx::fee::SelectionPolicy::default()
+            selection_policy: tx::fee::SelectionPolicy::default(),
+            derivation_scheme: derivation_scheme,
+            fee_policy: tx::fee::LinearFee::default(),
+            validation_parameters: tx::validation::Parameters::default(),
+            change_policy: ChangePolicy::default(),
         }
     }
 
@@ -84,16 +168,70 @@ impl Wallet {
 
     pub fn account(&self, account_index: u32) -> Result<Account> {
         let account = bip44::Account::new(account_index)?;
-        let account_key = self.get_root_key().derive(account.index()).public();
+        let account_key = self.get_root_key().derive(self.derivation_scheme, account.index()).public();
 
-        Ok(Account::new(account, account_key))
+        Ok(Account::new(account, account_key).with_derivation_scheme(self.derivation_scheme))
     }
 
     /// create an extended address from the given addressing
     ///
+    /// the addresses are tagged with the protocol magic of the wallet's
+    /// network if it requires it.
+    ///
     pub fn gen_addresses(&self, account: u32, addr_type: AddrType, indices: Vec<u32>) -> Result<Vec<address::ExtendedAddr>>
     {
-        self.account(account)?.gen_addresses(addr_type, indices)
+        self.account(account)?.gen_network_addresses(&self.config, addr_type, indices)
+    }
+
+    /// discover the used accounts of the wallet and their used addresses
+    /// (see `Account::discover_addresses`).
+    ///
+    /// the accounts are scanned in order, the discovery stops at the first
+    /// account without any used external address (as specified by BIP44).
+    ///
+    pub fn discover_accounts<F>(&self, gap_limit: u32, is_used: &mut F) -> Result<Vec<(Account, Vec<(Addressing, address::ExtendedAddr)>)>>
+        where F: FnMut(&address::ExtendedAddr) -> bool
+    {
+        let mut res = vec![];
+        for account_index in 0..0x80000000 {
+            let account = self.account(account_index)?;
+            let addresses = account.discover_addresses(&self.config, gap_limit, is_used)?;
+            if ! addresses.iter().any(|&(ref addressing, _)| addressing.address_type() == AddrType::External) {
+                break;
+            }
+            res.push((account, addresses));
+        }
+        Ok(res)
+    }
+
+    /// the next unused addresses of the internal chain of the given
+    /// account, to send the change of a transaction to (as many as the
+    /// change policy's number of outputs).
+    ///
+    pub fn change_addresses<F>(&self, account: u32, is_used: &mut F) -> Result<Vec<(Addressing, address::ExtendedAddr)>>
+        where F: FnMut(&address::ExtendedAddr) -> bool
+    {
+        let count = ::std::cmp::max(self.change_policy.outputs, 1);
+        self.account(account)?.next_unused_addresses(&self.config, AddrType::Internal, count, is_used)
+    }
+
+    /// same as `new_transaction` but the change is sent to the next
+    /// unused addresses of the internal chain of the given account (see
+    /// `change_addresses`), split as set by the change policy.
+    ///
+    pub fn new_transaction_with_change<F>( &self
+                                         , account: u32
+                                         , inputs: &tx::Inputs
+                                         , outputs: &tx::Outputs
+                                         , is_used: &mut F
+                                         )
+        -> Result<(tx::TxAux, tx::fee::Fee)>
+        where F: FnMut(&address::ExtendedAddr) -> bool
+    {
+        let change_addrs : Vec<_> = self.change_addresses(account, is_used)?.into_iter().map(|(_, addr)| addr).collect();
+        let (mut ptx, fee) = new_partial_transaction(&self.config, &self.validation_parameters, &self.fee_policy, self.selection_policy, &self.change_policy, inputs, outputs, &change_addrs)?;
+        self.sign_partial_transaction(&mut ptx, inputs)?;
+        Ok((ptx.finalize()?, fee))
     }
 
     /// function to create a ready to send transaction to the network
@@ -101,6 +239,9 @@ impl Wallet {
     /// it select the needed inputs, compute the fee and possible change
     /// signes every TxIn as needed.
     ///
+    /// the outputs and the change address must belong to the wallet's
+    /// network.
+    ///
     pub fn new_transaction( &self
                           , inputs: &tx::Inputs
                           , outputs: &tx::Outputs
@@ -108,27 +249,47 @@ impl Wallet {
                           )
         -> Result<(tx::TxAux, tx::fee::Fee)>
     {
-        let alg = tx::fee::LinearFee::default();
-
-        let (fee, selected_inputs, change) = alg.compute(self.selection_policy, inputs, outputs, change_addr)?;
-
-        let mut tx = tx::Tx::new_with(
-            selected_inputs.iter().cloned().map(|input| input.ptr).collect(),
-            outputs.iter().cloned().collect()
-        );
-
-        tx.add_output(tx::TxOut::new(change_addr.clone(), change));
-
-        let mut witnesses = vec![];
+        let (mut ptx, fee) = self.new_partial_transaction(inputs, outputs, change_addr)?;
+        self.sign_partial_transaction(&mut ptx, inputs)?;
+        Ok((ptx.finalize()?, fee))
+    }
 
-        for input in selected_inputs {
-            let key  = self.get_xprv(&input.addressing);
+    /// same as `new_transaction` but nothing is signed: the returned
+    /// partial transaction is to be signed by the owners of the selected
+    /// inputs (see `sign_partial_transaction`).
+    ///
+    /// the inputs may belong to different wallets, their `addressing`
+    /// is not used here.
+    ///
+    pub fn new_partial_transaction( &self
+                                  , inputs: &tx::Inputs
+                                  , outputs: &tx::Outputs
+                                  , change_addr: &address::ExtendedAddr
+                                  )
+        -> Result<(partialtx::PartialTx, tx::fee::Fee)>
+    {
+        new_partial_transaction(&self.config, &self.validation_parameters, &self.fee_policy, self.selection_policy, &self.change_policy, inputs, outputs, &[change_addr.clone()])
+    }
 
-            let txwitness = tx::TxInWitness::new(&self.config, &key, &tx);
-            witnesses.push(txwitness);
+    /// sign the inputs of the partial transaction owned by this wallet,
+    /// i.e. the unsigned inputs found in `inputs`.
+    ///
+    /// returns the number of inputs signed.
+    ///
+    pub fn sign_partial_transaction(&self, ptx: &mut partialtx::PartialTx, inputs: &tx::Inputs) -> Result<usize> {
+        let mut to_sign = vec![];
+        for (index, txin) in ptx.tx().inputs.iter().enumerate() {
+            if ptx.witnesses()[index].is_some() { continue; }
+            let spent = &ptx.spent_outputs()[index];
+            if let Some(input) = inputs.iter().find(|input| &input.ptr == txin && &input.value == spent) {
+                to_sign.push((index, self.get_xprv(&input.addressing)));
+            }
         }
 
-        Ok((tx::TxAux::new(tx, witnesses), fee))
+        for &(index, ref key) in to_sign.iter() {
+            ptx.sign(&self.config, index, key)?;
+        }
+        Ok(to_sign.len())
     }
 
     pub fn verify_transaction(&self, inputs: &tx::Inputs, txaux: &tx::TxAux) -> bool {
@@ -158,10 +319,7 @@ impl Wallet {
     ///
     /// TODO: this function is not meant to be public
     fn get_xprv(&self, addressing: &Addressing) -> hdwallet::XPrv {
-        self.get_root_key()
-            .derive(addressing.account.index())
-            .derive(addressing.change)
-            .derive(addressing.index)
+        addressing.derive_xprv(self.get_root_key(), self.derivation_scheme)
     }
 }
 
@@ -171,37 +329,327 @@ impl Wallet {
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Account {
     pub account: bip44::Account,
-    pub cached_account_key: hdwallet::XPub
+    pub cached_account_key: hdwallet::XPub,
+    #[serde(default)]
+    pub derivation_scheme: hdwallet::DerivationScheme,
 }
 impl Account {
-    pub fn new(account: bip44::Account, xpub: hdwallet::XPub) -> Self { Account { account: account, cached_account_key: xpub } }
+    pub fn new(account: bip44::Account, xpub: hdwallet::XPub) -> Self {
+        Account { account: account, cached_account_key: xpub, derivation_scheme: hdwallet::DerivationScheme::default() }
+    }
+
+    /// set the derivation scheme of the wallet's keys (`V2` by default)
+    pub fn with_derivation_scheme(mut self, derivation_scheme: hdwallet::DerivationScheme) -> Self {
+        self.derivation_scheme = derivation_scheme;
+        self
+    }
 
     /// create an extended address from the given addressing
     ///
+    /// the addresses are not tagged with any protocol magic, see
+    /// `gen_network_addresses` for the networks requiring it.
+    ///
     pub fn gen_addresses(&self, addr_type: AddrType, indices: Vec<u32>) -> Result<Vec<address::ExtendedAddr>>
+    {
+        self.gen_addresses_with_magic(None, addr_type, indices)
+    }
+
+    /// create an extended address from the given addressing, for the
+    /// network of the given configuration
+    ///
+    pub fn gen_network_addresses(&self, config: &config::Config, addr_type: AddrType, indices: Vec<u32>) -> Result<Vec<address::ExtendedAddr>>
+    {
+        self.gen_addresses_with_magic(config.address_protocol_magic(), addr_type, indices)
+    }
+
+    /// discover the used addresses of the account, on the external then
+    /// on the internal chain.
+    ///
+    /// the addresses are generated (for the network of the given
+    /// configuration) by chunks of `gap_limit` addresses and checked with
+    /// `is_used`; a chain is scanned until `gap_limit` consecutive unused
+    /// addresses are found.
+    ///
+    pub fn discover_addresses<F>(&self, config: &config::Config, gap_limit: u32, is_used: &mut F) -> Result<Vec<(Addressing, address::ExtendedAddr)>>
+        where F: FnMut(&address::ExtendedAddr) -> bool
+    {
+        let mut res = vec![];
+        for addr_type in [AddrType::External, AddrType::Internal].iter() {
+            let mut start = self.account.change(*addr_type)?.index(0)?;
+            let mut gap = 0;
+            while gap < gap_limit {
+                let chunk = start.next_chunks(gap_limit as usize)?;
+                let last = match chunk.last() {
+                    None => break,
+                    Some(addressing) => *addressing,
+                };
+                let indices = chunk.iter().map(|addressing| addressing.index).collect();
+                let addresses = self.gen_network_addresses(config, *addr_type, indices)?;
+                for (addressing, addr) in chunk.into_iter().zip(addresses.into_iter()) {
+                    if gap >= gap_limit { break; }
+                    if is_used(&addr) {
+                        res.push((addressing, addr));
+                        gap = 0;
+                    } else {
+                        gap += 1;
+                    }
+                }
+                start = match last.incr(1) {
+                    Err(bip44::Error::IndexOutOfBound(_)) => break,
+                    Err(err) => return Err(Error::from(err)),
+                    Ok(next) => next,
+                };
+            }
+        }
+        Ok(res)
+    }
+
+    /// the first `count` unused addresses of the given chain of the
+    /// account (for the network of the given configuration), in the
+    /// order of their indices.
+    ///
+    pub fn next_unused_addresses<F>(&self, config: &config::Config, addr_type: AddrType, count: u32, is_used: &mut F) -> Result<Vec<(Addressing, address::ExtendedAddr)>>
+        where F: FnMut(&address::ExtendedAddr) -> bool
+    {
+        let mut res = vec![];
+        let mut start = self.account.change(addr_type)?.index(0)?;
+        while res.len() < count as usize {
+            let chunk = start.next_chunks(count as usize)?;
+            let last = match chunk.last() {
+                None => break,
+                Some(addressing) => *addressing,
+            };
+            let indices = chunk.iter().map(|addressing| addressing.index).collect();
+            let addresses = self.gen_network_addresses(config, addr_type, indices)?;
+            for (addressing, addr) in chunk.into_iter().zip(addresses.into_iter()) {
+                if res.len() < count as usize && ! is_used(&addr) {
+                    res.push((addressing, addr));
+                }
+            }
+            start = last.incr(1)?;
+        }
+        Ok(res)
+    }
+
+    fn gen_addresses_with_magic(&self, protocol_magic: Option<config::ProtocolMagic>, addr_type: AddrType, indices: Vec<u32>) -> Result<Vec<address::ExtendedAddr>>
     {
         let addressing = self.account.change(addr_type)?.index(0)?;
 
         let change_prv = self.cached_account_key
-            .derive(addressing.change)?;
+            .derive(self.derivation_scheme, addressing.change)?;
 
         let mut res = vec![];
         for index in indices {
-            let pk = change_prv.derive(index)?;
+            let pk = change_prv.derive(self.derivation_scheme, index)?;
             let addr_type = address::AddrType::ATPubKey;
             let sd = address::SpendingData::PubKeyASD(pk);
-            let attrs = address::Attributes::new_bootstrap_era(None);
+            let attrs = address::Attributes::new_bootstrap_era(None).with_protocol_magic(protocol_magic);
             res.push(address::ExtendedAddr::new(addr_type, sd, attrs));
         }
         Ok(res)
     }
 }
 
+/// select the inputs, compute the fee and the change of the transaction
+/// (see `Wallet::new_partial_transaction`)
+///
+/// the change is split between the change addresses as set by the
+/// change policy, there must be at least one.
+fn new_partial_transaction<A: Algorithm>( config: &config::Config
+                                        , params: &tx::validation::Parameters
+                                        , alg: &A
+                                        , policy: tx::fee::SelectionPolicy
+                                        , change_policy: &ChangePolicy
+                                        , inputs: &tx::Inputs
+                                        , outputs: &tx::Outputs
+                                        , change_addrs: &[address::ExtendedAddr]
+                                        )
+    -> Result<(partialtx::PartialTx, tx::fee::Fee)>
+{
+    tx::validation::validate_outputs(config, params, outputs, change_addrs)?;
+
+    let change_addr = change_addrs.first().ok_or(Error::NoChangeAddress)?;
+    let (mut fee, selected_inputs, change) = alg.compute(policy, inputs, outputs, change_addr)?;
+
+    let mut tx = tx::Tx::new_with(
+        selected_inputs.iter().cloned().map(|input| input.ptr).collect(),
+        outputs.iter().cloned().collect()
+    );
+
+    // a change lower than the dust threshold or than the minimal output
+    // value is given to the fee
+    if change < change_policy.dust_threshold || change < params.min_output_value {
+        fee = tx::fee::Fee::new((fee.to_coin() + change).map_err(tx::validation::Error::OutputsOverflow)?);
+    } else {
+        let split_addrs = &change_addrs[..::std::cmp::min(change_addrs.len(), ::std::cmp::max(change_policy.outputs, 1) as usize)];
+        let (split_fee, change_outputs) = alg.split_change(selected_inputs.as_slice(), outputs, fee, change, split_addrs, params.min_output_value)?;
+        fee = split_fee;
+        for output in change_outputs {
+            tx.add_output(output);
+        }
+    }
+
+    tx::validation::validate_tx(config, params, &tx)?;
+
+    let spent = selected_inputs.into_iter().map(|input| input.value).collect();
+
+    Ok((partialtx::PartialTx::new(tx, spent)?, fee))
+}
+
+/// Legacy (Daedalus) wallet with random derivation indices.
+///
+/// The addresses are derived from the root key along a path of random
+/// hardened indices (the account and the address indices), the path is
+/// stored in the address' `HDAddressPayload`, encrypted with the wallet's
+/// `HDKey`. The keys are derived with the `V1` derivation scheme.
+///
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RandomIndexWallet {
+    pub root_key: hdwallet::XPrv,
+
+    pub config: config::Config,
+    pub selection_policy: tx::fee::SelectionPolicy,
+    /// the fee policy of the wallet's network
+    #[serde(default)]
+    pub fee_policy: tx::fee::LinearFee,
+    /// the limits of the wallet's transactions, checked before signing
+    #[serde(default)]
+    pub validation_parameters: tx::validation::Parameters,
+}
+impl RandomIndexWallet {
+    pub fn new(root_key: hdwallet::XPrv, config: config::Config, policy: tx::fee::SelectionPolicy) -> Self {
+        RandomIndexWallet { root_key: root_key, config: config, selection_policy: policy, fee_policy: tx::fee::LinearFee::default(), validation_parameters: tx::validation::Parameters::default() }
+    }
+
+    /// set the fee policy of the wallet's network (the mainnet's by default)
+    pub fn with_fee_policy(mut self, fee_policy: tx::fee::LinearFee) -> Self {
+        self.fee_policy = fee_policy;
+        self
+    }
+
+    /// set the limits of the wallet's transactions (the mainnet's by default)
+    pub fn with_validation_parameters(mut self, validation_parameters: tx::validation::Parameters) -> Self {
+        self.validation_parameters = validation_parameters;
+        self
+    }
+
+    /// create a new wallet from the given seed, as Daedalus does: the root
+    /// key is generated from the CBOR encoding of the seed
+    pub fn new_from_seed(seed: &hdwallet::Seed) -> Self {
+        let seed = cbor::encode_to_cbor(&cbor::Bytes::from_slice(seed.as_ref())).unwrap();
+        Self::new(hdwallet::XPrv::generate_from_daedalus_seed(&seed), config::Config::default(), tx::fee::SelectionPolicy::default())
+    }
+
+    /// create a new wallet from the entropy of its mnemonics, as Daedalus
+    /// does: the seed is the Blake2b-256 hash of the CBOR encoded entropy
+    pub fn new_from_bip39_entropy(entropy: &bip39::Entropy) -> Self {
+        let entropy = cbor::encode_to_cbor(&cbor::Bytes::from_slice(entropy.as_ref())).unwrap();
+        let seed = hash::Blake2b256::new(&entropy);
+        Self::new_from_seed(&hdwallet::Seed::from_slice(seed.as_ref()).unwrap())
+    }
+
+    /// the key encrypting the derivation paths in the wallet's addresses
+    pub fn hdkey(&self) -> hdpayload::HDKey {
+        hdpayload::HDKey::new(&self.root_key.public())
+    }
+
+    /// derive the private key of the given path
+    pub fn get_xprv(&self, path: &hdpayload::Path) -> hdwallet::XPrv {
+        path.as_ref().iter().fold(self.root_key.clone(), |key, index| {
+            key.derive(RANDOM_INDEX_DERIVATION_SCHEME, *index)
+        })
+    }
+
+    /// create the address of the given path
+    ///
+    /// the address is tagged with the protocol magic of the wallet's
+    /// network if it requires it.
+    ///
+    pub fn gen_address(&self, path: &hdpayload::Path) -> address::ExtendedAddr {
+        let attrs = address::Attributes::new_bootstrap_era(Some(self.hdkey().encrypt_path(path)))
+                        .with_protocol_magic(self.config.address_protocol_magic());
+        self.gen_address_with(path, attrs)
+    }
+
+    /// create a new address in the given account, at a random index
+    pub fn new_address(&self, account: u32) -> (hdpayload::Path, address::ExtendedAddr) {
+        let path = hdpayload::Path::new(vec![account | 0x80000000, rand::random::<u32>() | 0x80000000]);
+        let addr = self.gen_address(&path);
+        (path, addr)
+    }
+
+    /// the derivation path of the given address if it belongs to the
+    /// wallet
+    pub fn recognize(&self, addr: &address::ExtendedAddr) -> Option<hdpayload::Path> {
+        self.recognize_with(&self.hdkey(), addr)
+    }
+
+    /// create a transaction spending the given inputs of the wallet (their
+    /// `addressing` is not used, the keys are found from the addresses)
+    ///
+    /// the outputs and the change address must belong to the wallet's
+    /// network.
+    ///
+    pub fn new_transaction( &self
+                          , inputs: &tx::Inputs
+                          , outputs: &tx::Outputs
+                          , change_addr: &address::ExtendedAddr
+                          )
+        -> Result<(tx::TxAux, tx::fee::Fee)>
+    {
+        let (mut ptx, fee) = new_partial_transaction(&self.config, &self.validation_parameters, &self.fee_policy, self.selection_policy, &ChangePolicy::default(), inputs, outputs, &[change_addr.clone()])?;
+        self.sign_partial_transaction(&mut ptx)?;
+        Ok((ptx.finalize()?, fee))
+    }
+
+    /// sign the inputs of the partial transaction spending addresses of
+    /// the wallet.
+    ///
+    /// returns the number of inputs signed.
+    ///
+    pub fn sign_partial_transaction(&self, ptx: &mut partialtx::PartialTx) -> Result<usize> {
+        let hdkey = self.hdkey();
+        let mut to_sign = vec![];
+        for (index, spent) in ptx.spent_outputs().iter().enumerate() {
+            if ptx.witnesses()[index].is_some() { continue; }
+            if let Some(path) = self.recognize_with(&hdkey, &spent.address) {
+                to_sign.push((index, self.get_xprv(&path)));
+            }
+        }
+
+        for &(index, ref key) in to_sign.iter() {
+            ptx.sign(&self.config, index, key)?;
+        }
+        Ok(to_sign.len())
+    }
+
+    fn gen_address_with(&self, path: &hdpayload::Path, attrs: address::Attributes) -> address::ExtendedAddr {
+        let pk = self.get_xprv(path).public();
+        address::ExtendedAddr::new(address::AddrType::ATPubKey, address::SpendingData::PubKeyASD(pk), attrs)
+    }
+
+    fn recognize_with(&self, hdkey: &hdpayload::HDKey, addr: &address::ExtendedAddr) -> Option<hdpayload::Path> {
+        let path = match &addr.attributes.derivation_path {
+            &Some(ref payload) => hdkey.decrypt_path(payload)?,
+            &None              => return None,
+        };
+        // the payload only says the address claims to be ours
+        if addr.addr_type == address::AddrType::ATPubKey && &self.gen_address_with(&path, addr.attributes.clone()) == addr {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
+/// the random index wallets were created before the V2 derivation scheme
+const RANDOM_INDEX_DERIVATION_SCHEME : hdwallet::DerivationScheme = hdwallet::DerivationScheme::V1;
+
 #[cfg(test)]
 mod test {
     use super::*;
     use address::ExtendedAddr;
-    use cbor;
+    use util::hex;
     use tx;
     use coin;
     use serde_json;
@@ -266,10 +714,224 @@ mod test {
 
         let bytes = cbor::encode_to_cbor(&aux).unwrap();
 
-        let expected = coin::Coin::new(bytes.len() as u64 * 44 + 155381).unwrap();
+        // 155381 + 43.946 * size, rounded up
+        let expected = coin::Coin::new((bytes.len() as u64 * 43946 + 155381000 + 999) / 1000).unwrap();
 
         println!("computed fee: {:?}", fee.to_coin());
         println!("expected fee: {:?}", expected);
         assert!(fee.to_coin() >= expected);
     }
+
+    #[test]
+    fn discover_used_addresses() {
+        let wallet : Wallet = serde_json::from_str(WALLET_JSON).unwrap();
+        let account0 = wallet.account(0).unwrap();
+        let account1 = wallet.account(1).unwrap();
+        let mut used = vec![];
+        used.extend(account0.gen_network_addresses(&wallet.config, AddrType::External, vec![0, 5, 24]).unwrap());
+        // too far from the previous used address
+        used.extend(account0.gen_network_addresses(&wallet.config, AddrType::External, vec![45]).unwrap());
+        used.extend(account0.gen_network_addresses(&wallet.config, AddrType::Internal, vec![3]).unwrap());
+        used.extend(account1.gen_network_addresses(&wallet.config, AddrType::External, vec![19]).unwrap());
+
+        let mut is_used = |addr: &ExtendedAddr| used.contains(addr);
+        let found = wallet.discover_accounts(DEFAULT_GAP_LIMIT, &mut is_used).unwrap();
+        assert_eq!(found.len(), 2);
+        let indices : Vec<(AddrType, u32)> = found[0].1.iter().map(|&(ref a, _)| (a.address_type(), a.index)).collect();
+        assert_eq!(indices, vec![(AddrType::External, 0), (AddrType::External, 5), (AddrType::External, 24), (AddrType::Internal, 3)]);
+        assert_eq!(found[1].1.len(), 1);
+        assert_eq!(found[1].1[0].0.index, 19);
+    }
+
+    #[test]
+    fn fee_policy_transaction() {
+        let fee_policy = tx::fee::LinearFee::new(tx::fee::Coeff::new(200000, 0).unwrap(), tx::fee::Coeff::new(50, 500_000_000).unwrap());
+        let wallet = serde_json::from_str::<Wallet>(WALLET_JSON).unwrap().with_fee_policy(fee_policy);
+        let inputs : tx::Inputs = serde_json::from_str(INPUTS_JSON).unwrap();
+        let outputs : tx::Outputs = serde_json::from_str(OUTPUTS_JSON).unwrap();
+        let change_addr : ExtendedAddr = serde_json::from_str(CHANGE_ADDR_JSON).unwrap();
+
+        let (aux, fee) = wallet.new_transaction(&inputs, &outputs, &change_addr).unwrap();
+        let size = cbor::encode_to_cbor(&aux).unwrap().len();
+        assert!(fee.to_coin() >= fee_policy.estimate(size).unwrap().to_coin());
+        assert!(fee.to_coin() > tx::fee::LinearFee::default().estimate(size).unwrap().to_coin());
+    }
+
+    #[test]
+    fn change_to_internal_addresses() {
+        let wallet = Wallet::new_from_seed(&hdwallet::Seed::from_bytes([7;32]))
+                        .with_change_policy(ChangePolicy { dust_threshold: coin::Coin::zero(), outputs: 2 });
+        let addressing = bip44::Account::new(0).unwrap().external().unwrap().index(0).unwrap();
+        let addr = wallet.gen_addresses(0, AddrType::External, vec![0]).unwrap().remove(0);
+        let internal = wallet.gen_addresses(0, AddrType::Internal, vec![0, 1, 2]).unwrap();
+        let mut inputs = tx::Inputs::new();
+        inputs.push(tx::Input::new(tx::TxIn::new(tx::TxId::new(&[1]), 0), tx::TxOut::new(addr.clone(), coin::Coin::new(10000000).unwrap()), addressing));
+        let mut outputs = tx::Outputs::new();
+        outputs.push(tx::TxOut::new(addr.clone(), coin::Coin::new(1000000).unwrap()));
+
+        // the first internal address is already used
+        let mut is_used = |a: &ExtendedAddr| a == &internal[0];
+        let (aux, fee) = wallet.new_transaction_with_change(0, &inputs, &outputs, &mut is_used).unwrap();
+        let change : Vec<_> = aux.tx.outputs.iter().skip(1).collect();
+        assert_eq!(change.len(), 2);
+        assert_eq!(change[0].address, internal[1]);
+        assert_eq!(change[1].address, internal[2]);
+        let total = aux.tx.outputs.iter().fold(fee.to_coin(), |acc, output| (acc + output.value).unwrap());
+        assert_eq!(total, coin::Coin::new(10000000).unwrap());
+        assert!(wallet.verify_transaction(&inputs, &aux));
+
+        // the dust change is given to the fee
+        let wallet = wallet.with_change_policy(ChangePolicy { dust_threshold: coin::Coin::new(10000000).unwrap(), outputs: 2 });
+        let (aux, fee) = wallet.new_transaction_with_change(0, &inputs, &outputs, &mut is_used).unwrap();
+        assert_eq!(aux.tx.outputs.len(), 1);
+        assert_eq!(fee.to_coin(), coin::Coin::new(9000000).unwrap());
+
+        // no change address at all
+        let res = new_partial_transaction(&wallet.config, &wallet.validation_parameters, &wallet.fee_policy, wallet.selection_policy, &wallet.change_policy, &inputs, &outputs, &[]);
+        assert_eq!(res.err(), Some(Error::NoChangeAddress));
+    }
+
+    #[test]
+    fn co_sign_transaction() {
+        let wallet1 : Wallet = serde_json::from_str(WALLET_JSON).unwrap();
+        let mut wallet2 = Wallet::new_from_seed(&hdwallet::Seed::from_bytes([7;32]));
+        wallet2.config = wallet1.config.clone();
+        let inputs1 : tx::Inputs = serde_json::from_str(INPUTS_JSON).unwrap();
+        let change_addr : ExtendedAddr = serde_json::from_str(CHANGE_ADDR_JSON).unwrap();
+
+        let addressing = bip44::Account::new(0).unwrap().external().unwrap().index(0).unwrap();
+        let addr2 = wallet2.gen_addresses(0, AddrType::External, vec![0]).unwrap().remove(0);
+        let mut inputs2 = tx::Inputs::new();
+        inputs2.push(tx::Input::new(tx::TxIn::new(tx::TxId::new(&[42]), 0), tx::TxOut::new(addr2, coin::Coin::new(1000000).unwrap()), addressing));
+
+        // both inputs are needed
+        let mut outputs = tx::Outputs::new();
+        outputs.push(tx::TxOut::new(change_addr.clone(), coin::Coin::new(1500000).unwrap()));
+        let mut inputs = inputs1.clone();
+        inputs.append(&mut inputs2.clone());
+
+        let (mut ptx, _) = wallet1.new_partial_transaction(&inputs, &outputs, &change_addr).unwrap();
+        assert_eq!(ptx.missing(), vec![0, 1]);
+        assert_eq!(wallet1.sign_partial_transaction(&mut ptx, &inputs1).unwrap(), 1);
+        assert_eq!(wallet2.sign_partial_transaction(&mut ptx, &inputs2).unwrap(), 1);
+
+        let aux = ptx.finalize().unwrap();
+        assert!(wallet1.verify_transaction(&inputs, &aux));
+    }
+
+    #[test]
+    fn derivation_scheme_addresses() {
+        let mut wallet : Wallet = serde_json::from_str(WALLET_JSON).unwrap();
+        let inputs : tx::Inputs = serde_json::from_str(INPUTS_JSON).unwrap();
+        let outputs : tx::Outputs = serde_json::from_str(OUTPUTS_JSON).unwrap();
+        let change_addr : ExtendedAddr = serde_json::from_str(CHANGE_ADDR_JSON).unwrap();
+        assert_eq!(wallet.derivation_scheme, hdwallet::DerivationScheme::V2);
+        assert_eq!(wallet.gen_addresses(0, AddrType::External, vec![0]).unwrap(), vec![change_addr.clone()]);
+
+        wallet.derivation_scheme = hdwallet::DerivationScheme::V1;
+        assert!(wallet.gen_addresses(0, AddrType::External, vec![0]).unwrap() != vec![change_addr.clone()]);
+
+        // the V1 keys cannot spend the V2 addresses
+        match wallet.new_transaction(&inputs, &outputs, &change_addr) {
+            Err(Error::PartialTxError(partialtx::Error::InvalidWitness(0))) => {},
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn derivation_scheme_root_key() {
+        let root = hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_bytes([5;32]));
+        let v1 = Wallet::new_from_root_xprv_with_derivation_scheme(root.clone(), hdwallet::DerivationScheme::V1);
+        let v2 = Wallet::new_from_root_xprv(root.clone());
+        assert_eq!(v1.derivation_scheme, hdwallet::DerivationScheme::V1);
+        assert_eq!(v2.derivation_scheme, hdwallet::DerivationScheme::V2);
+
+        // the purpose and the coin type are derived with the wallet's scheme too
+        let v1_coin_type = root.derive(hdwallet::DerivationScheme::V1, BIP44_PURPOSE).derive(hdwallet::DerivationScheme::V1, BIP44_COIN_TYPE);
+        assert_eq!(v1.cached_root_key, v1_coin_type);
+        assert!(v1.cached_root_key != v2.cached_root_key);
+        assert!(v1.gen_addresses(0, AddrType::External, vec![0]).unwrap() != v2.gen_addresses(0, AddrType::External, vec![0]).unwrap());
+    }
+
+    #[test]
+    fn random_index_wallet_root_key() {
+        let entropy = bip39::Entropy::from_slice(&[0;16]).unwrap();
+        let wallet = RandomIndexWallet::new_from_bip39_entropy(&entropy);
+
+        // cbor(blake2b_256(cbor(entropy)))
+        let seed = hex::decode("5820dfee64f10fd452c2882951ef64eeb43880aa4304fd11110a2f1b13913f258a9d").unwrap();
+        assert_eq!(wallet.root_key, hdwallet::XPrv::generate_from_daedalus_seed(&seed));
+        assert_eq!(wallet, RandomIndexWallet::new_from_seed(&hdwallet::Seed::from_slice(&seed[2..]).unwrap()));
+        assert!(wallet.root_key != hdwallet::XPrv::generate_from_seed(&hdwallet::Seed::from_slice(&seed[2..]).unwrap()));
+    }
+
+    // known answer of the Daedalus wallet of the mnemonics below, computed
+    // with a separate implementation of the legacy wallet (cardano-sl's
+    // root key generation, V1 derivation and address payload encryption)
+    const DAEDALUS_MNEMONICS : &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    const DAEDALUS_ROOT_XPUB : &str = "415c3fdca455fc0af81f0a47fceb3f6d724f62c0f2f8f9574102e91ec887adbcd1d55468e5ecb65c821e3908a7fd84ba1a101d37c4710301a0637f5af8367342";
+    const DAEDALUS_ADDRESS : &str = "\"DdzFFzCqrhtCs3s92yyeEmGLtu6QZ7bUg6jLY6RHKQuw4RacnqGXyNMxYH4ApEHsvtVBkzTYkPR6XRfaATrH7HedooNyB3tGdCfVVVPn\"";
+    const DAEDALUS_ADDRESS_XPUB : &str = "e8dc448749c59b2f813523bcdae9b32d6026f9b7565ad76fb4e1f8ccec2bf51cc3a89e0e07de63979890333a9026a0c6901604667d67af4c3e5f0b9c117b3b43";
+
+    #[test]
+    fn random_index_wallet_daedalus_address() {
+        let mnemonics = bip39::Mnemonics::from_string(&bip39::dictionary::ENGLISH, DAEDALUS_MNEMONICS).unwrap();
+        let wallet = RandomIndexWallet::new_from_bip39_entropy(&bip39::Entropy::from_mnemonics(&mnemonics).unwrap());
+        assert_eq!(wallet.root_key.public(), hdwallet::XPub::from_hex(DAEDALUS_ROOT_XPUB).unwrap());
+
+        let addr : ExtendedAddr = serde_json::from_str(DAEDALUS_ADDRESS).unwrap();
+        let path = hdpayload::Path::new(vec![0x80000000, 0x8000002a]);
+        assert_eq!(wallet.recognize(&addr), Some(path.clone()));
+        let xpub = wallet.get_xprv(&path).public();
+        assert_eq!(xpub, hdwallet::XPub::from_hex(DAEDALUS_ADDRESS_XPUB).unwrap());
+        assert_eq!(address::ExtendedAddr::new(addr.addr_type, address::SpendingData::PubKeyASD(xpub), addr.attributes.clone()), addr);
+        assert_eq!(wallet.gen_address(&path), addr);
+    }
+
+    #[test]
+    fn random_index_wallet() {
+        let wallet = RandomIndexWallet::new_from_seed(&hdwallet::Seed::from_bytes([3;32]));
+        let other = RandomIndexWallet::new_from_seed(&hdwallet::Seed::from_bytes([4;32]));
+        let bip44_wallet : Wallet = serde_json::from_str(WALLET_JSON).unwrap();
+
+        let (path, addr) = wallet.new_address(0);
+        assert_eq!(wallet.gen_address(&path), addr);
+        assert_eq!(wallet.recognize(&addr), Some(path.clone()));
+        assert_eq!(wallet.recognize(&other.new_address(0).1), None);
+        assert_eq!(wallet.recognize(&bip44_wallet.gen_addresses(0, AddrType::External, vec![0]).unwrap()[0]), None);
+        // an address with our payload but someone else's key
+        let mut forged = other.gen_address(&path);
+        forged.attributes = addr.attributes.clone();
+        assert_eq!(wallet.recognize(&forged), None);
+
+        let addressing = bip44::Account::new(0).unwrap().external().unwrap().index(0).unwrap();
+        let mut inputs = tx::Inputs::new();
+        inputs.push(tx::Input::new(tx::TxIn::new(tx::TxId::new(&[1]), 0), tx::TxOut::new(addr, coin::Coin::new(1000000).unwrap()), addressing));
+        let outputs : tx::Outputs = serde_json::from_str(OUTPUTS_JSON).unwrap();
+        let (change_path, change_addr) = wallet.new_address(1);
+        assert_eq!(change_path.as_ref()[0], 0x80000001);
+
+        let (aux, _) = wallet.new_transaction(&inputs, &outputs, &change_addr).unwrap();
+        assert!(aux.witnesses[0].verify(&wallet.config, &inputs[0].value.address, &aux.tx));
+    }
+
+    #[test]
+    fn network_magic_addresses() {
+        let mut wallet : Wallet = serde_json::from_str(WALLET_JSON).unwrap();
+        wallet.config.requires_network_magic = config::RequiresNetworkMagic::RequiresMagic;
+        let inputs : tx::Inputs = serde_json::from_str(INPUTS_JSON).unwrap();
+        let outputs : tx::Outputs = serde_json::from_str(OUTPUTS_JSON).unwrap();
+
+        let addresses = wallet.gen_addresses(0, AddrType::Internal, vec![0, 1]).unwrap();
+        for addr in addresses.iter() {
+            assert_eq!(addr.attributes.protocol_magic, Some(wallet.config.protocol_magic));
+            assert_eq!(&ExtendedAddr::from_bytes(&addr.to_bytes()).unwrap(), addr);
+        }
+
+        // the outputs are not tagged with the protocol magic
+        match wallet.new_transaction(&inputs, &outputs, &addresses[0]) {
+            Err(Error::ValidationError(tx::validation::Error::ForeignAddress(addr))) => assert_eq!(&addr, &outputs[0].address),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
 }